@@ -0,0 +1,30 @@
+use std::process::Command;
+
+/// Runs a command at build time and returns its trimmed output, if it succeeded.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8(output.stdout).ok()?;
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+fn main() {
+    // Build metadata for `whisp --version --json`.
+    let git_hash = output("git", &["rev-parse", "--short=12", "HEAD"]).unwrap_or("unknown".into());
+    let rustc = std::env::var("RUSTC").unwrap_or("rustc".into());
+    let rustc_version = output(&rustc, &["--version"]).unwrap_or("unknown".into());
+    println!("cargo:rustc-env=WHISP_GIT_HASH={git_hash}");
+    println!(
+        "cargo:rustc-env=WHISP_TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+    println!(
+        "cargo:rustc-env=WHISP_PROFILE={}",
+        std::env::var("PROFILE").unwrap()
+    );
+    println!("cargo:rustc-env=WHISP_RUSTC={rustc_version}");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
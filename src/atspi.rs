@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Context, Result};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type;
+use zbus::MatchRule;
+
+/// `PasswordText` in the AT-SPI role enumeration.
+const ROLE_PASSWORD_TEXT: u32 = 40;
+
+/// Follows keyboard focus over the AT-SPI accessibility bus so output can refuse to
+/// type into password entries. Apps that don't report focus are never flagged.
+#[derive(Clone)]
+pub struct FocusWatch {
+    password: Arc<AtomicBool>,
+}
+
+impl FocusWatch {
+    /// Connects to the accessibility bus and follows focus changes in the background.
+    pub fn spawn() -> Result<Self> {
+        let password = Arc::new(AtomicBool::new(false));
+        let focused = password.clone();
+        let (ready_tx, ready_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let (connection, messages) = match subscribe() {
+                Ok(subscribed) => {
+                    let _ = ready_tx.send(Ok(()));
+                    subscribed
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                    return;
+                }
+            };
+            for message in messages {
+                let Ok(message) = message else {
+                    continue;
+                };
+                let header = message.header();
+                let (Some(sender), Some(path)) = (header.sender(), header.path()) else {
+                    continue;
+                };
+                let Ok((kind, gained, _)) =
+                    message.body().deserialize_unchecked::<(String, i32, i32)>()
+                else {
+                    continue;
+                };
+                if kind != "focused" || gained != 1 {
+                    continue;
+                }
+                let role = connection
+                    .call_method(
+                        Some(sender.as_str()),
+                        path.as_str(),
+                        Some("org.a11y.atspi.Accessible"),
+                        "GetRole",
+                        &(),
+                    )
+                    .and_then(|reply| reply.body().deserialize::<u32>());
+                match role {
+                    Ok(role) => focused.store(role == ROLE_PASSWORD_TEXT, Ordering::SeqCst),
+                    Err(err) => log::debug!("AT-SPI GetRole failed for {sender}{path}: {err}"),
+                }
+            }
+            log::warn!("AT-SPI focus events stopped; password fields are no longer detected");
+        });
+        ready_rx
+            .recv()
+            .map_err(|_| anyhow!("AT-SPI focus thread exited"))?
+            .context("watching focus over AT-SPI (is at-spi2-core installed?)")?;
+        Ok(Self { password })
+    }
+
+    /// Whether the widget with keyboard focus is a password entry.
+    pub fn is_password(&self) -> bool {
+        self.password.load(Ordering::SeqCst)
+    }
+}
+
+/// Connects to the accessibility bus and asks apps to report focus changes.
+fn subscribe() -> Result<(Connection, MessageIterator)> {
+    let session = Connection::session()?;
+    let address: String = session
+        .call_method(
+            Some("org.a11y.Bus"),
+            "/org/a11y/bus",
+            Some("org.a11y.Bus"),
+            "GetAddress",
+            &(),
+        )?
+        .body()
+        .deserialize()?;
+    let connection = zbus::blocking::connection::Builder::address(address.as_str())?.build()?;
+    // Apps only emit events some client registered for.
+    if let Err(err) = connection.call_method(
+        Some("org.a11y.atspi.Registry"),
+        "/org/a11y/atspi/registry",
+        Some("org.a11y.atspi.Registry"),
+        "RegisterEvent",
+        &("object:state-changed:focused",),
+    ) {
+        log::debug!("AT-SPI RegisterEvent failed: {err}");
+    }
+    let rule = MatchRule::builder()
+        .msg_type(Type::Signal)
+        .interface("org.a11y.atspi.Event.Object")?
+        .member("StateChanged")?
+        .build();
+    let messages = MessageIterator::for_match_rule(rule, &connection, Some(64))?;
+    Ok((connection, messages))
+}
@@ -1,9 +1,15 @@
 use anyhow::{bail, Context, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleRate, Stream, StreamConfig};
+use cpal::{FromSample, Sample, SampleFormat, SampleRate, SizedSample, Stream, StreamConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
 
-const SAMPLE_RATE: u32 = 16_000;
+pub const SAMPLE_RATE: u32 = 16_000;
 const MAX_BUFFER: usize = 10 * 60 * SAMPLE_RATE as usize; // 10 minutes
 
 pub struct AudioBuffer {
@@ -20,23 +26,65 @@ impl AudioBuffer {
             recording: false,
         }
     }
+
+    /// Peak level of roughly the last 100ms recorded, from 0.0 to 1.0.
+    pub fn level(&self) -> f32 {
+        let start = self.write_idx.saturating_sub(SAMPLE_RATE as usize / 10);
+        self.data[start..self.write_idx]
+            .iter()
+            .map(|s| s.abs())
+            .fold(0.0f32, f32::max)
+            .min(1.0)
+    }
 }
 
 pub struct AudioCapture {
     pub buffer: Arc<Mutex<AudioBuffer>>,
-    _stream: Stream,
+    /// The cpal stream for a single device.
+    _stream: Option<Stream>,
+    /// One `parec` per source when capturing several, fed through a `Mixer`.
+    _sources: Vec<ParecChild>,
+}
+
+/// How recordings from several `audio_devices` are combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MicMix {
+    /// Per 20ms frame, keep only the loudest source.
+    #[default]
+    Loudest,
+    /// Sum all sources.
+    Mix,
 }
 
+/// Samples per mixing frame (20ms).
+const MIX_FRAME: usize = SAMPLE_RATE as usize / 50;
+/// A source this far ahead of the slowest one stops waiting and pads the others with silence.
+const MIX_MAX_LAG: usize = SAMPLE_RATE as usize / 2;
+
+/// `audio_device` value for the monitor of the current default output.
+pub const DEFAULT_MONITOR: &str = "@DEFAULT_MONITOR@";
+
 #[derive(Debug, Clone)]
 pub struct InputSource {
     pub name: String,
     pub description: String,
 }
 
+/// Whether `name` is a sink monitor, which carries what is playing rather than a microphone.
+pub fn is_monitor(name: &str) -> bool {
+    name.ends_with(".monitor") || name == DEFAULT_MONITOR
+}
+
 impl AudioCapture {
     pub fn new(device_name: &str) -> Result<Self> {
         if !device_name.is_empty() {
-            set_default_source(device_name)?;
+            let name = resolve_source_name(device_name)?;
+            if is_monitor(&name) {
+                // Record it directly instead of making it every app's default source.
+                return Self::new_mixed(&[name], MicMix::Mix);
+            }
+            set_default_source(&name)?;
         }
         let host = cpal::default_host();
         let device = host
@@ -52,31 +100,50 @@ impl AudioCapture {
         };
 
         let buffer = Arc::new(Mutex::new(AudioBuffer::new()));
-        let buf_clone = Arc::clone(&buffer);
-
-        let stream = device.build_input_stream(
-            &config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                let mut buf = buf_clone.lock().unwrap();
-                if !buf.recording {
-                    return;
-                }
-                let remaining = MAX_BUFFER.saturating_sub(buf.write_idx);
-                let n = data.len().min(remaining);
-                if n > 0 {
-                    let start = buf.write_idx;
-                    buf.data[start..start + n].copy_from_slice(&data[..n]);
-                    buf.write_idx = start + n;
-                }
-            },
-            |err| log::error!("Audio stream error: {err}"),
-            None,
-        )?;
+        let format = input_sample_format(&device);
+        log::debug!("Input sample format: {format}");
+        let stream = match format {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, Arc::clone(&buffer))?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, Arc::clone(&buffer))?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, Arc::clone(&buffer))?,
+            other => bail!("Input device only offers unsupported sample format {other}"),
+        };
         stream.play()?;
 
         Ok(Self {
             buffer,
-            _stream: stream,
+            _stream: Some(stream),
+            _sources: Vec::new(),
+        })
+    }
+
+    /// Captures all `devices` at once (names, descriptions, or substrings as for
+    /// `audio_device`) and combines them according to `mode`.
+    pub fn new_mixed(devices: &[String], mode: MicMix) -> Result<Self> {
+        let buffer = Arc::new(Mutex::new(AudioBuffer::new()));
+        let mixer = Arc::new(Mutex::new(Mixer {
+            pending: vec![VecDeque::new(); devices.len()],
+            mode,
+        }));
+        let mut children = Vec::with_capacity(devices.len());
+        for (index, device) in devices.iter().enumerate() {
+            let name = resolve_source_name(device)?;
+            log::info!("Using audio device: {name}");
+            let (child, stdout) = spawn_parec(&name)?;
+            children.push(child);
+            let mixer = Arc::clone(&mixer);
+            let buffer = Arc::clone(&buffer);
+            read_samples(stdout, MIX_FRAME, move |samples| {
+                mixer
+                    .lock()
+                    .unwrap()
+                    .push(index, samples, &mut buffer.lock().unwrap());
+            });
+        }
+        Ok(Self {
+            buffer,
+            _stream: None,
+            _sources: children,
         })
     }
 
@@ -86,6 +153,10 @@ impl AudioCapture {
         buf.recording = true;
     }
 
+    pub fn level(&self) -> f32 {
+        self.buffer.lock().unwrap().level()
+    }
+
     pub fn stop_recording(&self) -> Vec<f32> {
         let mut buf = self.buffer.lock().unwrap();
         buf.recording = false;
@@ -107,10 +178,78 @@ impl AudioCapture {
     }
 }
 
-/// Lists PulseAudio/PipeWire input sources and their descriptions.
+/// Sample format to open the device with: f32 when offered, else a 16-bit integer
+/// format (some ALSA-bridged devices have nothing else).
+fn input_sample_format(device: &cpal::Device) -> SampleFormat {
+    let Ok(configs) = device.supported_input_configs() else {
+        return SampleFormat::F32;
+    };
+    let formats: Vec<SampleFormat> = configs.map(|range| range.sample_format()).collect();
+    [SampleFormat::F32, SampleFormat::I16, SampleFormat::U16]
+        .into_iter()
+        .find(|format| formats.contains(format))
+        .or_else(|| formats.first().copied())
+        .unwrap_or(SampleFormat::F32)
+}
+
+/// Opens the input stream for samples of type `T`, converting them to f32 as they
+/// are appended to `buffer`.
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    buffer: Arc<Mutex<AudioBuffer>>,
+) -> Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let mut buf = buffer.lock().unwrap();
+            if !buf.recording {
+                return;
+            }
+            let remaining = MAX_BUFFER.saturating_sub(buf.write_idx);
+            let n = data.len().min(remaining);
+            if n > 0 {
+                let start = buf.write_idx;
+                for (dst, &src) in buf.data[start..start + n].iter_mut().zip(data) {
+                    *dst = f32::from_sample(src);
+                }
+                buf.write_idx = start + n;
+            }
+        },
+        |err| log::error!("Audio stream error: {err}"),
+        None,
+    )?;
+    Ok(stream)
+}
+
+/// Lists PulseAudio/PipeWire input sources and their descriptions, leaving out
+/// sink monitors.
 ///
 /// `name` is the value to use in config `audio_device`.
 pub fn list_input_sources() -> Result<Vec<InputSource>> {
+    let sources: Vec<InputSource> = list_sources()?
+        .into_iter()
+        .filter(|source| !is_monitor(&source.name))
+        .collect();
+    if sources.is_empty() {
+        bail!("No input sources found via pactl");
+    }
+    Ok(sources)
+}
+
+/// Lists sink monitors: sources that carry what an output device is playing.
+pub fn list_monitor_sources() -> Result<Vec<InputSource>> {
+    Ok(list_sources()?
+        .into_iter()
+        .filter(|source| is_monitor(&source.name))
+        .collect())
+}
+
+fn list_sources() -> Result<Vec<InputSource>> {
     let output = std::process::Command::new("pactl")
         .args(["-f", "json", "list", "sources"])
         .output()
@@ -124,7 +263,7 @@ pub fn list_input_sources() -> Result<Vec<InputSource>> {
     let mut result = Vec::new();
     for source in sources {
         let name = source["name"].as_str().unwrap_or("").trim();
-        if name.is_empty() || name.contains(".monitor") {
+        if name.is_empty() {
             continue;
         }
 
@@ -139,12 +278,294 @@ pub fn list_input_sources() -> Result<Vec<InputSource>> {
             description: description.to_string(),
         });
     }
+    Ok(result)
+}
 
-    if result.is_empty() {
-        bail!("No input sources found via pactl");
+/// Maps a configured `audio_device` to a source name. Exact names are used as-is;
+/// otherwise the value is matched case-insensitively against descriptions, then as a
+/// substring of names and descriptions ("Blue Yeti"). Sink monitors are only
+/// matched when the value mentions "monitor", so microphone names stay unambiguous.
+fn resolve_source_name(device: &str) -> Result<String> {
+    if device == DEFAULT_MONITOR {
+        return Ok(format!("{}.monitor", default_sink()?));
     }
+    let monitor = device.to_lowercase().contains("monitor");
+    let sources = if monitor {
+        list_monitor_sources()
+    } else {
+        list_input_sources()
+    };
+    let sources = match sources {
+        Ok(sources) => sources,
+        // Without a source list only exact names can work; let pactl judge it.
+        Err(_) => return Ok(device.to_string()),
+    };
+    let source = match_source(&sources, device)?;
+    if source.name != device {
+        log::info!("audio_device '{device}' matched {}", source.name);
+    }
+    Ok(source.name.clone())
+}
 
-    Ok(result)
+fn match_source<'a>(sources: &'a [InputSource], query: &str) -> Result<&'a InputSource> {
+    if let Some(source) = sources.iter().find(|source| source.name == query) {
+        return Ok(source);
+    }
+    let lowered = query.to_lowercase();
+    let described: Vec<&InputSource> = sources
+        .iter()
+        .filter(|source| source.description.to_lowercase() == lowered)
+        .collect();
+    if let [source] = described.as_slice() {
+        return Ok(source);
+    }
+    let matches: Vec<&InputSource> = sources
+        .iter()
+        .filter(|source| {
+            source.name.to_lowercase().contains(&lowered)
+                || source.description.to_lowercase().contains(&lowered)
+        })
+        .collect();
+    match matches.as_slice() {
+        [source] => Ok(source),
+        [] => bail!(
+            "No input source matches audio_device '{query}'. Run `whisp --list-audio-devices` to see them."
+        ),
+        _ => bail!(
+            "audio_device '{query}' matches several sources; use a longer name:\n  {}",
+            matches
+                .iter()
+                .map(|source| format!("{}  ({})", source.name, source.description))
+                .collect::<Vec<_>>()
+                .join("\n  ")
+        ),
+    }
+}
+
+/// Combines per-source sample queues into frames for the shared `AudioBuffer`.
+struct Mixer {
+    pending: Vec<VecDeque<f32>>,
+    mode: MicMix,
+}
+
+impl Mixer {
+    fn push(&mut self, source: usize, samples: &[f32], buffer: &mut AudioBuffer) {
+        if !buffer.recording {
+            self.pending.iter_mut().for_each(VecDeque::clear);
+            return;
+        }
+        self.pending[source].extend(samples);
+        loop {
+            let ready = self.pending.iter().all(|queue| queue.len() >= MIX_FRAME);
+            let lagging = self.pending.iter().any(|queue| queue.len() >= MIX_MAX_LAG);
+            if !ready && !lagging {
+                return;
+            }
+            let frames: Vec<Vec<f32>> = self
+                .pending
+                .iter_mut()
+                .map(|queue| {
+                    let mut frame: Vec<f32> = queue.drain(..MIX_FRAME.min(queue.len())).collect();
+                    frame.resize(MIX_FRAME, 0.0);
+                    frame
+                })
+                .collect();
+            let mixed = mix_frame(&frames, self.mode);
+            let start = buffer.write_idx;
+            let n = mixed.len().min(MAX_BUFFER - start);
+            buffer.data[start..start + n].copy_from_slice(&mixed[..n]);
+            buffer.write_idx = start + n;
+        }
+    }
+}
+
+fn mix_frame(frames: &[Vec<f32>], mode: MicMix) -> Vec<f32> {
+    match mode {
+        MicMix::Loudest => {
+            let energy = |frame: &Vec<f32>| frame.iter().map(|s| s * s).sum::<f32>();
+            frames
+                .iter()
+                .max_by(|a, b| energy(a).total_cmp(&energy(b)))
+                .cloned()
+                .unwrap_or_default()
+        }
+        MicMix::Mix => (0..MIX_FRAME)
+            .map(|i| frames.iter().map(|frame| frame[i]).sum())
+            .collect(),
+    }
+}
+
+/// A running `parec`, killed when dropped.
+struct ParecChild(Child);
+
+impl Drop for ParecChild {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// Starts recording `name` (a source name or "@DEFAULT_SOURCE@") as 16kHz mono f32.
+fn spawn_parec(name: &str) -> Result<(ParecChild, ChildStdout)> {
+    let mut child = Command::new("parec")
+        .arg(format!("--device={name}"))
+        .args(["--format=float32le", "--channels=1", "--latency-msec=50"])
+        .arg(format!("--rate={SAMPLE_RATE}"))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run parec. Install pulseaudio-utils or pipewire-pulse.")?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    Ok((ParecChild(child), stdout))
+}
+
+/// Reads `parec` output on a new thread, `chunk` samples at a time, until it exits.
+fn read_samples(
+    mut stdout: ChildStdout,
+    chunk: usize,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+) {
+    thread::spawn(move || {
+        let mut bytes = vec![0u8; chunk * 4];
+        let mut samples = vec![0f32; chunk];
+        while stdout.read_exact(&mut bytes).is_ok() {
+            for (sample, b) in samples.iter_mut().zip(bytes.chunks_exact(4)) {
+                *sample = f32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+            }
+            on_samples(&samples);
+        }
+    });
+}
+
+/// Continuous capture of one source through `parec`, stopped when dropped.
+pub struct SourceStream {
+    _child: ParecChild,
+}
+
+impl SourceStream {
+    /// Calls `on_samples` with each 100ms of `device` (matched as for `audio_device`;
+    /// empty for the default source).
+    pub fn spawn(device: &str, on_samples: impl FnMut(&[f32]) + Send + 'static) -> Result<Self> {
+        let name = if device.is_empty() {
+            "@DEFAULT_SOURCE@".to_string()
+        } else {
+            resolve_source_name(device)?
+        };
+        log::info!("Capturing {name}");
+        let (child, stdout) = spawn_parec(&name)?;
+        read_samples(stdout, SAMPLE_RATE as usize / 10, on_samples);
+        Ok(Self { _child: child })
+    }
+}
+
+/// Live peak level of one PulseAudio/PipeWire source, read through `parec` so several
+/// sources can be watched at once without changing the default source.
+pub struct SourceMonitor {
+    _child: ParecChild,
+    /// Latest peak as f32 bits.
+    level: Arc<AtomicU32>,
+}
+
+impl SourceMonitor {
+    /// `name` is a source name from `list_input_sources`, or "@DEFAULT_SOURCE@".
+    pub fn spawn(name: &str) -> Result<Self> {
+        let (child, stdout) = spawn_parec(name)?;
+        let level = Arc::new(AtomicU32::new(0));
+        let shared = Arc::clone(&level);
+        // 100ms of samples per reading, matching AudioBuffer::level.
+        read_samples(stdout, SAMPLE_RATE as usize / 10, move |samples| {
+            let peak = samples
+                .iter()
+                .map(|s| s.abs())
+                .fold(0.0f32, f32::max)
+                .min(1.0);
+            shared.store(peak.to_bits(), Ordering::Relaxed);
+        });
+        Ok(Self {
+            _child: child,
+            level,
+        })
+    }
+
+    /// Peak of the last 100ms, from 0.0 to 1.0.
+    pub fn level(&self) -> f32 {
+        f32::from_bits(self.level.load(Ordering::Relaxed))
+    }
+}
+
+/// Source name used when whisp loads the echo-cancel module itself.
+const ECHO_CANCEL_SOURCE: &str = "whisp_echo_cancel";
+
+/// An echo-cancelled source to capture from, so speaker output is removed from the
+/// microphone signal. Unloads the module on drop if whisp loaded it.
+pub struct EchoCancel {
+    pub source: String,
+    module: Option<String>,
+}
+
+impl EchoCancel {
+    /// Reuses an existing echo-cancel source, or loads `module-echo-cancel` (supported
+    /// by PulseAudio and pipewire-pulse) on top of `master` (empty for the default source).
+    pub fn setup(master: &str) -> Result<Self> {
+        if let Some(existing) = list_input_sources()?.into_iter().find(|source| {
+            source.name.contains("echo-cancel") || source.name.contains("echo_cancel")
+        }) {
+            log::info!("Using existing echo-cancelled source {}", existing.name);
+            return Ok(Self {
+                source: existing.name,
+                module: None,
+            });
+        }
+
+        let mut command = std::process::Command::new("pactl");
+        command.args([
+            "load-module",
+            "module-echo-cancel",
+            "aec_method=webrtc",
+            &format!("source_name={ECHO_CANCEL_SOURCE}"),
+        ]);
+        if !master.is_empty() {
+            command.arg(format!("source_master={}", resolve_source_name(master)?));
+        }
+        let output = command
+            .output()
+            .context("Failed to run pactl load-module")?;
+        if !output.status.success() {
+            bail!(
+                "Loading module-echo-cancel failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let module = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        log::info!("Loaded module-echo-cancel (module {module})");
+        Ok(Self {
+            source: ECHO_CANCEL_SOURCE.to_string(),
+            module: Some(module),
+        })
+    }
+}
+
+impl Drop for EchoCancel {
+    fn drop(&mut self) {
+        if let Some(module) = &self.module {
+            let _ = std::process::Command::new("pactl")
+                .args(["unload-module", module])
+                .status();
+        }
+    }
+}
+
+/// Name of the PulseAudio default sink, whose `.monitor` source captures what's playing.
+fn default_sink() -> Result<String> {
+    let output = std::process::Command::new("pactl")
+        .arg("get-default-sink")
+        .output()
+        .context("Failed to run pactl get-default-sink")?;
+    let sink = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || sink.is_empty() {
+        bail!("pactl found no default output to capture from");
+    }
+    Ok(sink)
 }
 
 /// Set the PulseAudio default source so cpal picks it up.
@@ -158,3 +579,68 @@ pub fn set_default_source(name: &str) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{match_source, mix_frame, InputSource, MicMix, MIX_FRAME};
+
+    #[test]
+    fn mixes_or_picks_the_loudest_frame() {
+        let quiet = vec![0.1; MIX_FRAME];
+        let loud = vec![-0.5; MIX_FRAME];
+        let frames = [quiet, loud.clone()];
+        assert_eq!(mix_frame(&frames, MicMix::Loudest), loud);
+        assert!(mix_frame(&frames, MicMix::Mix)
+            .iter()
+            .all(|s| (s + 0.4).abs() < 1e-6));
+    }
+
+    fn sources() -> Vec<InputSource> {
+        [
+            (
+                "alsa_input.usb-Blue_Yeti-00.analog-stereo",
+                "Yeti Stereo Microphone",
+            ),
+            (
+                "alsa_input.pci-0000_00_1f.3.analog-stereo",
+                "Built-in Audio Analog Stereo",
+            ),
+            ("bluez_input.headset", "WH-1000XM4 Headset"),
+        ]
+        .into_iter()
+        .map(|(name, description)| InputSource {
+            name: name.into(),
+            description: description.into(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn matches_exact_names_descriptions_and_substrings() {
+        let sources = sources();
+        let name = |query| match_source(&sources, query).unwrap().name.as_str();
+        assert_eq!(name("bluez_input.headset"), "bluez_input.headset");
+        assert_eq!(name("yeti stereo microphone"), sources[0].name);
+        assert_eq!(name("Blue_Yeti"), sources[0].name);
+        assert_eq!(name("built-in"), sources[1].name);
+    }
+
+    #[test]
+    fn recognizes_monitor_sources() {
+        assert!(super::is_monitor(
+            "alsa_output.pci-0000_00_1f.3.analog-stereo.monitor"
+        ));
+        assert!(super::is_monitor(super::DEFAULT_MONITOR));
+        assert!(!super::is_monitor(
+            "alsa_input.usb-Blue_Yeti-00.analog-stereo"
+        ));
+    }
+
+    #[test]
+    fn lists_candidates_when_ambiguous() {
+        let err = match_source(&sources(), "stereo").unwrap_err().to_string();
+        assert!(err.contains("Yeti Stereo Microphone"), "{err}");
+        assert!(err.contains("Built-in Audio"), "{err}");
+        assert!(match_source(&sources(), "webcam").is_err());
+    }
+}
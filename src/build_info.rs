@@ -0,0 +1,58 @@
+use serde::Serialize;
+
+/// What this binary was built from and with, for bug reports (`whisp --version --json`).
+#[derive(Debug, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub target: &'static str,
+    pub profile: &'static str,
+    pub rustc: &'static str,
+    /// Speech recognition backends this build can run (`backend = ...`).
+    pub backends: Vec<&'static str>,
+    pub features: Features,
+}
+
+/// Optional cargo features compiled into this binary.
+#[derive(Debug, Serialize)]
+pub struct Features {
+    /// onnxruntime CUDA provider (`provider = "cuda"`).
+    pub cuda: bool,
+    /// xdg-desktop-portal hotkey and typing (`portal = true`).
+    pub portal: bool,
+    /// AT-SPI password field detection (`password_guard = true`).
+    pub atspi: bool,
+    /// libvosk recognizer (`backend = "vosk"`).
+    pub vosk: bool,
+    /// OBS live captions (`[obs] enabled = true`).
+    pub obs: bool,
+    /// `whisp --settings` window.
+    pub settings_gui: bool,
+}
+
+pub fn current() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_hash: env!("WHISP_GIT_HASH"),
+        target: env!("WHISP_TARGET"),
+        profile: env!("WHISP_PROFILE"),
+        rustc: env!("WHISP_RUSTC"),
+        backends: backends(),
+        features: Features {
+            cuda: cfg!(feature = "cuda"),
+            portal: cfg!(feature = "portal"),
+            atspi: cfg!(feature = "atspi"),
+            vosk: cfg!(feature = "vosk"),
+            obs: cfg!(feature = "obs"),
+            settings_gui: cfg!(feature = "settings-gui"),
+        },
+    }
+}
+
+fn backends() -> Vec<&'static str> {
+    let mut backends = vec!["sherpa", "cloud"];
+    if cfg!(feature = "vosk") {
+        backends.push("vosk");
+    }
+    backends
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::speech::{ReadBack, TtsEngine};
+use crate::{uinput, util};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Fail,
+    /// Not needed with the current config.
+    Skip,
+}
+
+/// One result of `whisp --check`, as reported by `--check --json`.
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Skip,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+}
+
+/// Checks for a command the config needs (`needed_for` is None when it isn't).
+fn command(name: &'static str, package: &str, needed_for: Option<&str>) -> Check {
+    let Some(needed_for) = needed_for else {
+        return Check::skip(name, "not needed with this config");
+    };
+    if util::has_command(name) {
+        Check::ok(name, format!("found (needed for {needed_for})"))
+    } else {
+        Check::fail(
+            name,
+            format!("{name} is required when {needed_for} is set"),
+            format!("Install {package}"),
+        )
+    }
+}
+
+/// Device access and helper programs the config depends on.
+pub fn runtime_deps(config: &Config) -> Vec<Check> {
+    let uinput = if config.portal {
+        Check::skip("uinput", "typing goes through the RemoteDesktop portal")
+    } else if uinput::is_available() {
+        Check::ok("uinput", "/dev/uinput is writable")
+    } else {
+        Check::fail(
+            "uinput",
+            "/dev/uinput is not accessible",
+            "Ensure user is in the 'input' group (or 'uinput' group on some distros)",
+        )
+    };
+
+    let read_back = config.read_back != ReadBack::Off;
+    let spd_say = if config.announce {
+        Some("announce")
+    } else if read_back && config.tts == TtsEngine::SpeechDispatcher {
+        Some("read_back")
+    } else {
+        None
+    };
+    let piper = (read_back && config.tts == TtsEngine::Piper).then_some("tts = \"piper\"");
+    let pactl = if config.echo_cancel {
+        Some("echo_cancel")
+    } else if !config.audio_device.is_empty() {
+        Some("audio_device")
+    } else {
+        None
+    };
+    let parec = if !config.audio_devices.is_empty() {
+        Some("audio_devices")
+    } else if config.audio_device.to_lowercase().contains("monitor") {
+        Some("a monitor audio_device")
+    } else {
+        None
+    };
+
+    vec![
+        uinput,
+        command("pactl", "pulseaudio-utils or pipewire-pulse", pactl),
+        command("parec", "pulseaudio-utils or pipewire-pulse", parec),
+        command("spd-say", "speech-dispatcher", spd_say),
+        command("piper", "piper", piper),
+        command("aplay", "alsa-utils", piper),
+    ]
+}
+
+/// Prints `checks` as JSON and fails if any of them did.
+pub fn report_json(checks: &[Check]) -> Result<()> {
+    #[derive(Serialize)]
+    struct Report<'a> {
+        ok: bool,
+        checks: &'a [Check],
+    }
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    let report = Report {
+        ok: failed == 0,
+        checks,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if failed > 0 {
+        bail!("{failed} check(s) failed");
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio::SAMPLE_RATE;
+
+/// Longest a single request (or AssemblyAI's whole upload-and-poll cycle) may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Hosted speech-to-text API for `backend = "cloud"`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Service {
+    /// Deepgram's pre-recorded `/v1/listen` endpoint.
+    #[default]
+    Deepgram,
+    /// AssemblyAI's upload, transcript, and poll endpoints.
+    AssemblyAi,
+}
+
+impl fmt::Display for Service {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Service::Deepgram => "deepgram",
+            Service::AssemblyAi => "assemblyai",
+        })
+    }
+}
+
+impl Service {
+    pub fn default_endpoint(self) -> &'static str {
+        match self {
+            Service::Deepgram => "https://api.deepgram.com/v1/listen",
+            Service::AssemblyAi => "https://api.assemblyai.com/v2",
+        }
+    }
+
+    fn adapter(self) -> &'static dyn Adapter {
+        match self {
+            Service::Deepgram => &Deepgram,
+            Service::AssemblyAi => &AssemblyAi,
+        }
+    }
+}
+
+/// Where recordings go and how to authenticate. The key is never printed.
+#[derive(Clone)]
+pub struct Settings {
+    pub service: Service,
+    pub endpoint: String,
+    pub api_key: String,
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("service", &self.service)
+            .field("endpoint", &self.endpoint)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Speaks one service's HTTP API: sends the recording, then reads the transcript
+/// out of the final response.
+trait Adapter: Sync {
+    fn send(&self, agent: &ureq::Agent, settings: &Settings, wav: Vec<u8>) -> Result<Value>;
+    fn transcript(&self, response: &Value) -> Result<String>;
+}
+
+struct Deepgram;
+
+impl Adapter for Deepgram {
+    fn send(&self, agent: &ureq::Agent, settings: &Settings, wav: Vec<u8>) -> Result<Value> {
+        let request = agent
+            .post(&settings.endpoint)
+            .query("smart_format", "true")
+            .set("Authorization", &format!("Token {}", settings.api_key))
+            .set("Content-Type", "audio/wav");
+        read_json(request.send_bytes(&wav))
+    }
+
+    fn transcript(&self, response: &Value) -> Result<String> {
+        response
+            .pointer("/results/channels/0/alternatives/0/transcript")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Deepgram response has no transcript"))
+    }
+}
+
+struct AssemblyAi;
+
+impl Adapter for AssemblyAi {
+    fn send(&self, agent: &ureq::Agent, settings: &Settings, wav: Vec<u8>) -> Result<Value> {
+        let started = Instant::now();
+        let base = settings.endpoint.trim_end_matches('/');
+        let uploaded = read_json(
+            agent
+                .post(&format!("{base}/upload"))
+                .set("Authorization", &settings.api_key)
+                .send_bytes(&wav),
+        )?;
+        let audio_url = uploaded["upload_url"]
+            .as_str()
+            .ok_or_else(|| anyhow!("AssemblyAI upload returned no upload_url"))?;
+        let created = read_json(
+            agent
+                .post(&format!("{base}/transcript"))
+                .set("Authorization", &settings.api_key)
+                .set("Content-Type", "application/json")
+                .send_string(&json!({ "audio_url": audio_url }).to_string()),
+        )?;
+        let id = created["id"]
+            .as_str()
+            .ok_or_else(|| anyhow!("AssemblyAI returned no transcript id"))?;
+        loop {
+            let status = read_json(
+                agent
+                    .get(&format!("{base}/transcript/{id}"))
+                    .set("Authorization", &settings.api_key)
+                    .call(),
+            )?;
+            match status["status"].as_str() {
+                Some("completed") => return Ok(status),
+                Some("error") => bail!(
+                    "AssemblyAI failed: {}",
+                    status["error"].as_str().unwrap_or("unknown error")
+                ),
+                _ if started.elapsed() > REQUEST_TIMEOUT => {
+                    bail!("AssemblyAI did not finish within {REQUEST_TIMEOUT:?}")
+                }
+                _ => thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    fn transcript(&self, response: &Value) -> Result<String> {
+        Ok(response["text"].as_str().unwrap_or_default().to_string())
+    }
+}
+
+fn read_json(response: Result<ureq::Response, ureq::Error>) -> Result<Value> {
+    match response {
+        Ok(response) => {
+            let body = response.into_string().context("reading response")?;
+            serde_json::from_str(&body).context("parsing response")
+        }
+        Err(ureq::Error::Status(code, response)) => {
+            let body = response.into_string().unwrap_or_default();
+            bail!("HTTP {code}: {}", body.trim())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Sends recordings to a hosted speech-to-text service.
+pub struct Client {
+    settings: Settings,
+    agent: ureq::Agent,
+}
+
+impl Client {
+    pub fn new(settings: Settings) -> Self {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+        Self { settings, agent }
+    }
+
+    pub fn transcribe(&self, audio: &[f32]) -> Result<String> {
+        let adapter = self.settings.service.adapter();
+        let response = adapter
+            .send(&self.agent, &self.settings, wav(audio))
+            .with_context(|| format!("sending audio to {}", self.settings.service))?;
+        adapter.transcript(&response)
+    }
+}
+
+/// 16-bit mono PCM WAV of `audio`.
+fn wav(audio: &[f32]) -> Vec<u8> {
+    let data_len = (audio.len() * 2) as u32;
+    let mut out = Vec::with_capacity(44 + audio.len() * 2);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    out.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in audio {
+        let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wav, Adapter, Deepgram};
+    use serde_json::json;
+
+    #[test]
+    fn encodes_pcm_wav() {
+        let bytes = wav(&[0.0, 1.0, -1.0]);
+        assert_eq!(bytes.len(), 44 + 6);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[44..], &[0, 0, 0xff, 0x7f, 0x01, 0x80]);
+    }
+
+    #[test]
+    fn reads_deepgram_transcript() {
+        let response = json!({
+            "results": { "channels": [{ "alternatives": [{ "transcript": "Hello there." }] }] }
+        });
+        assert_eq!(Deepgram.transcript(&response).unwrap(), "Hello there.");
+        assert!(Deepgram.transcript(&json!({})).is_err());
+    }
+}
@@ -1,40 +1,244 @@
 use anyhow::{anyhow, bail, Context, Result};
 use hf_hub::{Repo, RepoType};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::Duration;
 
+use crate::audio::MicMix;
+use crate::cloud;
+use crate::history::{Encryption, Secret};
 use crate::hotkey;
+use crate::keymap::Keymap;
+use crate::logging;
+use crate::output;
+use crate::postprocess::{self, Breaks, Censor, DictationMode};
+use crate::speech::{ReadBack, TtsEngine, Voice};
+use crate::transcriber::{Backend, Provider, QueuePolicy};
 
 const DEFAULT_CONFIG: &str = include_str!("../config.example.toml");
 const MODEL_DOWNLOAD_ATTEMPTS: usize = 3;
+/// Environment variable holding the passphrase for `[history] encryption = "passphrase"`.
+const PASSPHRASE_ENV: &str = "WHISP_HISTORY_PASSPHRASE";
+/// Environment variable holding the API key for `backend = "cloud"`; overrides `[cloud] api_key_file`.
+const CLOUD_KEY_ENV: &str = "WHISP_CLOUD_API_KEY";
 
+/// Numeric precision of a preset's model weights.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Quantized weights: smallest download, fastest on the CPU.
+    #[default]
+    Int8,
+    /// Half-precision weights: slightly more accurate, best paired with `provider = "cuda"`.
+    Fp16,
+}
+
+impl std::fmt::Display for Precision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Precision::Int8 => "int8",
+            Precision::Fp16 => "fp16",
+        })
+    }
+}
+
+/// Whether a downloaded model follows its upstream repo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelUpdate {
+    /// Keep using the commit first downloaded until `whisp --update-model`.
+    #[default]
+    Pinned,
+    /// Check for a newer commit on every start and download it.
+    Auto,
+}
+
+/// One downloadable file set of a preset.
 #[derive(Clone, Copy)]
-struct ModelPreset {
+struct ModelVariant {
+    precision: Precision,
     repo: &'static str,
     revision: &'static str,
     files: &'static [&'static str],
+    /// Approximate total size of `files`, for `--list-models`.
+    download_mb: u32,
+}
+
+/// Model architecture of a preset, which decides how its files are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelKind {
+    /// NeMo transducer: encoder, decoder, joiner, tokens.
+    Transducer,
+    /// Whisper encoder-decoder: encoder, decoder, tokens. An empty `language`
+    /// lets the model detect it.
+    Whisper { language: &'static str },
+    /// Moonshine: preprocessor, encoder, uncached decoder, cached decoder, tokens.
+    Moonshine,
+}
+
+#[derive(Clone, Copy)]
+struct ModelPreset {
+    description: &'static str,
+    languages: &'static str,
+    kind: ModelKind,
+    variants: &'static [ModelVariant],
+}
+
+impl ModelPreset {
+    fn variant(&self, precision: Precision) -> Option<&'static ModelVariant> {
+        self.variants.iter().find(|v| v.precision == precision)
+    }
+
+    fn precisions(&self) -> Vec<String> {
+        self.variants
+            .iter()
+            .map(|v| v.precision.to_string())
+            .collect()
+    }
+}
+
+/// One preset variant as shown by `whisp --list-models`.
+pub struct ModelListing {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub languages: &'static str,
+    pub precision: Precision,
+    pub download_mb: u32,
+    /// Bytes on disk when every file of the variant is in the Hugging Face cache.
+    pub cached_bytes: Option<u64>,
+}
+
+/// Every preset variant with its cache status. Only reads the local cache.
+pub fn list_models() -> Vec<ModelListing> {
+    let cache = hf_hub::Cache::default();
+    let mut listings = Vec::new();
+    for name in available_presets() {
+        let preset = resolve_preset(name).expect("available presets resolve");
+        for variant in preset.variants {
+            let repo = cache.repo(Repo::with_revision(
+                variant.repo.to_string(),
+                RepoType::Model,
+                variant.revision.to_string(),
+            ));
+            let cached_bytes = variant
+                .files
+                .iter()
+                .map(|file| {
+                    let path = repo.get(file)?;
+                    fs::metadata(path).ok().map(|meta| meta.len())
+                })
+                .sum::<Option<u64>>();
+            listings.push(ModelListing {
+                name,
+                description: preset.description,
+                languages: preset.languages,
+                precision: variant.precision,
+                download_mb: variant.download_mb,
+                cached_bytes,
+            });
+        }
+    }
+    listings
 }
 
 pub fn available_presets() -> &'static [&'static str] {
-    &["parakeet-tdt-0.6b-v3"]
+    &[
+        "parakeet-tdt-0.6b-v3",
+        "whisper-large-v3-turbo",
+        "moonshine-tiny-en",
+        "moonshine-base-en",
+    ]
 }
 
+/// Files of the sherpa-onnx Moonshine int8 exports, which share their names across sizes.
+const MOONSHINE_INT8_FILES: &[&str] = &[
+    "preprocess.onnx",
+    "encode.int8.onnx",
+    "uncached_decode.int8.onnx",
+    "cached_decode.int8.onnx",
+    "tokens.txt",
+];
+
 /// Named model presets.
 fn resolve_preset(name: &str) -> Option<ModelPreset> {
     Some(match name {
         "parakeet-tdt-0.6b-v3" => ModelPreset {
-            repo: "csukuangfj/sherpa-onnx-nemo-parakeet-tdt-0.6b-v3-int8",
-            revision: "main",
-            files: &[
-                "encoder.int8.onnx",
-                "decoder.int8.onnx",
-                "joiner.int8.onnx",
-                "tokens.txt",
+            description:
+                "NVIDIA Parakeet TDT 0.6B v3 transducer with punctuation and capitalization",
+            languages:
+                "25 European languages (bg, cs, da, de, el, en, es, et, fi, fr, hr, hu, it, \
+                        lt, lv, mt, nl, pl, pt, ro, ru, sk, sl, sv, uk)",
+            kind: ModelKind::Transducer,
+            variants: &[
+                ModelVariant {
+                    precision: Precision::Int8,
+                    repo: "csukuangfj/sherpa-onnx-nemo-parakeet-tdt-0.6b-v3-int8",
+                    revision: "main",
+                    files: &[
+                        "encoder.int8.onnx",
+                        "decoder.int8.onnx",
+                        "joiner.int8.onnx",
+                        "tokens.txt",
+                    ],
+                    download_mb: 670,
+                },
+                ModelVariant {
+                    precision: Precision::Fp16,
+                    repo: "csukuangfj/sherpa-onnx-nemo-parakeet-tdt-0.6b-v3-fp16",
+                    revision: "main",
+                    files: &[
+                        "encoder.fp16.onnx",
+                        "decoder.fp16.onnx",
+                        "joiner.fp16.onnx",
+                        "tokens.txt",
+                    ],
+                    download_mb: 1250,
+                },
             ],
         },
+        "whisper-large-v3-turbo" => ModelPreset {
+            description: "OpenAI Whisper large-v3-turbo (4-layer decoder); audio is decoded in 30s windows",
+            languages: "99 languages, detected automatically",
+            kind: ModelKind::Whisper { language: "" },
+            variants: &[ModelVariant {
+                precision: Precision::Int8,
+                repo: "csukuangfj/sherpa-onnx-whisper-turbo",
+                revision: "main",
+                files: &[
+                    "turbo-encoder.int8.onnx",
+                    "turbo-decoder.int8.onnx",
+                    "turbo-tokens.txt",
+                ],
+                download_mb: 1000,
+            }],
+        },
+        "moonshine-tiny-en" => ModelPreset {
+            description: "Useful Sensors Moonshine tiny; compute scales with clip length, so short utterances are very fast",
+            languages: "en",
+            kind: ModelKind::Moonshine,
+            variants: &[ModelVariant {
+                precision: Precision::Int8,
+                repo: "csukuangfj/sherpa-onnx-moonshine-tiny-en-int8",
+                revision: "main",
+                files: MOONSHINE_INT8_FILES,
+                download_mb: 120,
+            }],
+        },
+        "moonshine-base-en" => ModelPreset {
+            description: "Useful Sensors Moonshine base; more accurate than tiny, still fast on short utterances",
+            languages: "en",
+            kind: ModelKind::Moonshine,
+            variants: &[ModelVariant {
+                precision: Precision::Int8,
+                repo: "csukuangfj/sherpa-onnx-moonshine-base-en-int8",
+                revision: "main",
+                files: MOONSHINE_INT8_FILES,
+                download_mb: 280,
+            }],
+        },
         _ => return None,
     })
 }
@@ -43,19 +247,213 @@ fn resolve_preset(name: &str) -> Option<ModelPreset> {
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub hotkey: String,
+    /// Keys that must also be held for `hotkey` to start a recording, e.g. ["leftmeta"].
+    pub hotkey_modifiers: Vec<String>,
+    /// Also listen on virtual input devices, for remappers like keyd that type through one.
+    pub virtual_keyboards: bool,
+    /// Only listen on these keyboards, each given by its evdev name, phys or uniq.
+    pub keyboards: Vec<String>,
+    /// Optional key that erases the most recently typed transcription.
+    pub undo_hotkey: String,
+    /// Optional key that toggles numeric dictation on and off.
+    pub numeric_hotkey: String,
+    /// Optional key that types the last transcription again after typing it failed.
+    pub retry_hotkey: String,
     pub audio_device: String,
+    /// Several sources captured at once instead of `audio_device`.
+    pub audio_devices: Vec<String>,
+    /// How `audio_devices` are combined: "loudest" or "mix".
+    pub mic_mix: MicMix,
+    /// Capture through an echo-cancelled source so speaker audio isn't transcribed.
+    pub echo_cancel: bool,
+    /// Use xdg-desktop-portal for the hotkey and typing instead of evdev and /dev/uinput.
+    pub portal: bool,
+    /// Ignore hotkeys while logind reports the session as locked.
+    pub ignore_when_locked: bool,
+    /// Refuse to type while a password field has focus, detected over AT-SPI.
+    pub password_guard: bool,
     pub debounce_ms: u64,
+    /// Pressing the hotkey again within this long of releasing it continues the
+    /// same utterance instead of starting a new one. 0 disables merging.
+    pub merge_within_ms: u64,
+    /// "hold" for push-to-talk, "tap-toggle" to also start and stop with quick taps,
+    /// or "toggle" to start and stop with presses only.
+    pub recording_mode: hotkey::RecordingMode,
+    /// Presses shorter than this count as taps in "tap-toggle" mode.
+    pub hold_threshold_ms: u64,
+    /// Characters typed per burst; 0 types each transcription in one go.
+    pub type_chunk_size: usize,
+    /// Pause between typing bursts.
+    pub type_chunk_delay_ms: u64,
+    /// Longest transcription typed without intervention; 0 disables the limit.
+    pub max_output_chars: usize,
+    /// "truncate" or "confirm" (wait for the retry key) past `max_output_chars`.
+    pub on_long_output: output::LongOutput,
+    /// Minimum pause between consecutive transcriptions being typed.
+    pub emit_spacing_ms: u64,
+    /// Type characters outside ASCII via Ctrl+Shift+U (GTK/IBus Unicode entry).
+    pub unicode_input: bool,
+    /// Desktop keyboard layout for typing: "us", "de", "fr", or a compiled XKB keymap path.
+    pub keymap: String,
+    /// The desktop's compose key (evdev name), used to type accented letters; empty disables.
+    pub compose_key: String,
+    /// Wraps each transcription, e.g. "- {text}\n" for bullet lists.
+    pub output_template: String,
+    /// Type line breaks and tabs as spaces and drop other control characters, so
+    /// dictating into a shell never presses Enter mid-command.
+    pub terminal_safe: bool,
+    /// Line breaks in transcriptions: "keep" (typed as Enter), "space", or "remove".
+    pub newlines: Breaks,
+    /// Tabs in transcriptions: "keep", "space", or "remove".
+    pub tabs: Breaks,
+    /// Join back-to-back utterances with one space and consistent capitalization.
+    pub smart_join: bool,
+    /// Type each segment of a long Whisper recording as soon as it is decoded.
+    pub progressive_output: bool,
+    /// How transcriptions are interpreted before typing ("text", "spelling", "numeric", "markdown", "code", "phrases").
+    pub dictation_mode: DictationMode,
+    /// The only utterances typed with `dictation_mode = "phrases"`.
+    pub phrases: Vec<String>,
+    /// Interpret spoken commands ("all caps", "no space", "send it", "scratch that").
+    pub voice_commands: bool,
+    /// Replace spoken emoji names ("thumbs up emoji") with the emoji itself.
+    pub emoji: bool,
+    /// Drop non-speech annotations ("[MUSIC]", "[BLANK_AUDIO]", "(laughs)", "(applause)", "(inaudible)", "♪").
+    pub strip_non_speech: bool,
+    /// Add capitalization and sentence punctuation when the model outputs none.
+    pub punctuate: bool,
+    /// Mask ("mask") or drop ("remove") profanity; "off" types it as transcribed.
+    pub censor: Censor,
+    /// Keep transcription text out of logs, the dashboard, and history.
+    pub privacy: bool,
+    /// Announce recording state and transcriptions through speech-dispatcher.
+    pub announce: bool,
+    /// Speak each transcription "before" or "after" typing it; "off" stays silent.
+    pub read_back: ReadBack,
+    /// Read-back voice: "speech-dispatcher" or "piper".
+    pub tts: TtsEngine,
+    /// Piper voice model (.onnx) for `tts = "piper"`.
+    pub piper_model: String,
+    /// Recognition engine: "sherpa" (the `model` preset), "vosk", or "cloud".
+    pub backend: Backend,
+    /// Unpacked Vosk model directory for `backend = "vosk"`.
+    pub vosk_model: String,
     /// Named preset (e.g. "parakeet-tdt-0.6b-v3").
     pub model: String,
+    /// Which file set of the preset to use: "int8" or "fp16".
+    pub precision: Precision,
+    /// Directory already holding the preset's files; when set, nothing is downloaded.
+    pub model_dir: String,
+    /// "pinned" keeps the downloaded model revision; "auto" follows upstream.
+    pub model_update: ModelUpdate,
+    /// Preset that re-transcribes each recording in the background; a notification
+    /// shows its text when it differs from what was typed. Empty disables it.
+    pub second_pass_model: String,
+    /// Inference device: "cpu", "cuda" (falls back to the CPU when unavailable), or
+    /// "auto" to pick the best one available.
+    pub provider: Provider,
+    /// Run a short synthetic inference at startup so the first utterance is fast.
+    pub warmup: bool,
+    /// Which queued recording is transcribed next: "oldest-first", "newest-first", or "latest-only".
+    pub queue_policy: QueuePolicy,
+    /// Give up on a transcription after this many seconds and move on; 0 waits forever.
+    /// The abandoned inference keeps its copy of the model until it returns.
+    pub transcribe_timeout_secs: u64,
+    pub logging: LoggingConfig,
+    /// Hosted service for `backend = "cloud"`.
+    pub cloud: CloudConfig,
+    /// Live captions in OBS through obs-websocket.
+    pub obs: ObsConfig,
+    pub history: HistoryConfig,
+    pub redact: RedactConfig,
+    /// Spoken word or phrase -> how it should be written ("github" = "GitHub").
+    pub dictionary: BTreeMap<String, String>,
 }
 
-/// Resolved paths for sherpa transducer model files.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Also write logs to a file (in addition to stderr).
+    pub file: bool,
+    /// Log file path. Empty uses `$XDG_STATE_HOME/whisp/whisp.log`.
+    pub path: String,
+    /// Rotate the log file once it grows past this size.
+    pub max_size_kb: u64,
+    /// Number of rotated files to keep (`whisp.log.1` .. `whisp.log.N`).
+    pub max_files: usize,
+    /// Level per module ("transcriber" = "debug", "hotkey" = "warn"), on top of `info`.
+    pub levels: BTreeMap<String, String>,
+}
+
+/// What the configured backend loads: a preset's sherpa model files, a Vosk model,
+/// or a hosted service.
 #[derive(Debug)]
-pub struct ModelPaths {
-    pub encoder: PathBuf,
-    pub decoder: PathBuf,
-    pub joiner: PathBuf,
-    pub tokens: PathBuf,
+pub enum ModelPaths {
+    Transducer {
+        encoder: PathBuf,
+        decoder: PathBuf,
+        joiner: PathBuf,
+        tokens: PathBuf,
+    },
+    Whisper {
+        encoder: PathBuf,
+        decoder: PathBuf,
+        tokens: PathBuf,
+        language: &'static str,
+    },
+    Moonshine {
+        preprocessor: PathBuf,
+        encoder: PathBuf,
+        uncached_decoder: PathBuf,
+        cached_decoder: PathBuf,
+        tokens: PathBuf,
+    },
+    /// Unpacked Vosk model directory.
+    Vosk { dir: PathBuf },
+    /// No local files: recordings go to a hosted service.
+    Cloud(cloud::Settings),
+}
+
+impl ModelPaths {
+    /// Builds paths from a preset's file list, in the order `kind` documents.
+    fn from_files(kind: ModelKind, files: Vec<PathBuf>) -> Self {
+        let mut files = files.into_iter();
+        let mut next = || files.next().expect("preset lists every model file");
+        match kind {
+            ModelKind::Transducer => Self::Transducer {
+                encoder: next(),
+                decoder: next(),
+                joiner: next(),
+                tokens: next(),
+            },
+            ModelKind::Whisper { language } => Self::Whisper {
+                encoder: next(),
+                decoder: next(),
+                tokens: next(),
+                language,
+            },
+            ModelKind::Moonshine => Self::Moonshine {
+                preprocessor: next(),
+                encoder: next(),
+                uncached_decoder: next(),
+                cached_decoder: next(),
+                tokens: next(),
+            },
+        }
+    }
+
+    /// Directory holding the model files, or the service recordings are sent to.
+    pub fn location(&self) -> String {
+        match self {
+            Self::Transducer { encoder, .. }
+            | Self::Whisper { encoder, .. }
+            | Self::Moonshine { encoder, .. } => {
+                encoder.parent().unwrap_or(encoder).display().to_string()
+            }
+            Self::Vosk { dir } => dir.display().to_string(),
+            Self::Cloud(settings) => format!("{} ({})", settings.service, settings.endpoint),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -65,20 +463,305 @@ pub struct LoadedConfig {
     pub created: bool,
 }
 
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistoryConfig {
+    /// Append each transcription to a local JSONL file.
+    pub enabled: bool,
+    /// History file path. Empty uses `$XDG_DATA_HOME/whisp/history.jsonl`.
+    pub path: String,
+    /// Encrypt entries at rest: "off", "key-file", or "passphrase".
+    pub encryption: Encryption,
+    /// Key for `encryption = "key-file"`. Empty uses `$XDG_CONFIG_HOME/whisp/history.key`.
+    pub key_file: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CloudConfig {
+    /// API the recordings are sent to: "deepgram" or "assemblyai".
+    pub service: cloud::Service,
+    /// Base URL override (for a proxy or compatible server). Empty uses the service's own.
+    pub endpoint: String,
+    /// File holding the API key. Empty uses `$XDG_CONFIG_HOME/whisp/cloud.key`.
+    pub api_key_file: String,
+}
+
+/// Where `[obs]` puts each transcription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ObsTarget {
+    /// CEA-608 stream captions; only shown while OBS is streaming.
+    #[default]
+    Captions,
+    /// The text of a Text (GDI+/FreeType 2) source named by `source`.
+    TextSource,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ObsConfig {
+    /// Send each typed transcription to OBS.
+    pub enabled: bool,
+    /// obs-websocket server address.
+    pub url: String,
+    /// obs-websocket server password; empty when authentication is off.
+    pub password: String,
+    pub target: ObsTarget,
+    /// Text source to update with `target = "text-source"`.
+    pub source: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Mask card numbers (13-19 digits passing the Luhn check).
+    pub credit_cards: bool,
+    /// Mask email addresses, written ("a@b.com") or spoken ("a at b dot com").
+    pub emails: bool,
+    /// Extra regular expressions whose matches are masked.
+    pub patterns: Vec<String>,
+    /// Text typed in place of each match.
+    pub mask: String,
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
             hotkey: "insert".into(),
+            hotkey_modifiers: Vec::new(),
+            virtual_keyboards: false,
+            keyboards: Vec::new(),
+            undo_hotkey: String::new(),
+            numeric_hotkey: String::new(),
+            retry_hotkey: String::new(),
             audio_device: String::new(),
+            audio_devices: Vec::new(),
+            mic_mix: MicMix::Loudest,
+            echo_cancel: false,
+            portal: false,
+            ignore_when_locked: false,
+            password_guard: false,
             debounce_ms: 100,
+            merge_within_ms: 0,
+            recording_mode: hotkey::RecordingMode::Hold,
+            hold_threshold_ms: 300,
+            type_chunk_size: 100,
+            type_chunk_delay_ms: 20,
+            max_output_chars: 0,
+            on_long_output: output::LongOutput::Truncate,
+            emit_spacing_ms: 0,
+            unicode_input: false,
+            keymap: "us".into(),
+            compose_key: String::new(),
+            output_template: output::TEXT_PLACEHOLDER.into(),
+            terminal_safe: false,
+            newlines: Breaks::Keep,
+            tabs: Breaks::Keep,
+            smart_join: false,
+            progressive_output: false,
+            dictation_mode: DictationMode::Text,
+            phrases: Vec::new(),
+            voice_commands: false,
+            emoji: false,
+            strip_non_speech: false,
+            punctuate: false,
+            censor: Censor::Off,
+            privacy: false,
+            announce: false,
+            read_back: ReadBack::Off,
+            tts: TtsEngine::SpeechDispatcher,
+            piper_model: String::new(),
+            backend: Backend::Sherpa,
+            vosk_model: String::new(),
             model: "parakeet-tdt-0.6b-v3".into(),
+            precision: Precision::Int8,
+            model_dir: String::new(),
+            model_update: ModelUpdate::Pinned,
+            second_pass_model: String::new(),
+            provider: Provider::Cpu,
+            warmup: true,
+            queue_policy: QueuePolicy::OldestFirst,
+            transcribe_timeout_secs: 0,
+            logging: LoggingConfig::default(),
+            cloud: CloudConfig::default(),
+            obs: ObsConfig::default(),
+            history: HistoryConfig::default(),
+            redact: RedactConfig::default(),
+            dictionary: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: false,
+            path: String::new(),
+            max_size_kb: 1024,
+            max_files: 3,
+            levels: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for ObsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            url: "ws://localhost:4455".into(),
+            password: String::new(),
+            target: ObsTarget::Captions,
+            source: String::new(),
         }
     }
 }
 
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            credit_cards: false,
+            emails: false,
+            patterns: Vec::new(),
+            mask: "[redacted]".into(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// Resolved log file path (configured path or the XDG state default).
+    pub fn file_path(&self) -> PathBuf {
+        if self.path.is_empty() {
+            default_log_path()
+        } else {
+            PathBuf::from(&self.path)
+        }
+    }
+}
+
+impl HistoryConfig {
+    /// Resolved history file path (configured path or the XDG data default).
+    pub fn file_path(&self) -> PathBuf {
+        if self.path.is_empty() {
+            default_history_path()
+        } else {
+            PathBuf::from(&self.path)
+        }
+    }
+
+    /// The encryption key source, or None when history is stored in plain text.
+    pub fn secret(&self) -> Result<Option<Secret>> {
+        match self.encryption {
+            Encryption::Off => Ok(None),
+            Encryption::KeyFile if self.key_file.is_empty() => Ok(Some(Secret::KeyFile(
+                default_config_path().with_file_name("history.key"),
+            ))),
+            Encryption::KeyFile => Ok(Some(Secret::KeyFile(PathBuf::from(&self.key_file)))),
+            Encryption::Passphrase => match std::env::var(PASSPHRASE_ENV) {
+                Ok(passphrase) if !passphrase.is_empty() => {
+                    Ok(Some(Secret::Passphrase(passphrase)))
+                }
+                _ => bail!("[history] encryption = \"passphrase\" needs {PASSPHRASE_ENV} set"),
+            },
+        }
+    }
+}
+
+impl CloudConfig {
+    /// Service, endpoint, and API key, read from the environment or the key file.
+    pub fn settings(&self) -> Result<cloud::Settings> {
+        let api_key = match std::env::var(CLOUD_KEY_ENV) {
+            Ok(key) if !key.trim().is_empty() => key,
+            _ => {
+                let path = if self.api_key_file.is_empty() {
+                    default_config_path().with_file_name("cloud.key")
+                } else {
+                    PathBuf::from(&self.api_key_file)
+                };
+                fs::read_to_string(&path).with_context(|| {
+                    format!(
+                        "backend = \"cloud\" needs an API key in {} or {CLOUD_KEY_ENV}",
+                        path.display()
+                    )
+                })?
+            }
+        };
+        let api_key = api_key.trim().to_string();
+        if api_key.is_empty() {
+            bail!("backend = \"cloud\" needs a non-empty API key");
+        }
+        let endpoint = if self.endpoint.is_empty() {
+            self.service.default_endpoint().to_string()
+        } else {
+            self.endpoint.clone()
+        };
+        Ok(cloud::Settings {
+            service: self.service,
+            endpoint,
+            api_key,
+        })
+    }
+}
+
 impl Config {
     fn normalize(&mut self) {
         self.hotkey = hotkey::normalize_hotkey_name(&self.hotkey);
+        for modifier in &mut self.hotkey_modifiers {
+            *modifier = hotkey::normalize_hotkey_name(modifier);
+        }
+        for value in [
+            &mut self.undo_hotkey,
+            &mut self.numeric_hotkey,
+            &mut self.retry_hotkey,
+            &mut self.compose_key,
+        ] {
+            if !value.is_empty() {
+                *value = hotkey::normalize_hotkey_name(value);
+            }
+        }
+    }
+
+    /// Voice for `read_back`.
+    pub fn voice(&self) -> Voice {
+        match self.tts {
+            TtsEngine::SpeechDispatcher => Voice::SpeechDispatcher,
+            TtsEngine::Piper => Voice::Piper {
+                model: PathBuf::from(&self.piper_model),
+            },
+        }
+    }
+
+    /// Optional action hotkeys as (config key, action, value); empty values are unbound.
+    fn optional_hotkeys(&self) -> [(&'static str, hotkey::Action, &str); 3] {
+        [
+            ("undo_hotkey", hotkey::Action::Undo, &self.undo_hotkey),
+            (
+                "numeric_hotkey",
+                hotkey::Action::ToggleNumeric,
+                &self.numeric_hotkey,
+            ),
+            ("retry_hotkey", hotkey::Action::Retry, &self.retry_hotkey),
+        ]
+    }
+
+    /// Device selection and modifiers for `hotkey::spawn_listener`.
+    pub fn listener_options(&self) -> hotkey::ListenerOptions<'_> {
+        hotkey::ListenerOptions {
+            modifiers: &self.hotkey_modifiers,
+            virtual_keyboards: self.virtual_keyboards,
+            keyboards: &self.keyboards,
+        }
+    }
+
+    /// All bound keys, starting with the record hotkey.
+    pub fn hotkey_bindings(&self) -> Vec<(hotkey::Action, &str)> {
+        let mut bindings = vec![(hotkey::Action::Record, self.hotkey.as_str())];
+        for (_, action, value) in self.optional_hotkeys() {
+            if !value.is_empty() {
+                bindings.push((action, value));
+            }
+        }
+        bindings
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -89,6 +772,105 @@ impl Config {
             )
         })?;
 
+        let mut bound = vec![("hotkey", self.hotkey.as_str())];
+        for (field, _, value) in self.optional_hotkeys() {
+            if value.is_empty() {
+                continue;
+            }
+            hotkey::parse_hotkey(value).with_context(|| {
+                format!(
+                    "Invalid {field} '{value}'. Run `whisp --list-hotkeys` to see all supported values."
+                )
+            })?;
+            if let Some((other, _)) = bound.iter().find(|(_, key)| *key == value) {
+                bail!("{field} '{value}' is already bound to {other}");
+            }
+            bound.push((field, value));
+        }
+
+        for modifier in &self.hotkey_modifiers {
+            hotkey::parse_hotkey(modifier).with_context(|| {
+                format!("Invalid hotkey_modifiers entry '{modifier}'. Run `whisp --list-hotkeys` to see all supported values.")
+            })?;
+            if *modifier == self.hotkey {
+                bail!("hotkey_modifiers can't include the hotkey '{modifier}' itself");
+            }
+        }
+
+        if !self.audio_device.is_empty() && !self.audio_devices.is_empty() {
+            bail!("Set either audio_device or audio_devices, not both");
+        }
+
+        if self.echo_cancel && !self.audio_devices.is_empty() {
+            bail!("echo_cancel works with a single audio_device, not audio_devices");
+        }
+
+        Keymap::load(&self.keymap)?;
+        if !self.compose_key.is_empty() {
+            hotkey::parse_hotkey(&self.compose_key)
+                .with_context(|| format!("Invalid compose_key '{}'", self.compose_key))?;
+        }
+
+        if self.portal && !cfg!(feature = "portal") {
+            bail!("portal = true needs a build with `cargo build --release --features portal`");
+        }
+
+        if self.backend == Backend::Vosk {
+            if !cfg!(feature = "vosk") {
+                bail!(
+                    "backend = \"vosk\" needs a build with `cargo build --release --features vosk`"
+                );
+            }
+            if self.vosk_model.is_empty() {
+                bail!(
+                    "backend = \"vosk\" needs vosk_model set to an unpacked Vosk model directory"
+                );
+            }
+        }
+
+        if self.dictation_mode == DictationMode::Phrases
+            && postprocess::Phrases::new(&self.phrases).is_empty()
+        {
+            bail!("dictation_mode = \"phrases\" needs at least one entry in phrases");
+        }
+
+        if self.backend == Backend::Cloud {
+            if self.privacy {
+                bail!("privacy = true keeps transcripts on this machine; it can't be combined with backend = \"cloud\"");
+            }
+            if !self.cloud.endpoint.is_empty() && !self.cloud.endpoint.starts_with("https://") {
+                bail!(
+                    "[cloud] endpoint {} must use https:// so recordings and the API key are encrypted in transit",
+                    self.cloud.endpoint
+                );
+            }
+        }
+
+        if self.obs.enabled {
+            if !cfg!(feature = "obs") {
+                bail!("[obs] enabled = true needs a build with `cargo build --release --features obs`");
+            }
+            if !self.obs.url.starts_with("ws://") {
+                bail!("[obs] url {} must start with ws://", self.obs.url);
+            }
+            if self.obs.target == ObsTarget::TextSource && self.obs.source.is_empty() {
+                bail!("[obs] target = \"text-source\" needs source set to the text source's name");
+            }
+        }
+
+        if self.on_long_output == output::LongOutput::Confirm && self.retry_hotkey.is_empty() {
+            bail!("on_long_output = \"confirm\" holds long transcriptions until the retry key is pressed; set retry_hotkey too");
+        }
+
+        if self.password_guard && !cfg!(feature = "atspi") {
+            bail!(
+                "password_guard = true needs a build with `cargo build --release --features atspi`"
+            );
+        }
+        if self.password_guard && self.retry_hotkey.is_empty() {
+            bail!("password_guard = true holds text typed into a password field until the retry key is pressed; set retry_hotkey too");
+        }
+
         if self.debounce_ms > 5000 {
             bail!(
                 "debounce_ms {} exceeds maximum of 5000ms. Use a value between 0-5000.",
@@ -96,12 +878,95 @@ impl Config {
             );
         }
 
-        if resolve_preset(&self.model).is_none() {
+        if self.merge_within_ms > 5000 {
+            bail!(
+                "merge_within_ms {} exceeds maximum of 5000ms. Each recording waits this long before transcription starts.",
+                self.merge_within_ms
+            );
+        }
+
+        if !(50..=2000).contains(&self.hold_threshold_ms) {
+            bail!(
+                "hold_threshold_ms {} must be between 50 and 2000.",
+                self.hold_threshold_ms
+            );
+        }
+
+        if self.type_chunk_delay_ms > 1000 {
+            bail!(
+                "type_chunk_delay_ms {} exceeds maximum of 1000ms.",
+                self.type_chunk_delay_ms
+            );
+        }
+
+        if self.emit_spacing_ms > 5000 {
+            bail!(
+                "emit_spacing_ms {} exceeds maximum of 5000ms.",
+                self.emit_spacing_ms
+            );
+        }
+
+        if !self.output_template.contains(output::TEXT_PLACEHOLDER) {
+            bail!(
+                "output_template '{}' must contain {}",
+                self.output_template,
+                output::TEXT_PLACEHOLDER
+            );
+        }
+
+        if self.tts == TtsEngine::Piper && self.piper_model.is_empty() {
+            bail!("tts = \"piper\" needs piper_model set to a voice .onnx file");
+        }
+
+        postprocess::Redactor::new(&self.redact)?;
+
+        if self.logging.max_size_kb == 0 {
+            bail!("logging.max_size_kb must be greater than 0");
+        }
+
+        if self.logging.max_files > 20 {
+            bail!(
+                "logging.max_files {} exceeds maximum of 20.",
+                self.logging.max_files
+            );
+        }
+
+        logging::parse_levels(&self.logging.levels)?;
+
+        let Some(preset) = resolve_preset(&self.model) else {
             bail!(
                 "Unknown model '{}'. Available presets: {}",
                 self.model,
                 available_presets().join(", ")
             );
+        };
+        if preset.variant(self.precision).is_none() {
+            bail!(
+                "Model '{}' has no {} variant. Available precisions: {}",
+                self.model,
+                self.precision,
+                preset.precisions().join(", ")
+            );
+        }
+
+        if self.progressive_output && self.output_template != output::TEXT_PLACEHOLDER {
+            bail!("progressive_output types a recording in several pieces; it can't be combined with output_template");
+        }
+
+        if !self.second_pass_model.is_empty() {
+            if resolve_preset(&self.second_pass_model).is_none() {
+                bail!(
+                    "Unknown second_pass_model '{}'. Available presets: {}",
+                    self.second_pass_model,
+                    available_presets().join(", ")
+                );
+            }
+            if self.backend != Backend::Sherpa {
+                bail!("second_pass_model needs backend = \"sherpa\"");
+            }
+            if self.second_pass_model == self.model {
+                bail!("second_pass_model is the same preset as model; pick a slower, more accurate one");
+            }
         }
 
         Ok(())
@@ -120,6 +985,30 @@ pub fn default_config_path() -> PathBuf {
         .join("config.toml")
 }
 
+pub fn default_log_path() -> PathBuf {
+    dirs::state_dir()
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|h| PathBuf::from(h).join(".local").join("state"))
+        })
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("whisp")
+        .join("whisp.log")
+}
+
+pub fn default_history_path() -> PathBuf {
+    dirs::data_dir()
+        .or_else(|| {
+            std::env::var("HOME")
+                .ok()
+                .map(|h| PathBuf::from(h).join(".local").join("share"))
+        })
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("whisp")
+        .join("history.jsonl")
+}
+
 pub fn model_cache_hint() -> PathBuf {
     dirs::cache_dir()
         .unwrap_or_else(|| PathBuf::from("/tmp"))
@@ -127,6 +1016,16 @@ pub fn model_cache_hint() -> PathBuf {
 }
 
 pub fn write_default_config(path_override: Option<&Path>, force: bool) -> Result<PathBuf> {
+    write_config(path_override, force, &[])
+}
+
+/// Writes the default config with the given top-level keys set, keeping the
+/// comments of the example config.
+pub fn write_config(
+    path_override: Option<&Path>,
+    force: bool,
+    settings: &[(&str, toml::Value)],
+) -> Result<PathBuf> {
     let path = path_override
         .map(PathBuf::from)
         .unwrap_or_else(default_config_path);
@@ -138,17 +1037,198 @@ pub fn write_default_config(path_override: Option<&Path>, force: bool) -> Result
         );
     }
 
+    let text = set_keys(DEFAULT_CONFIG, settings);
+    check_config_text(&path, &text)?;
+
     if let Some(parent) = path.parent() {
         fs::create_dir_all(parent)
             .with_context(|| format!("creating config directory {}", parent.display()))?;
     }
 
-    fs::write(&path, DEFAULT_CONFIG)
-        .with_context(|| format!("writing default config to {}", path.display()))?;
+    fs::write(&path, text).with_context(|| format!("writing config to {}", path.display()))?;
 
     Ok(path)
 }
 
+/// Sets keys in an existing config file, keeping everything else (comments,
+/// other keys, tables) as written. The result must still validate.
+pub fn update_config(path: &Path, settings: &[(&str, toml::Value)]) -> Result<()> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("reading config from {}", path.display()))?;
+    let text = set_keys(&text, settings);
+    check_config_text(path, &text)?;
+    fs::write(path, text).with_context(|| format!("writing config to {}", path.display()))
+}
+
+fn check_config_text(path: &Path, text: &str) -> Result<()> {
+    let mut config = parse_config_text(path, text)?;
+    config.normalize();
+    config.validate()
+}
+
+/// Replaces the value of each `key = ...` line, where a dotted key ("history.enabled")
+/// names a key in that table. Missing top-level keys are added before the first table,
+/// missing table keys right after the table's header, and missing tables at the end.
+/// A value spanning several lines is replaced as a whole.
+fn set_keys(text: &str, settings: &[(&str, toml::Value)]) -> String {
+    let settings: Vec<(&str, &str, &toml::Value)> = settings
+        .iter()
+        .map(|(key, value)| {
+            let (table, leaf) = key.rsplit_once('.').unwrap_or(("", key));
+            (table, leaf, value)
+        })
+        .collect();
+    let statements = statements(text);
+    // Keys already in the file are replaced in place; the rest are inserted.
+    let mut table = "";
+    let mut present = vec![false; settings.len()];
+    for statement in &statements {
+        match table_header(statement[0]) {
+            Some(header) => table = header,
+            None => {
+                if let Some(index) = setting_on_line(&settings, table, statement[0]) {
+                    present[index] = true;
+                }
+            }
+        }
+    }
+    let mut missing: Vec<(&str, &str, &toml::Value)> = settings
+        .iter()
+        .zip(&present)
+        .filter(|(_, present)| !**present)
+        .map(|(setting, _)| *setting)
+        .collect();
+
+    let mut out = String::with_capacity(text.len());
+    let mut table = "";
+    for statement in &statements {
+        let line = statement[0];
+        if let Some(header) = table_header(line) {
+            if table.is_empty() {
+                for (_, leaf, value) in take_table(&mut missing, "") {
+                    out.push_str(&format!("{leaf} = {value}\n\n"));
+                }
+            }
+            table = header;
+            out.push_str(line);
+            out.push('\n');
+            for (_, leaf, value) in take_table(&mut missing, table) {
+                out.push_str(&format!("{leaf} = {value}\n"));
+            }
+            continue;
+        }
+        match setting_on_line(&settings, table, line) {
+            Some(index) => {
+                let (_, leaf, value) = settings[index];
+                out.push_str(&format!("{leaf} = {value}\n"));
+            }
+            None => {
+                for line in statement {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    for (_, leaf, value) in take_table(&mut missing, "") {
+        out.push_str(&format!("{leaf} = {value}\n"));
+    }
+    while let Some(&(table, _, _)) = missing.first() {
+        out.push_str(&format!("\n[{table}]\n"));
+        for (_, leaf, value) in take_table(&mut missing, table) {
+            out.push_str(&format!("{leaf} = {value}\n"));
+        }
+    }
+    out
+}
+
+/// Groups config lines into statements: each line on its own, except that a
+/// `key = value` whose value continues on later lines (a multi-line array, inline
+/// table or string) takes those lines with it.
+fn statements(text: &str) -> Vec<Vec<&str>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut statements = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let mut end = start + 1;
+        let line = lines[start];
+        if table_header(line).is_none()
+            && line.contains('=')
+            && toml::from_str::<toml::Table>(line).is_err()
+        {
+            // Extend to the first line that completes the value; a line that never
+            // parses stays on its own.
+            end = (start + 2..=lines.len())
+                .find(|&end| toml::from_str::<toml::Table>(&lines[start..end].join("\n")).is_ok())
+                .unwrap_or(end);
+        }
+        statements.push(lines[start..end].to_vec());
+        start = end;
+    }
+    statements
+}
+
+/// The table a `[table]` line opens, ignoring a trailing comment. An array-of-tables
+/// line (`[[table]]`) comes back with its inner brackets, which no dotted key matches.
+fn table_header(line: &str) -> Option<&str> {
+    let line = line.split('#').next().unwrap_or_default().trim();
+    let name = line.strip_prefix('[')?.strip_suffix(']')?;
+    if let Some(inner) = name.strip_prefix('[') {
+        let inner = inner.strip_suffix(']')?;
+        return (!inner.contains(['[', ']'])).then_some(name);
+    }
+    (!name.contains(['[', ']'])).then(|| name.trim())
+}
+
+/// Which of `settings` the `key = ...` line in `table` assigns.
+fn setting_on_line(
+    settings: &[(&str, &str, &toml::Value)],
+    table: &str,
+    line: &str,
+) -> Option<usize> {
+    settings.iter().position(|(key_table, leaf, _)| {
+        *key_table == table
+            && line
+                .trim_start()
+                .strip_prefix(leaf)
+                .is_some_and(|rest| rest.trim_start().starts_with('='))
+    })
+}
+
+/// Removes and returns the settings that belong in `table`.
+fn take_table<'a>(
+    missing: &mut Vec<(&'a str, &'a str, &'a toml::Value)>,
+    table: &str,
+) -> Vec<(&'a str, &'a str, &'a toml::Value)> {
+    let (matching, rest) = missing
+        .drain(..)
+        .partition(|(key_table, _, _)| *key_table == table);
+    *missing = rest;
+    matching
+}
+
+/// A value given on the command line: TOML syntax when it parses ("true", "250",
+/// "[\"a\"]"), otherwise a plain string, so `whisp config set hotkey f13` needs no quotes.
+pub fn parse_cli_value(raw: &str) -> toml::Value {
+    toml::from_str::<toml::Table>(&format!("value = {raw}"))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// The effective value of a config key, with dotted keys for tables ("logging.file").
+pub fn get_value(config: &Config, key: &str) -> Result<toml::Value> {
+    let mut value = toml::Value::try_from(config).context("serializing config")?;
+    for part in key.split('.') {
+        value = match value {
+            toml::Value::Table(mut table) => table.remove(part),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("Unknown config key '{key}'"))?;
+    }
+    Ok(value)
+}
+
 pub fn load_config(path_override: Option<&Path>) -> Result<LoadedConfig> {
     let path = path_override
         .map(PathBuf::from)
@@ -182,8 +1262,7 @@ pub fn load_config(path_override: Option<&Path>) -> Result<LoadedConfig> {
 }
 
 fn parse_config_text(path: &Path, text: &str) -> Result<Config> {
-    let raw: toml::Value =
-        toml::from_str(text).with_context(|| format!("parsing TOML from {}", path.display()))?;
+    let raw: toml::Value = toml::from_str(text).map_err(|err| describe_error(path, text, &err))?;
     if raw.get("language").is_some() {
         bail!(
             "Config key 'language' was removed. Delete 'language' from {}",
@@ -191,50 +1270,281 @@ fn parse_config_text(path: &Path, text: &str) -> Result<Config> {
         );
     }
 
-    let config: Config =
-        toml::from_str(text).with_context(|| format!("parsing config from {}", path.display()))?;
+    let config: Config = toml::from_str(text).map_err(|err| describe_error(path, text, &err))?;
     Ok(config)
 }
 
-pub fn resolve_model_paths(config: &Config) -> Result<ModelPaths> {
-    let preset = resolve_preset(&config.model).ok_or_else(|| {
+/// Turns a TOML or serde error into "path:line:column: problem", suggesting the
+/// closest valid name for a misspelled key or value instead of listing them all.
+fn describe_error(path: &Path, text: &str, err: &toml::de::Error) -> anyhow::Error {
+    let location = err
+        .span()
+        .map(|span| {
+            let before = &text[..span.start.min(text.len())];
+            let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+            let line = before.matches('\n').count() + 1;
+            let column = before[line_start..].chars().count() + 1;
+            format!(":{line}:{column}")
+        })
+        .unwrap_or_default();
+    let message = err.message().trim();
+    let (problem, expected) = message.split_once(", expected ").unwrap_or((message, ""));
+    let mut description = format!("{}{location}: {problem}", path.display());
+    let given = backticked(problem).next();
+    let suggestion = given.and_then(|given| {
+        backticked(expected)
+            .map(|name| (postprocess::edit_distance(given, name), name))
+            .filter(|(distance, _)| *distance <= given.chars().count().div_ceil(2))
+            .min()
+    });
+    if let Some((_, name)) = suggestion {
+        description.push_str(&format!("; did you mean `{name}`?"));
+    } else if !expected.is_empty() && backticked(expected).count() <= 8 {
+        description.push_str(&format!(", expected {expected}"));
+    }
+    anyhow!(description)
+}
+
+/// The `quoted` names in a serde error message.
+fn backticked(message: &str) -> impl Iterator<Item = &str> {
+    message.split('`').skip(1).step_by(2)
+}
+
+fn model_variant(config: &Config) -> Result<(ModelKind, &'static ModelVariant)> {
+    preset_variant(&config.model, config.precision)
+}
+
+fn preset_variant(model: &str, precision: Precision) -> Result<(ModelKind, &'static ModelVariant)> {
+    let preset = resolve_preset(model).ok_or_else(|| {
         anyhow!(
             "Unknown model preset '{}'. Valid presets: {}",
-            config.model,
+            model,
             available_presets().join(", ")
         )
     })?;
+    let variant = preset.variant(precision).ok_or_else(|| {
+        anyhow!(
+            "Model '{}' has no {} variant. Available precisions: {}",
+            model,
+            precision,
+            preset.precisions().join(", ")
+        )
+    })?;
+    Ok((preset.kind, variant))
+}
+
+/// Locates the configured model's files, downloading any that are missing.
+///
+/// With `model_update = "pinned"` the commit recorded on first download is reused
+/// until `whisp --update-model`; with "auto" the latest upstream commit is fetched.
+/// A non-empty `model_dir` takes precedence and is never downloaded into.
+/// With `backend = "vosk"` the `vosk_model` directory is used as is, and
+/// `backend = "cloud"` needs no files at all.
+pub fn resolve_model_paths(config: &Config) -> Result<ModelPaths> {
+    if config.backend == Backend::Cloud {
+        return Ok(ModelPaths::Cloud(config.cloud.settings()?));
+    }
+    if config.backend == Backend::Vosk {
+        let dir = PathBuf::from(&config.vosk_model);
+        if !dir.is_dir() {
+            bail!(
+                "vosk_model {} is not a directory (unpack a model from https://alphacephei.com/vosk/models)",
+                dir.display()
+            );
+        }
+        return Ok(ModelPaths::Vosk { dir });
+    }
+    if !config.model_dir.is_empty() {
+        let (kind, variant) = model_variant(config)?;
+        return local_model_paths(Path::new(&config.model_dir), kind, variant);
+    }
+    fetch_model(config, config.model_update)
+}
+
+/// Moves the configured model to the latest upstream commit.
+pub fn update_model(config: &Config) -> Result<ModelPaths> {
+    ensure_downloadable_model(config)?;
+    fetch_model(config, ModelUpdate::Auto)
+}
+
+fn ensure_downloadable_model(config: &Config) -> Result<()> {
+    if config.backend == Backend::Vosk {
+        bail!(
+            "backend = \"vosk\" uses a local model; whisp does not download or update Vosk models"
+        );
+    }
+    if config.backend == Backend::Cloud {
+        bail!("backend = \"cloud\" transcribes on the provider's servers; there is no local model to download or update");
+    }
+    if !config.model_dir.is_empty() {
+        bail!(
+            "model_dir is set to {}; whisp does not download or update models there",
+            config.model_dir
+        );
+    }
+    Ok(())
+}
+
+fn local_model_paths(dir: &Path, kind: ModelKind, variant: &ModelVariant) -> Result<ModelPaths> {
+    let paths: Vec<PathBuf> = variant.files.iter().map(|file| dir.join(file)).collect();
+    let missing: Vec<&str> = variant
+        .files
+        .iter()
+        .zip(&paths)
+        .filter(|(_, path)| !path.is_file())
+        .map(|(file, _)| *file)
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "model_dir {} is missing {} (expected the {} files from {})",
+            dir.display(),
+            missing.join(", "),
+            variant.precision,
+            variant.repo
+        );
+    }
+    log::info!("Using model files from {}", dir.display());
+    Ok(ModelPaths::from_files(kind, paths))
+}
+
+fn fetch_model(config: &Config, update: ModelUpdate) -> Result<ModelPaths> {
+    fetch_preset(&config.model, config.precision, update)
+}
+
+/// Locates the `second_pass_model` preset's files, downloading any that are missing.
+/// Uses the configured precision when the preset has it, int8 otherwise.
+pub fn resolve_second_pass_paths(config: &Config) -> Result<Option<ModelPaths>> {
+    if config.second_pass_model.is_empty() {
+        return Ok(None);
+    }
+    let precision = match resolve_preset(&config.second_pass_model) {
+        Some(preset) if preset.variant(config.precision).is_some() => config.precision,
+        _ => Precision::Int8,
+    };
+    fetch_preset(&config.second_pass_model, precision, config.model_update).map(Some)
+}
+
+fn fetch_preset(model: &str, precision: Precision, update: ModelUpdate) -> Result<ModelPaths> {
+    let (kind, variant) = preset_variant(model, precision)?;
+    let api = hf_hub::api::sync::Api::new().context("initializing Hugging Face API")?;
+    let mut revisions = load_model_revisions();
+    let pinned = revisions.get(variant.repo).cloned();
+
+    let revision = match (update, pinned) {
+        (ModelUpdate::Pinned, Some(commit)) => commit,
+        (ModelUpdate::Pinned, None) => variant.revision.to_string(),
+        (ModelUpdate::Auto, pinned) => match latest_revision(&api, variant) {
+            Ok(commit) => commit,
+            Err(err) => {
+                log::warn!("Could not check {} for updates: {err:#}", variant.repo);
+                pinned.unwrap_or_else(|| variant.revision.to_string())
+            }
+        },
+    };
 
     log::info!(
-        "Ensuring model files for '{}' are available (repo={}, revision={})",
-        config.model,
-        preset.repo,
-        preset.revision
+        "Ensuring model files for '{}' ({}) are available (repo={}, revision={})",
+        model,
+        precision,
+        variant.repo,
+        revision
     );
     log::info!("Model cache root: {}", model_cache_hint().display());
 
-    let api = hf_hub::api::sync::Api::new().context("initializing Hugging Face API")?;
-    let hf_repo = api.repo(Repo::with_revision(
-        preset.repo.to_string(),
-        RepoType::Model,
-        preset.revision.to_string(),
-    ));
-
-    let mut paths = Vec::with_capacity(preset.files.len());
-    for file in preset.files {
+    let repo = Repo::with_revision(variant.repo.to_string(), RepoType::Model, revision.clone());
+    if revision != variant.revision {
+        // The cache only resolves files through refs/<revision>; point the commit at
+        // itself so an existing snapshot is found without a network round trip.
+        hf_hub::Cache::default()
+            .repo(repo.clone())
+            .create_ref(&revision)
+            .context("recording model revision in the Hugging Face cache")?;
+    }
+    let hf_repo = api.repo(repo);
+
+    let mut paths = Vec::with_capacity(variant.files.len());
+    for file in variant.files {
         let path = download_with_retries(&hf_repo, file)?;
         log::info!("Model file ready: {} -> {}", file, path.display());
         paths.push(path);
     }
 
-    Ok(ModelPaths {
-        encoder: paths[0].clone(),
-        decoder: paths[1].clone(),
-        joiner: paths[2].clone(),
-        tokens: paths[3].clone(),
+    if let Some(commit) = snapshot_commit(&paths[0]) {
+        if revisions.get(variant.repo) != Some(&commit) {
+            log::info!("Pinned {} at commit {commit}", variant.repo);
+            revisions.insert(variant.repo.to_string(), commit);
+            if let Err(err) = save_model_revisions(&revisions) {
+                log::warn!("Could not record model revision: {err:#}");
+            }
+        }
+    }
+
+    Ok(ModelPaths::from_files(kind, paths))
+}
+
+/// Pinned and latest upstream commits of the configured model.
+pub struct ModelUpdateStatus {
+    pub repo: &'static str,
+    pub pinned: Option<String>,
+    pub latest: String,
+}
+
+/// Asks Hugging Face for the newest commit of the configured model without downloading it.
+pub fn check_model_update(config: &Config) -> Result<ModelUpdateStatus> {
+    ensure_downloadable_model(config)?;
+    let (_, variant) = model_variant(config)?;
+    let api = hf_hub::api::sync::Api::new().context("initializing Hugging Face API")?;
+    Ok(ModelUpdateStatus {
+        repo: variant.repo,
+        pinned: load_model_revisions().remove(variant.repo),
+        latest: latest_revision(&api, variant)?,
     })
 }
 
+fn latest_revision(api: &hf_hub::api::sync::Api, variant: &ModelVariant) -> Result<String> {
+    let info = api
+        .repo(Repo::with_revision(
+            variant.repo.to_string(),
+            RepoType::Model,
+            variant.revision.to_string(),
+        ))
+        .info()
+        .with_context(|| format!("fetching {} repo info", variant.repo))?;
+    Ok(info.sha)
+}
+
+/// Commit of a cached file, from its `snapshots/<commit>/<file>` path.
+fn snapshot_commit(path: &Path) -> Option<String> {
+    let commit = path.parent()?.file_name()?.to_str()?;
+    Some(commit.to_string())
+}
+
+/// Commits whisp downloaded each model repo at (repo id -> commit).
+pub fn default_model_revisions_path() -> PathBuf {
+    default_history_path().with_file_name("model-revisions.toml")
+}
+
+fn load_model_revisions() -> BTreeMap<String, String> {
+    let path = default_model_revisions_path();
+    let Ok(text) = fs::read_to_string(&path) else {
+        return BTreeMap::new();
+    };
+    toml::from_str(&text).unwrap_or_else(|err| {
+        log::warn!("Ignoring unreadable {}: {err}", path.display());
+        BTreeMap::new()
+    })
+}
+
+fn save_model_revisions(revisions: &BTreeMap<String, String>) -> Result<()> {
+    let path = default_model_revisions_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating directory {}", parent.display()))?;
+    }
+    let text = toml::to_string(revisions).context("serializing model revisions")?;
+    fs::write(&path, text).with_context(|| format!("writing {}", path.display()))
+}
+
 fn download_with_retries(hf_repo: &hf_hub::api::sync::ApiRepo, file: &str) -> Result<PathBuf> {
     let mut last_err = None;
     for attempt in 1..=MODEL_DOWNLOAD_ATTEMPTS {
@@ -278,6 +1588,162 @@ mod tests {
         assert_eq!(cfg.hotkey, "insert");
     }
 
+    #[test]
+    fn output_template_requires_text_placeholder() {
+        let mut cfg = Config {
+            output_template: "- {text}\n".into(),
+            ..Config::default()
+        };
+        assert!(cfg.validate().is_ok());
+        cfg.output_template = "- ".into();
+        assert!(cfg.validate().unwrap_err().to_string().contains("{text}"));
+    }
+
+    #[test]
+    fn second_pass_model_must_be_another_preset() {
+        let mut cfg = Config {
+            model: "moonshine-base-en".into(),
+            second_pass_model: "parakeet-tdt-0.6b-v3".into(),
+            ..Config::default()
+        };
+        assert!(cfg.validate().is_ok());
+        cfg.second_pass_model = "moonshine-base-en".into();
+        assert!(cfg.validate().is_err());
+        cfg.second_pass_model = "parakeet".into();
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn confirm_long_output_requires_retry_hotkey() {
+        let mut cfg = Config {
+            max_output_chars: 500,
+            on_long_output: crate::output::LongOutput::Confirm,
+            ..Config::default()
+        };
+        assert!(cfg
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("retry_hotkey"));
+        cfg.retry_hotkey = "f5".into();
+        assert!(cfg.validate().is_ok());
+    }
+
+    #[test]
+    fn every_preset_has_an_int8_variant() {
+        for name in super::available_presets() {
+            let preset = super::resolve_preset(name).unwrap();
+            assert!(preset.variant(super::Precision::Int8).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn preset_files_match_model_kind() {
+        for name in super::available_presets() {
+            let preset = super::resolve_preset(name).unwrap();
+            let expected = match preset.kind {
+                super::ModelKind::Transducer => 4,
+                super::ModelKind::Whisper { .. } => 3,
+                super::ModelKind::Moonshine => 5,
+            };
+            for variant in preset.variants {
+                assert_eq!(
+                    variant.files.len(),
+                    expected,
+                    "{name} {}",
+                    variant.precision
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn model_dir_reports_missing_files() {
+        let dir = std::env::temp_dir().join(format!("whisp-model-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tokens.txt"), "").unwrap();
+        let cfg = Config {
+            model_dir: dir.display().to_string(),
+            ..Config::default()
+        };
+        let err = super::resolve_model_paths(&cfg).unwrap_err().to_string();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(err.contains("encoder.int8.onnx"), "{err}");
+        assert!(!err.contains("tokens.txt"), "{err}");
+    }
+
+    #[test]
+    fn reads_commit_from_snapshot_path() {
+        let path = Path::new("/cache/models--repo/snapshots/0123abcd/encoder.int8.onnx");
+        assert_eq!(super::snapshot_commit(path).as_deref(), Some("0123abcd"));
+    }
+
+    #[test]
+    fn renders_config_with_settings() {
+        let text = super::set_keys(
+            super::DEFAULT_CONFIG,
+            &[("hotkey", "f13".into()), ("audio_device", "usb-mic".into())],
+        );
+        let cfg = super::parse_config_text(Path::new("/tmp/test.toml"), &text).unwrap();
+        assert_eq!(cfg.hotkey, "f13");
+        assert_eq!(cfg.audio_device, "usb-mic");
+        assert!(text.contains("# Hotkey notes:"));
+    }
+
+    #[test]
+    fn adds_missing_keys_before_first_table() {
+        let text = "# mine\nhotkey = \"insert\"\n\n[history]\nenabled = true\n";
+        let updated = super::set_keys(
+            text,
+            &[("hotkey", "f9".into()), ("smart_join", true.into())],
+        );
+        assert_eq!(
+            updated,
+            "# mine\nhotkey = \"f9\"\n\nsmart_join = true\n\n[history]\nenabled = true\n"
+        );
+    }
+
+    #[test]
+    fn sets_keys_inside_tables() {
+        let text = "hotkey = \"insert\"\n\n[history]\n# keep\nenabled = false\n";
+        let updated = super::set_keys(
+            text,
+            &[
+                ("history.enabled", true.into()),
+                ("history.path", "/tmp/h.jsonl".into()),
+                ("logging.levels.audio", "warn".into()),
+            ],
+        );
+        assert_eq!(
+            updated,
+            "hotkey = \"insert\"\n\n[history]\npath = \"/tmp/h.jsonl\"\n# keep\nenabled = true\n\n[logging.levels]\naudio = \"warn\"\n"
+        );
+        assert_eq!(super::parse_cli_value("true"), toml::Value::Boolean(true));
+        assert_eq!(super::parse_cli_value("f13"), toml::Value::from("f13"));
+    }
+
+    #[test]
+    fn sets_keys_under_commented_headers_and_multi_line_values() {
+        let text = "phrases = [\n  \"Lights on\",\n  \"[off]\",\n]\n\n[logging] # file logging\nfile = \"a.log\"\n\n[[extra]]\nfile = \"b\"\n";
+        let updated = super::set_keys(
+            text,
+            &[
+                ("phrases", vec!["Next"].into()),
+                ("logging.file", "c.log".into()),
+            ],
+        );
+        assert_eq!(
+            updated,
+            "phrases = [\"Next\"]\n\n[logging] # file logging\nfile = \"c.log\"\n\n[[extra]]\nfile = \"b\"\n"
+        );
+        assert_eq!(
+            super::table_header("[logging] # file logging"),
+            Some("logging")
+        );
+        assert_eq!(super::table_header("[[extra]]"), Some("[extra]"));
+        assert_eq!(super::table_header("[a] b"), None);
+    }
+
     #[test]
     fn rejects_removed_language_key() {
         let text = r#"
@@ -304,6 +1770,22 @@ unexpected = true
         assert!(format!("{err:#}").contains("unknown field"));
     }
 
+    #[test]
+    fn locates_config_errors_and_suggests_names() {
+        let path = Path::new("/tmp/test.toml");
+        let err =
+            super::parse_config_text(path, "hotkey = \"insert\"\nsmart_jion = true\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/tmp/test.toml:2:1: unknown field `smart_jion`; did you mean `smart_join`?"
+        );
+        let err = super::parse_config_text(path, "dictation_mode = \"markdwn\"\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "/tmp/test.toml:1:18: unknown variant `markdwn`; did you mean `markdown`?"
+        );
+    }
+
     #[test]
     fn rejects_legacy_output_block() {
         let text = r#"
@@ -0,0 +1,166 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::media;
+use crate::transcriber::ClipTranscriber;
+
+/// Audio files `--eval` picks up; each needs a reference `.txt` with the same stem.
+const AUDIO_EXTENSIONS: &[&str] = &["wav", "flac", "mp3", "ogg", "opus", "m4a"];
+
+/// Errors against a reference transcript, as edit distances over words and characters.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub word_errors: usize,
+    pub words: usize,
+    pub char_errors: usize,
+    pub chars: usize,
+}
+
+impl Score {
+    /// Compares a transcription with its reference, ignoring case and punctuation.
+    pub fn new(reference: &str, hypothesis: &str) -> Self {
+        let reference = normalize(reference);
+        let hypothesis = normalize(hypothesis);
+        let reference_words: Vec<&str> = reference.split_whitespace().collect();
+        let hypothesis_words: Vec<&str> = hypothesis.split_whitespace().collect();
+        let reference_chars: Vec<char> = reference.chars().collect();
+        let hypothesis_chars: Vec<char> = hypothesis.chars().collect();
+        Self {
+            word_errors: distance(&reference_words, &hypothesis_words),
+            words: reference_words.len(),
+            char_errors: distance(&reference_chars, &hypothesis_chars),
+            chars: reference_chars.len(),
+        }
+    }
+
+    pub fn add(&mut self, other: Score) {
+        self.word_errors += other.word_errors;
+        self.words += other.words;
+        self.char_errors += other.char_errors;
+        self.chars += other.chars;
+    }
+
+    /// Word error rate, as a percentage.
+    pub fn wer(&self) -> f64 {
+        rate(self.word_errors, self.words)
+    }
+
+    /// Character error rate, as a percentage.
+    pub fn cer(&self) -> f64 {
+        rate(self.char_errors, self.chars)
+    }
+}
+
+fn rate(errors: usize, total: usize) -> f64 {
+    if total == 0 {
+        return if errors == 0 { 0.0 } else { 100.0 };
+    }
+    errors as f64 * 100.0 / total as f64
+}
+
+/// Lowercase words separated by single spaces, with punctuation dropped except
+/// inside words ("don't", "e-mail").
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| {
+            word.trim_matches(|c: char| !c.is_alphanumeric())
+                .to_lowercase()
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Levenshtein distance between two sequences.
+fn distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Audio files in `dir` that have a reference transcript next to them.
+fn samples(dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut samples = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("reading {}", dir.display()))? {
+        let path = entry?.path();
+        let is_audio = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+        if !is_audio {
+            continue;
+        }
+        let reference = path.with_extension("txt");
+        if reference.is_file() {
+            samples.push((path, reference));
+        } else {
+            log::warn!("Skipping {}: no {}", path.display(), reference.display());
+        }
+    }
+    samples.sort();
+    Ok(samples)
+}
+
+/// `whisp --eval <dir>`: transcribes each audio file with the configured model and
+/// prints its word and character error rates against the matching `.txt`.
+pub fn run(config: &Config, dir: &Path) -> Result<()> {
+    let samples = samples(dir)?;
+    if samples.is_empty() {
+        bail!(
+            "No audio files with matching .txt references in {}",
+            dir.display()
+        );
+    }
+    let paths = config::resolve_model_paths(config)?;
+    let mut transcriber = ClipTranscriber::new(&paths, config.provider)?;
+    println!("Model: {} ({})", config.model, paths.location());
+    println!("{:<40} {:>7} {:>7}", "File", "WER", "CER");
+
+    let mut total = Score::default();
+    for (audio_path, reference_path) in &samples {
+        let reference = fs::read_to_string(reference_path)
+            .with_context(|| format!("reading {}", reference_path.display()))?;
+        let audio = media::decode(audio_path)?;
+        let mut texts = Vec::new();
+        for (start, end) in media::segments(&audio) {
+            texts.push(transcriber.transcribe(&audio[start..end])?);
+        }
+        let score = Score::new(&reference, &texts.join(" "));
+        let name = audio_path.file_name().unwrap_or_default().to_string_lossy();
+        println!("{name:<40} {:>6.1}% {:>6.1}%", score.wer(), score.cer());
+        total.add(score);
+    }
+    println!(
+        "{:<40} {:>6.1}% {:>6.1}%",
+        format!("Overall ({} files, {} words)", samples.len(), total.words),
+        total.wer(),
+        total.cer()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Score;
+
+    #[test]
+    fn scores_words_and_characters() {
+        let score = Score::new("The cat sat on the mat.", "the cat sat on a mat");
+        assert_eq!((score.word_errors, score.words), (1, 6));
+        assert_eq!((score.char_errors, score.chars), (3, 22));
+        assert!((score.wer() - 100.0 / 6.0).abs() < 1e-9);
+
+        let missing = Score::new("Don't stop", "");
+        assert_eq!((missing.word_errors, missing.words), (2, 2));
+        assert_eq!(Score::new("", "").wer(), 0.0);
+    }
+}
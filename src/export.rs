@@ -0,0 +1,227 @@
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate};
+use std::fmt::Write;
+
+use crate::history::Entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Markdown,
+    Srt,
+    Vtt,
+    Text,
+}
+
+pub fn parse_format(name: &str) -> Result<ExportFormat> {
+    Ok(match name {
+        "md" | "markdown" => ExportFormat::Markdown,
+        "srt" => ExportFormat::Srt,
+        "vtt" => ExportFormat::Vtt,
+        "txt" | "text" => ExportFormat::Text,
+        other => bail!("Unknown export format '{other}'. Use md, srt, vtt, or txt."),
+    })
+}
+
+/// Parses `--since` values: `today`, `yesterday`, `<N>d` (days ago), or `YYYY-MM-DD`.
+pub fn parse_since(value: &str, today: NaiveDate) -> Result<NaiveDate> {
+    match value {
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today - Duration::days(1)),
+        _ => {}
+    }
+    if let Some(days) = value.strip_suffix('d').and_then(|n| n.parse::<i64>().ok()) {
+        return Ok(today - Duration::days(days));
+    }
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date),
+        Err(_) => {
+            bail!("Invalid --since value '{value}'. Use today, yesterday, <N>d, or YYYY-MM-DD.")
+        }
+    }
+}
+
+pub fn render(entries: &[Entry], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Markdown => render_markdown(entries),
+        ExportFormat::Srt | ExportFormat::Vtt => render_cues(&history_cues(entries), format),
+        ExportFormat::Text => render_text(entries),
+    }
+}
+
+/// One timed line of subtitles, in milliseconds from the start.
+pub struct Cue {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+}
+
+/// Renders cues as SubRip or WebVTT subtitles, or one line of text per cue.
+pub fn render_cues(cues: &[Cue], format: ExportFormat) -> String {
+    let mut out = String::new();
+    if format == ExportFormat::Vtt {
+        out.push_str("WEBVTT\n\n");
+    }
+    for (index, cue) in cues.iter().enumerate() {
+        let (start, end) = (cue.start_ms, cue.end_ms);
+        let _ = match format {
+            ExportFormat::Srt => writeln!(
+                out,
+                "{}\n{} --> {}\n{}\n",
+                index + 1,
+                srt_timestamp(start),
+                srt_timestamp(end),
+                cue.text
+            ),
+            ExportFormat::Vtt => writeln!(
+                out,
+                "{} --> {}\n{}\n",
+                vtt_timestamp(start),
+                vtt_timestamp(end),
+                cue.text
+            ),
+            ExportFormat::Markdown | ExportFormat::Text => writeln!(out, "{}", cue.text),
+        };
+    }
+    out
+}
+
+fn render_text(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        out.push_str(&entry.text);
+        out.push('\n');
+    }
+    out
+}
+
+fn render_markdown(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    let mut current_day = None;
+    for entry in entries {
+        let day = entry.timestamp.date_naive();
+        if current_day != Some(day) {
+            if current_day.is_some() {
+                out.push('\n');
+            }
+            let _ = writeln!(out, "## {day}\n");
+            current_day = Some(day);
+        }
+        let _ = writeln!(
+            out,
+            "- **{}** {}",
+            entry.timestamp.format("%H:%M"),
+            entry.text
+        );
+    }
+    out
+}
+
+/// Subtitles timed relative to the first exported utterance. Each cue spans the
+/// recorded audio, ending where processing of the utterance began.
+fn history_cues(entries: &[Entry]) -> Vec<Cue> {
+    let spans: Vec<(i64, i64)> = entries
+        .iter()
+        .map(|entry| {
+            let audio_ms = (entry.metrics.audio_secs * 1000.0) as i64;
+            let end = entry.timestamp.timestamp_millis() - entry.metrics.end_to_end_ms() as i64;
+            (end - audio_ms, end)
+        })
+        .collect();
+    let origin = spans.first().map(|(start, _)| *start).unwrap_or_default();
+    entries
+        .iter()
+        .zip(spans)
+        .map(|(entry, (start, end))| Cue {
+            start_ms: start - origin,
+            end_ms: end - origin,
+            text: entry.text.clone(),
+        })
+        .collect()
+}
+
+fn srt_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        (ms / 60_000) % 60,
+        (ms / 1000) % 60,
+        ms % 1000
+    )
+}
+
+/// WebVTT uses a period before the milliseconds where SubRip has a comma.
+fn vtt_timestamp(ms: i64) -> String {
+    srt_timestamp(ms).replace(',', ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_since, render, render_cues, srt_timestamp, Cue, ExportFormat};
+    use crate::history::Entry;
+    use crate::metrics::UtteranceMetrics;
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    fn entry_at(secs: u32, text: &str) -> Entry {
+        let mut entry = Entry::new(text, &UtteranceMetrics::default());
+        entry.timestamp = Local.with_ymd_and_hms(2026, 3, 1, 9, 0, secs).unwrap();
+        entry.metrics.audio_secs = 2.0;
+        entry
+    }
+
+    #[test]
+    fn parses_since_values() {
+        let today = NaiveDate::from_ymd_opt(2026, 3, 10).unwrap();
+        assert_eq!(parse_since("today", today).unwrap(), today);
+        assert_eq!(
+            parse_since("yesterday", today).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 3, 9).unwrap()
+        );
+        assert_eq!(
+            parse_since("7d", today).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 3, 3).unwrap()
+        );
+        assert_eq!(
+            parse_since("2026-01-02", today).unwrap(),
+            NaiveDate::from_ymd_opt(2026, 1, 2).unwrap()
+        );
+        assert!(parse_since("last week", today).is_err());
+    }
+
+    #[test]
+    fn formats_srt_timestamps() {
+        assert_eq!(srt_timestamp(3_723_045), "01:02:03,045");
+        assert_eq!(srt_timestamp(-5), "00:00:00,000");
+    }
+
+    #[test]
+    fn renders_srt_relative_to_first_entry() {
+        let entries = vec![entry_at(10, "first line"), entry_at(15, "second line")];
+        let srt = render(&entries, ExportFormat::Srt);
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:02,000\nfirst line\n\n\
+             2\n00:00:05,000 --> 00:00:07,000\nsecond line\n\n"
+        );
+    }
+
+    #[test]
+    fn renders_vtt_cues() {
+        let cues = vec![Cue {
+            start_ms: 1_500,
+            end_ms: 3_250,
+            text: "hello there".into(),
+        }];
+        assert_eq!(
+            render_cues(&cues, ExportFormat::Vtt),
+            "WEBVTT\n\n00:00:01.500 --> 00:00:03.250\nhello there\n\n"
+        );
+    }
+
+    #[test]
+    fn renders_markdown_grouped_by_day() {
+        let entries = vec![entry_at(10, "hello")];
+        let md = render(&entries, ExportFormat::Markdown);
+        assert_eq!(md, "## 2026-03-01\n\n- **09:00** hello\n");
+    }
+}
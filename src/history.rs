@@ -0,0 +1,417 @@
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+use crate::metrics::UtteranceMetrics;
+
+/// One transcribed utterance, stored as a single JSON line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+    pub words: usize,
+    pub metrics: UtteranceMetrics,
+}
+
+impl Entry {
+    pub fn new(text: &str, metrics: &UtteranceMetrics) -> Self {
+        Self {
+            timestamp: Local::now(),
+            text: text.to_string(),
+            words: text.split_whitespace().count(),
+            metrics: metrics.clone(),
+        }
+    }
+}
+
+/// How the history file is protected at rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Encryption {
+    /// Plain JSONL.
+    #[default]
+    Off,
+    /// A random key kept in a separate file, created on first use.
+    KeyFile,
+    /// A key derived from the `WHISP_HISTORY_PASSPHRASE` environment variable.
+    Passphrase,
+}
+
+/// Where the history encryption key comes from.
+pub enum Secret {
+    KeyFile(PathBuf),
+    Passphrase(String),
+}
+
+/// First line of an encrypted history file; a passphrase file adds its salt.
+const ENCRYPTED_HEADER: &str = "#whisp-history xchacha20poly1305";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Encrypts history lines with XChaCha20-Poly1305; each line is stored as
+/// base64(nonce || ciphertext).
+struct Cipher(XChaCha20Poly1305);
+
+impl Cipher {
+    /// Builds the cipher described by an existing header line.
+    fn from_header(header: &str, secret: &Secret) -> Result<Self> {
+        let salt = header
+            .strip_prefix(ENCRYPTED_HEADER)
+            .ok_or_else(|| anyhow!("unrecognized history header"))?
+            .trim();
+        match secret {
+            Secret::KeyFile(path) => {
+                if !salt.is_empty() {
+                    bail!("History was encrypted with a passphrase; set [history] encryption = \"passphrase\"");
+                }
+                Ok(Self::new(&read_key_file(path)?))
+            }
+            Secret::Passphrase(passphrase) => {
+                if salt.is_empty() {
+                    bail!("History was encrypted with a key file; set [history] encryption = \"key-file\"");
+                }
+                let salt = BASE64.decode(salt).context("decoding history salt")?;
+                Self::from_passphrase(passphrase, &salt)
+            }
+        }
+    }
+
+    /// Builds a cipher for a new file and returns it with the header to write.
+    fn create(secret: &Secret) -> Result<(Self, String)> {
+        match secret {
+            Secret::KeyFile(path) => {
+                let key = if path.exists() {
+                    read_key_file(path)?
+                } else {
+                    create_key_file(path)?
+                };
+                Ok((Self::new(&key), ENCRYPTED_HEADER.to_string()))
+            }
+            Secret::Passphrase(passphrase) => {
+                let mut salt = [0u8; SALT_LEN];
+                OsRng.fill_bytes(&mut salt);
+                let header = format!("{ENCRYPTED_HEADER} {}", BASE64.encode(salt));
+                Ok((Self::from_passphrase(passphrase, &salt)?, header))
+            }
+        }
+    }
+
+    fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self(XChaCha20Poly1305::new(key.into()))
+    }
+
+    fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self> {
+        let mut key = [0u8; KEY_LEN];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| anyhow!("deriving history key: {err}"))?;
+        Ok(Self::new(&key))
+    }
+
+    fn seal(&self, plaintext: &str) -> Result<String> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow!("encrypting history entry"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(BASE64.encode(sealed))
+    }
+
+    fn unseal(&self, line: &str) -> Result<String> {
+        let sealed = BASE64.decode(line).context("decoding history line")?;
+        if sealed.len() < NONCE_LEN {
+            bail!("truncated history line");
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let plaintext = self
+            .0
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("wrong key or corrupted history line"))?;
+        String::from_utf8(plaintext).context("decrypted history line is not UTF-8")
+    }
+}
+
+fn read_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    let encoded = fs::read_to_string(path)
+        .with_context(|| format!("reading history key {}", path.display()))?;
+    BASE64
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|key| <[u8; KEY_LEN]>::try_from(key).ok())
+        .ok_or_else(|| anyhow!("{} is not a whisp history key", path.display()))
+}
+
+/// Writes a new random key readable only by the user.
+fn create_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    let mut key = [0u8; KEY_LEN];
+    OsRng.fill_bytes(&mut key);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating key directory {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .with_context(|| format!("creating history key {}", path.display()))?;
+    writeln!(file, "{}", BASE64.encode(key))
+        .with_context(|| format!("writing history key {}", path.display()))?;
+    log::info!(
+        "Created history key {}; back it up to keep history readable",
+        path.display()
+    );
+    Ok(key)
+}
+
+/// Returns the header line of an encrypted history file, if it is one.
+fn encrypted_header(path: &Path) -> Result<Option<String>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| format!("opening history file {}", path.display()))
+        }
+    };
+    let mut first = String::new();
+    BufReader::new(file)
+        .read_line(&mut first)
+        .with_context(|| format!("reading history from {}", path.display()))?;
+    Ok(first
+        .starts_with(ENCRYPTED_HEADER)
+        .then(|| first.trim_end().to_string()))
+}
+
+/// Append-only JSONL transcript history, optionally encrypted line by line.
+pub struct History {
+    path: PathBuf,
+    file: File,
+    cipher: Option<Cipher>,
+}
+
+impl History {
+    pub fn open(path: &Path, secret: Option<&Secret>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating history directory {}", parent.display()))?;
+        }
+        let header = encrypted_header(path)?;
+        let cipher = match (secret, header) {
+            (None, None) => None,
+            (None, Some(_)) => bail!(
+                "{} is encrypted; set [history] encryption to read and extend it",
+                path.display()
+            ),
+            (Some(secret), Some(header)) => Some(Cipher::from_header(&header, secret)?),
+            (Some(secret), None) => Some(encrypt_file(path, secret)?),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("opening history file {}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            cipher,
+        })
+    }
+
+    pub fn append(&mut self, entry: &Entry) -> Result<()> {
+        let mut line = serde_json::to_string(entry).context("serializing history entry")?;
+        if let Some(cipher) = &self.cipher {
+            line = cipher.seal(&line)?;
+        }
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .with_context(|| format!("writing history to {}", self.path.display()))
+    }
+
+    /// Makes sure every appended entry has reached the disk.
+    pub fn sync(&self) -> Result<()> {
+        self.file
+            .sync_data()
+            .with_context(|| format!("syncing history to {}", self.path.display()))
+    }
+}
+
+/// Starts an encrypted history file at `path`, carrying over any plaintext
+/// entries already there.
+fn encrypt_file(path: &Path, secret: &Secret) -> Result<Cipher> {
+    let existing = if path.exists() {
+        load(path, None)?
+    } else {
+        Vec::new()
+    };
+    let (cipher, header) = Cipher::create(secret)?;
+    let mut contents = header + "\n";
+    for entry in &existing {
+        let line = serde_json::to_string(entry).context("serializing history entry")?;
+        contents.push_str(&cipher.seal(&line)?);
+        contents.push('\n');
+    }
+    let tmp = path.with_extension("jsonl.tmp");
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .with_context(|| format!("writing history file {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("replacing history file {}", path.display()))?;
+    if !existing.is_empty() {
+        log::info!(
+            "Encrypted {} existing history entries in {}",
+            existing.len(),
+            path.display()
+        );
+    }
+    Ok(cipher)
+}
+
+/// Reads all entries from a history file, skipping lines that fail to parse.
+/// Encrypted files need `secret`.
+pub fn load(path: &Path, secret: Option<&Secret>) -> Result<Vec<Entry>> {
+    let cipher = match (encrypted_header(path)?, secret) {
+        (None, _) => None,
+        (Some(_), None) => bail!(
+            "{} is encrypted; set [history] encryption to read it",
+            path.display()
+        ),
+        (Some(header), Some(secret)) => Some(Cipher::from_header(&header, secret)?),
+    };
+    let file =
+        File::open(path).with_context(|| format!("opening history file {}", path.display()))?;
+    let mut entries = Vec::new();
+    let mut lines = BufReader::new(file).lines().enumerate();
+    if cipher.is_some() {
+        lines.next();
+    }
+    for (index, line) in lines {
+        let mut line = line.with_context(|| format!("reading history from {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(cipher) = &cipher {
+            match cipher.unseal(line.trim()) {
+                Ok(plaintext) => line = plaintext,
+                Err(err) => {
+                    log::warn!(
+                        "Skipping unreadable history line {} in {}: {err:#}",
+                        index + 1,
+                        path.display()
+                    );
+                    continue;
+                }
+            }
+        }
+        match serde_json::from_str::<Entry>(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => log::warn!(
+                "Skipping malformed history line {} in {}: {err}",
+                index + 1,
+                path.display()
+            ),
+        }
+    }
+    Ok(entries)
+}
+
+/// Aggregated usage for one calendar day.
+#[derive(Debug, Default, PartialEq)]
+pub struct DailyUsage {
+    pub utterances: u64,
+    pub words: u64,
+    pub audio_secs: f64,
+}
+
+/// Groups entries by local calendar day, oldest first.
+pub fn daily_usage(entries: &[Entry]) -> BTreeMap<NaiveDate, DailyUsage> {
+    let mut days: BTreeMap<NaiveDate, DailyUsage> = BTreeMap::new();
+    for entry in entries {
+        let day = days.entry(entry.timestamp.date_naive()).or_default();
+        day.utterances += 1;
+        day.words += entry.words as u64;
+        day.audio_secs += entry.metrics.audio_secs;
+    }
+    days
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{daily_usage, load, Entry, History, Secret};
+    use crate::metrics::UtteranceMetrics;
+    use chrono::{Local, TimeZone};
+
+    fn entry_at(day: u32, text: &str) -> Entry {
+        let mut entry = Entry::new(text, &UtteranceMetrics::default());
+        entry.timestamp = Local.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap();
+        entry.metrics.audio_secs = 2.0;
+        entry
+    }
+
+    #[test]
+    fn groups_usage_by_day() {
+        let entries = vec![
+            entry_at(1, "one two"),
+            entry_at(1, "three"),
+            entry_at(2, "four five six"),
+        ];
+        let days = daily_usage(&entries);
+        let totals: Vec<(u64, u64)> = days.values().map(|d| (d.utterances, d.words)).collect();
+        assert_eq!(totals, vec![(2, 3), (1, 3)]);
+    }
+
+    #[test]
+    fn entries_round_trip_through_json() {
+        let entry = entry_at(5, "hello world");
+        let line = serde_json::to_string(&entry).unwrap();
+        let parsed: Entry = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed.text, "hello world");
+        assert_eq!(parsed.words, 2);
+        assert_eq!(parsed.timestamp, entry.timestamp);
+    }
+
+    #[test]
+    fn encrypts_new_and_existing_entries() {
+        let dir = std::env::temp_dir().join(format!("whisp-history-test-{}", std::process::id()));
+        let path = dir.join("history.jsonl");
+        let secret = Secret::KeyFile(dir.join("history.key"));
+        let mut plain = History::open(&path, None).unwrap();
+        plain.append(&entry_at(1, "plain words")).unwrap();
+        drop(plain);
+
+        let mut history = History::open(&path, Some(&secret)).unwrap();
+        history.append(&entry_at(2, "secret words")).unwrap();
+        drop(history);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("words"));
+        let texts: Vec<String> = load(&path, Some(&secret))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.text)
+            .collect();
+        assert_eq!(texts, vec!["plain words", "secret words"]);
+        assert!(load(&path, None).is_err());
+        assert!(load(&path, Some(&Secret::Passphrase("hunter2".into()))).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,13 +1,44 @@
 use anyhow::{bail, Result};
 use evdev::Key;
-use std::path::PathBuf;
-use std::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+
+use crate::uinput;
+
+/// What a bound key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Push-to-talk recording key.
+    Record,
+    /// Erase the most recently typed transcription.
+    Undo,
+    /// Toggle numeric dictation mode.
+    ToggleNumeric,
+    /// Type a transcription again after typing it failed.
+    Retry,
+}
+
+/// How presses of the record hotkey start and stop a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordingMode {
+    /// Record while the key is held.
+    #[default]
+    Hold,
+    /// Holding records as in `Hold`; a quick tap keeps recording until the next tap.
+    TapToggle,
+    /// One press starts recording and the next press stops it; releases do nothing.
+    Toggle,
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HotkeyEvent {
-    Pressed,
-    Released,
+    Pressed(Action),
+    Released(Action),
 }
 
 const HOTKEY_EXAMPLES: &[&str] = &["a", "f13", "insert", "leftctrl", "leftmeta", "micmute"];
@@ -16,12 +47,28 @@ pub fn hotkey_examples() -> &'static [&'static str] {
     HOTKEY_EXAMPLES
 }
 
+/// Config name for a key ("insert" for KEY_INSERT); None for buttons and unnamed codes.
+pub fn key_name(key: Key) -> Option<String> {
+    format!("{key:?}")
+        .strip_prefix("KEY_")
+        .map(|name| name.to_ascii_lowercase())
+}
+
+/// Key codes run from 0 to KEY_MAX (0x2ff).
+const KEY_CODES: u16 = 768;
+
+/// How `key` is written in the config: its name, or `code:N` for a key evdev has no
+/// name for. None for buttons.
+pub fn config_name(key: Key) -> Option<String> {
+    key_name(key).or_else(|| {
+        format!("{key:?}")
+            .starts_with("unknown")
+            .then(|| format!("code:{}", key.code()))
+    })
+}
+
 pub fn list_supported_hotkeys() -> Vec<String> {
-    let mut keys: Vec<String> = (0..768u16)
-        .map(Key::new)
-        .map(|key| format!("{:?}", key))
-        .filter_map(|name| name.strip_prefix("KEY_").map(|n| n.to_ascii_lowercase()))
-        .collect();
+    let mut keys: Vec<String> = (0..KEY_CODES).map(Key::new).filter_map(key_name).collect();
     keys.sort();
     keys.dedup();
     keys
@@ -49,11 +96,21 @@ pub fn normalize_hotkey_name(name: &str) -> String {
 }
 
 /// Parse a hotkey name (e.g. "insert", "f4", "leftctrl") to an evdev Key.
-/// Matches against `KEY_{NAME}` debug representation for all key codes 0..768.
+/// Matches against `KEY_{NAME}` debug representation for all key codes 0..768;
+/// "code:N" names a raw key code directly.
 pub fn parse_hotkey(name: &str) -> Result<Key> {
     let canonical = normalize_hotkey_name(name);
+    if let Some(code) = canonical.strip_prefix("code:") {
+        return match code.parse::<u16>() {
+            Ok(code) if code < KEY_CODES => Ok(Key::new(code)),
+            _ => bail!(
+                "Invalid key code in '{name}'; use code:N with N from 0 to {}",
+                KEY_CODES - 1
+            ),
+        };
+    }
     let target = format!("KEY_{}", canonical.to_uppercase());
-    for code in 0..768u16 {
+    for code in 0..KEY_CODES {
         let key = Key::new(code);
         if format!("{:?}", key) == target {
             return Ok(key);
@@ -66,66 +123,377 @@ pub fn parse_hotkey(name: &str) -> Result<Key> {
     )
 }
 
-fn find_devices_with_key(target: Key) -> Vec<PathBuf> {
-    let mut paths = Vec::new();
-    for (path, device) in evdev::enumerate() {
-        if let Some(keys) = device.supported_keys() {
-            if keys.contains(target) {
-                paths.push(path);
-            }
+/// Whether `device` is whisp's own virtual keyboard, which reports every key and
+/// would feed typed text back into the listener.
+fn is_own_device(device: &evdev::Device) -> bool {
+    device.name() == Some(uinput::DEVICE_NAME)
+}
+
+/// Virtual devices (uinput, ydotool, remappers) rather than physical keyboards.
+fn is_virtual(device: &evdev::Device) -> bool {
+    is_own_device(device) || device.input_id().bus_type() == evdev::BusType::BUS_VIRTUAL
+}
+
+/// Which input devices the listener uses and which modifiers it requires.
+pub struct ListenerOptions<'a> {
+    /// Keys that must be held with the record key.
+    pub modifiers: &'a [String],
+    /// Also use virtual devices other than whisp's own.
+    pub virtual_keyboards: bool,
+    /// Only use devices whose evdev name, phys or uniq equals one of these; empty uses all.
+    pub keyboards: &'a [String],
+}
+
+/// An input device as `whisp --list-keyboards` shows it.
+pub struct KeyboardInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub phys: String,
+    pub uniq: String,
+}
+
+/// Whether `id` names `device` by its evdev name, phys or uniq, which unlike
+/// /dev/input/eventN stay the same across reboots.
+fn device_matches(device: &evdev::Device, id: &str) -> bool {
+    [device.name(), device.physical_path(), device.unique_name()].contains(&Some(id))
+}
+
+impl KeyboardInfo {
+    fn new(path: PathBuf, device: &evdev::Device) -> Self {
+        Self {
+            path,
+            name: device.name().unwrap_or_default().to_string(),
+            phys: device.physical_path().unwrap_or_default().to_string(),
+            uniq: device.unique_name().unwrap_or_default().to_string(),
         }
     }
-    paths
 }
 
-pub fn spawn_listener(hotkey_name: &str, tx: mpsc::Sender<HotkeyEvent>) -> Result<()> {
-    let key = parse_hotkey(hotkey_name)?;
-    let devices = find_devices_with_key(key);
+/// Devices that report keys with names, excluding whisp's own virtual keyboard.
+pub fn list_keyboards() -> Vec<KeyboardInfo> {
+    let mut keyboards: Vec<KeyboardInfo> = evdev::enumerate()
+        .filter(|(_, device)| {
+            !is_own_device(device)
+                && device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.iter().any(|key| key_name(key).is_some()))
+        })
+        .map(|(path, device)| KeyboardInfo::new(path, &device))
+        .collect();
+    keyboards.sort_by(|a, b| a.path.cmp(&b.path));
+    keyboards
+}
+
+/// Physical devices with the names of the keys each one reports, for
+/// `--list-hotkeys --available`.
+pub fn list_available_hotkeys() -> Vec<(KeyboardInfo, Vec<String>)> {
+    let mut devices: Vec<(KeyboardInfo, Vec<String>)> = evdev::enumerate()
+        .filter(|(_, device)| !is_virtual(device))
+        .filter_map(|(path, device)| {
+            let mut keys: Vec<String> = device
+                .supported_keys()?
+                .iter()
+                .filter_map(key_name)
+                .collect();
+            keys.sort();
+            keys.dedup();
+            (!keys.is_empty()).then(|| (KeyboardInfo::new(path, &device), keys))
+        })
+        .collect();
+    devices.sort_by(|(a, _), (b, _)| a.path.cmp(&b.path));
+    devices
+}
+
+/// Whether the listener should use `device`: it reports one of `targets` and passes
+/// the virtual device and `keyboards` filters.
+fn accepts(
+    device: &evdev::Device,
+    targets: &[Key],
+    virtual_keyboards: bool,
+    keyboards: &[String],
+) -> bool {
+    if is_own_device(device) || (!virtual_keyboards && is_virtual(device)) {
+        return false;
+    }
+    if !keyboards.is_empty() && !keyboards.iter().any(|id| device_matches(device, id)) {
+        return false;
+    }
+    device
+        .supported_keys()
+        .is_some_and(|keys| targets.iter().any(|target| keys.contains(*target)))
+}
+
+fn find_devices_with_keys(targets: &[Key], options: &ListenerOptions) -> Vec<PathBuf> {
+    evdev::enumerate()
+        .filter(|(_, device)| {
+            accepts(
+                device,
+                targets,
+                options.virtual_keyboards,
+                options.keyboards,
+            )
+        })
+        .map(|(path, _)| path)
+        .collect()
+}
+
+/// Waits for the next key press on any keyboard-like device, for interactive setup.
+/// Returns None if nothing is pressed within `timeout`. Other virtual keyboards are
+/// kept, since remappers like keyd deliver every key through one.
+pub fn wait_for_key_press(timeout: Duration) -> Result<Option<Key>> {
+    let devices: Vec<PathBuf> = evdev::enumerate()
+        .filter(|(_, device)| {
+            !is_own_device(device)
+                && device
+                    .supported_keys()
+                    .is_some_and(|keys| keys.iter().any(|key| key_name(key).is_some()))
+        })
+        .map(|(path, _)| path)
+        .collect();
     if devices.is_empty() {
         bail!(
-            "No input devices found with key {key:?}.\n\nFix: run 'sudo usermod -aG input $USER' then log out and back in."
+            "No readable keyboards found.\n\nFix: run 'sudo usermod -aG input $USER' then log out and back in."
         );
     }
 
+    let (tx, rx) = mpsc::channel();
     for path in devices {
         let tx = tx.clone();
         thread::spawn(move || {
             let Ok(mut dev) = evdev::Device::open(&path) else {
-                log::warn!("Could not open {}", path.display());
                 return;
             };
-            log::debug!("Listening on {}", path.display());
-            loop {
-                match dev.fetch_events() {
-                    Ok(events) => {
-                        for ev in events {
-                            if ev.event_type() == evdev::EventType::KEY && ev.code() == key.code() {
-                                let msg = match ev.value() {
-                                    1 => Some(HotkeyEvent::Pressed),
-                                    0 => Some(HotkeyEvent::Released),
-                                    _ => None, // repeat
-                                };
-                                if let Some(msg) = msg {
-                                    let _ = tx.send(msg);
-                                }
-                            }
+            while let Ok(events) = dev.fetch_events() {
+                for ev in events {
+                    if ev.event_type() == evdev::EventType::KEY && ev.value() == 1 {
+                        // The receiver is gone once a key was chosen; stop reading.
+                        if tx.send(Key::new(ev.code())).is_err() {
+                            return;
                         }
                     }
-                    Err(e) => {
-                        log::warn!("evdev read error on {}: {e}", path.display());
-                        break;
-                    }
                 }
             }
         });
     }
+    Ok(rx.recv_timeout(timeout).ok())
+}
+
+/// Warns about configured keyboards that aren't connected; hotplug picks them up later.
+fn check_keyboards(keyboards: &[String]) {
+    let devices: Vec<evdev::Device> = evdev::enumerate().map(|(_, device)| device).collect();
+    for id in keyboards {
+        if !devices.iter().any(|device| device_matches(device, id)) {
+            log::warn!(
+                "No input device matches keyboards entry '{id}' yet; listening once it connects. Run `whisp --list-keyboards` to see the name, phys and uniq of each connected keyboard."
+            );
+        }
+    }
+}
+
+/// How often /dev/input is scanned for newly connected keyboards.
+const HOTPLUG_POLL: Duration = Duration::from_secs(2);
+/// errno for reads from a device that was unplugged.
+const ENODEV: i32 = 19;
+
+/// Reads bound keys from input devices, one thread per device.
+#[derive(Clone)]
+struct Listener {
+    keys: Vec<(Action, Key)>,
+    modifiers: Vec<Key>,
+    tx: mpsc::SyncSender<HotkeyEvent>,
+    /// Devices with a running listener thread; each removes itself when it stops.
+    active: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl Listener {
+    fn spawn(&self, path: PathBuf) {
+        if !self.active.lock().unwrap().insert(path.clone()) {
+            return;
+        }
+        let listener = self.clone();
+        thread::spawn(move || {
+            listener.listen(&path);
+            listener.active.lock().unwrap().remove(&path);
+        });
+    }
+
+    fn listen(&self, path: &Path) {
+        let Ok(mut dev) = evdev::Device::open(path) else {
+            log::warn!("Could not open {}", path.display());
+            return;
+        };
+        log::debug!("Listening on {}", path.display());
+        // Modifiers down right now, seeded with any held before we started.
+        let mut held: Vec<Key> = dev
+            .get_key_state()
+            .map(|state| {
+                self.modifiers
+                    .iter()
+                    .copied()
+                    .filter(|m| state.contains(*m))
+                    .collect()
+            })
+            .unwrap_or_default();
+        // Whether the last record press counted, so its release matches.
+        let mut recording = false;
+        loop {
+            let events = match dev.fetch_events() {
+                Ok(events) => events,
+                Err(e) if e.raw_os_error() == Some(ENODEV) => {
+                    log::info!("{} was disconnected", path.display());
+                    return;
+                }
+                Err(e) => {
+                    log::warn!("evdev read error on {}: {e}", path.display());
+                    return;
+                }
+            };
+            for ev in events {
+                if ev.event_type() != evdev::EventType::KEY {
+                    continue;
+                }
+                if self.modifiers.iter().any(|m| m.code() == ev.code()) {
+                    let key = Key::new(ev.code());
+                    match ev.value() {
+                        1 if !held.contains(&key) => held.push(key),
+                        0 => held.retain(|m| *m != key),
+                        _ => {}
+                    }
+                }
+                let Some((action, _)) = self.keys.iter().find(|(_, key)| key.code() == ev.code())
+                else {
+                    continue;
+                };
+                // Record presses without the modifiers, and their releases,
+                // are left to the focused app.
+                if *action == Action::Record {
+                    let counts = match ev.value() {
+                        1 => {
+                            recording = self.modifiers.iter().all(|m| held.contains(m));
+                            recording
+                        }
+                        0 => std::mem::take(&mut recording),
+                        _ => recording,
+                    };
+                    if !counts {
+                        continue;
+                    }
+                }
+                let msg = match ev.value() {
+                    1 => Some(HotkeyEvent::Pressed(*action)),
+                    0 => Some(HotkeyEvent::Released(*action)),
+                    _ => None, // repeat
+                };
+                if let Some(msg) = msg {
+                    let _ = self.tx.send(msg);
+                }
+            }
+        }
+    }
+}
+
+/// Spawns one listener thread per input device that reports any of the bound keys,
+/// and keeps watching /dev/input so keyboards connected later are used too.
+/// The record key only counts while every key in `modifiers` is held on the same device.
+pub fn spawn_listener(
+    bindings: &[(Action, &str)],
+    options: &ListenerOptions,
+    tx: mpsc::SyncSender<HotkeyEvent>,
+) -> Result<()> {
+    let mut keys: Vec<(Action, Key)> = Vec::with_capacity(bindings.len());
+    for (action, name) in bindings {
+        keys.push((*action, parse_hotkey(name)?));
+    }
+    let modifiers = options
+        .modifiers
+        .iter()
+        .map(|name| parse_hotkey(name))
+        .collect::<Result<Vec<Key>>>()?;
+    check_keyboards(options.keyboards);
+    let targets: Vec<Key> = keys.iter().map(|(_, key)| *key).collect();
+    let devices = find_devices_with_keys(&targets, options);
+    let with_virtual = ListenerOptions {
+        virtual_keyboards: true,
+        ..*options
+    };
+    if devices.is_empty()
+        && !options.virtual_keyboards
+        && !find_devices_with_keys(&targets, &with_virtual).is_empty()
+    {
+        bail!(
+            "Only virtual input devices (e.g. from keyd or input-remapper) have keys {targets:?}.\n\nFix: set virtual_keyboards = true in the config."
+        );
+    }
+    if devices.is_empty() && options.keyboards.is_empty() {
+        bail!(
+            "No input devices found with keys {targets:?}.\n\nFix: run 'sudo usermod -aG input $USER' then log out and back in."
+        );
+    }
+
+    let listener = Listener {
+        keys,
+        modifiers,
+        tx,
+        active: Arc::default(),
+    };
+    for path in devices {
+        listener.spawn(path);
+    }
+
+    let virtual_keyboards = options.virtual_keyboards;
+    let keyboards = options.keyboards.to_vec();
+    thread::spawn(move || {
+        let mut seen = input_nodes();
+        loop {
+            thread::sleep(HOTPLUG_POLL);
+            let present = input_nodes();
+            let mut unreadable = Vec::new();
+            for path in present.difference(&seen) {
+                // udev may not have granted access yet; the next scan retries.
+                let Ok(device) = evdev::Device::open(path) else {
+                    unreadable.push(path.clone());
+                    continue;
+                };
+                if accepts(&device, &targets, virtual_keyboards, &keyboards) {
+                    log::info!(
+                        "Keyboard connected: {} ({})",
+                        device.name().unwrap_or("unnamed"),
+                        path.display()
+                    );
+                    listener.spawn(path.clone());
+                }
+            }
+            seen = present;
+            for path in &unreadable {
+                seen.remove(path);
+            }
+        }
+    });
 
     Ok(())
 }
 
+/// Event device nodes currently in /dev/input.
+fn input_nodes() -> HashSet<PathBuf> {
+    std::fs::read_dir("/dev/input")
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with("event"))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_hotkey;
+    use super::{config_name, parse_hotkey};
+    use evdev::Key;
 
     #[test]
     fn parses_super_aliases() {
@@ -154,4 +522,15 @@ mod tests {
             parse_hotkey("leftshift").expect("leftshift should parse")
         );
     }
+
+    #[test]
+    fn parses_numeric_key_codes() {
+        assert_eq!(parse_hotkey("code:183").unwrap(), Key::KEY_F13);
+        assert_eq!(parse_hotkey("Code: 767").unwrap(), Key::new(767));
+        assert!(parse_hotkey("code:768").is_err());
+        assert!(parse_hotkey("code:f13").is_err());
+        assert_eq!(config_name(Key::KEY_F13).as_deref(), Some("f13"));
+        assert_eq!(config_name(Key::new(767)).as_deref(), Some("code:767"));
+        assert_eq!(config_name(Key::BTN_LEFT), None);
+    }
 }
@@ -0,0 +1,497 @@
+use anyhow::{bail, Context, Result};
+use evdev::Key;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Modifiers held to reach a character on its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Base,
+    Shift,
+    /// Right Alt (ISO level 3).
+    AltGr,
+    ShiftAltGr,
+}
+
+const LEVELS: [Level; 4] = [Level::Base, Level::Shift, Level::AltGr, Level::ShiftAltGr];
+
+/// Which key and modifiers type each character on the desktop's keyboard layout.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    keys: HashMap<char, (Key, Level)>,
+}
+
+/// Characters on each key by level (base, shift, AltGr, shift+AltGr); '\0' is unused.
+type Layout = &'static [(Key, [char; 4])];
+
+/// Keys whose characters every layout shares.
+const COMMON: Layout = &[
+    (Key::KEY_SPACE, [' ', '\0', '\0', '\0']),
+    (Key::KEY_ENTER, ['\n', '\0', '\0', '\0']),
+    (Key::KEY_TAB, ['\t', '\0', '\0', '\0']),
+];
+
+/// German QWERTZ (`de`), without dead keys.
+const DE: Layout = &[
+    (Key::KEY_GRAVE, ['\0', '°', '\0', '\0']),
+    (Key::KEY_1, ['1', '!', '¹', '¡']),
+    (Key::KEY_2, ['2', '"', '²', '\0']),
+    (Key::KEY_3, ['3', '§', '³', '\0']),
+    (Key::KEY_4, ['4', '$', '¼', '¤']),
+    (Key::KEY_5, ['5', '%', '½', '\0']),
+    (Key::KEY_6, ['6', '&', '¬', '\0']),
+    (Key::KEY_7, ['7', '/', '{', '\0']),
+    (Key::KEY_8, ['8', '(', '[', '\0']),
+    (Key::KEY_9, ['9', ')', ']', '±']),
+    (Key::KEY_0, ['0', '=', '}', '°']),
+    (Key::KEY_MINUS, ['ß', '?', '\\', '¿']),
+    (Key::KEY_Q, ['q', 'Q', '@', '\0']),
+    (Key::KEY_W, ['w', 'W', '\0', '\0']),
+    (Key::KEY_E, ['e', 'E', '€', '\0']),
+    (Key::KEY_R, ['r', 'R', '¶', '®']),
+    (Key::KEY_T, ['t', 'T', '\0', '\0']),
+    (Key::KEY_Y, ['z', 'Z', '\0', '\0']),
+    (Key::KEY_U, ['u', 'U', '\0', '\0']),
+    (Key::KEY_I, ['i', 'I', '\0', '\0']),
+    (Key::KEY_O, ['o', 'O', 'ø', 'Ø']),
+    (Key::KEY_P, ['p', 'P', 'þ', 'Þ']),
+    (Key::KEY_LEFTBRACE, ['ü', 'Ü', '\0', '\0']),
+    (Key::KEY_RIGHTBRACE, ['+', '*', '~', '¯']),
+    (Key::KEY_A, ['a', 'A', 'æ', 'Æ']),
+    (Key::KEY_S, ['s', 'S', '\0', '\0']),
+    (Key::KEY_D, ['d', 'D', 'ð', 'Ð']),
+    (Key::KEY_F, ['f', 'F', '\0', 'ª']),
+    (Key::KEY_G, ['g', 'G', '\0', '\0']),
+    (Key::KEY_H, ['h', 'H', '\0', '\0']),
+    (Key::KEY_J, ['j', 'J', '\0', '\0']),
+    (Key::KEY_K, ['k', 'K', '\0', '&']),
+    (Key::KEY_L, ['l', 'L', '\0', '\0']),
+    (Key::KEY_SEMICOLON, ['ö', 'Ö', '\0', '\0']),
+    (Key::KEY_APOSTROPHE, ['ä', 'Ä', '\0', '\0']),
+    (Key::KEY_BACKSLASH, ['#', '\'', '\0', '\0']),
+    (Key::KEY_102ND, ['<', '>', '|', '\0']),
+    (Key::KEY_Z, ['y', 'Y', '»', '›']),
+    (Key::KEY_X, ['x', 'X', '«', '‹']),
+    (Key::KEY_C, ['c', 'C', '¢', '©']),
+    (Key::KEY_V, ['v', 'V', '„', '‚']),
+    (Key::KEY_B, ['b', 'B', '“', '‘']),
+    (Key::KEY_N, ['n', 'N', '”', '’']),
+    (Key::KEY_M, ['m', 'M', 'µ', 'º']),
+    (Key::KEY_COMMA, [',', ';', '·', '×']),
+    (Key::KEY_DOT, ['.', ':', '…', '÷']),
+    (Key::KEY_SLASH, ['-', '_', '–', '—']),
+];
+
+/// French AZERTY (`fr`), without dead keys.
+const FR: Layout = &[
+    (Key::KEY_GRAVE, ['²', '\0', '\0', '\0']),
+    (Key::KEY_1, ['&', '1', '\0', '\0']),
+    (Key::KEY_2, ['é', '2', '\0', '\0']),
+    (Key::KEY_3, ['"', '3', '#', '\0']),
+    (Key::KEY_4, ['\'', '4', '{', '\0']),
+    (Key::KEY_5, ['(', '5', '[', '\0']),
+    (Key::KEY_6, ['-', '6', '|', '\0']),
+    (Key::KEY_7, ['è', '7', '\0', '\0']),
+    (Key::KEY_8, ['_', '8', '\\', '\0']),
+    (Key::KEY_9, ['ç', '9', '^', '\0']),
+    (Key::KEY_0, ['à', '0', '@', '\0']),
+    (Key::KEY_MINUS, [')', '°', ']', '\0']),
+    (Key::KEY_EQUAL, ['=', '+', '}', '\0']),
+    (Key::KEY_Q, ['a', 'A', 'æ', 'Æ']),
+    (Key::KEY_W, ['z', 'Z', '«', '\0']),
+    (Key::KEY_E, ['e', 'E', '€', '\0']),
+    (Key::KEY_R, ['r', 'R', '¶', '®']),
+    (Key::KEY_T, ['t', 'T', '\0', '\0']),
+    (Key::KEY_Y, ['y', 'Y', '\0', '¥']),
+    (Key::KEY_U, ['u', 'U', '\0', '\0']),
+    (Key::KEY_I, ['i', 'I', '\0', '\0']),
+    (Key::KEY_O, ['o', 'O', 'ø', 'Ø']),
+    (Key::KEY_P, ['p', 'P', 'þ', 'Þ']),
+    (Key::KEY_RIGHTBRACE, ['$', '£', '¤', '\0']),
+    (Key::KEY_A, ['q', 'Q', '\0', '\0']),
+    (Key::KEY_S, ['s', 'S', 'ß', '\0']),
+    (Key::KEY_D, ['d', 'D', 'ð', 'Ð']),
+    (Key::KEY_F, ['f', 'F', '\0', 'ª']),
+    (Key::KEY_G, ['g', 'G', '\0', '\0']),
+    (Key::KEY_H, ['h', 'H', '\0', '\0']),
+    (Key::KEY_J, ['j', 'J', '\0', '\0']),
+    (Key::KEY_K, ['k', 'K', '\0', '\0']),
+    (Key::KEY_L, ['l', 'L', '\0', '\0']),
+    (Key::KEY_SEMICOLON, ['m', 'M', 'µ', 'º']),
+    (Key::KEY_APOSTROPHE, ['ù', '%', '\0', '\0']),
+    (Key::KEY_BACKSLASH, ['*', 'µ', '\0', '\0']),
+    (Key::KEY_102ND, ['<', '>', '\0', '\0']),
+    (Key::KEY_Z, ['w', 'W', '\0', '\0']),
+    (Key::KEY_X, ['x', 'X', '»', '\0']),
+    (Key::KEY_C, ['c', 'C', '©', '\0']),
+    (Key::KEY_V, ['v', 'V', '\0', '\0']),
+    (Key::KEY_B, ['b', 'B', '\0', '\0']),
+    (Key::KEY_N, ['n', 'N', '¬', '\0']),
+    (Key::KEY_M, [',', '?', '¿', '\0']),
+    (Key::KEY_COMMA, [';', '.', '×', '\0']),
+    (Key::KEY_DOT, [':', '/', '÷', '\0']),
+    (Key::KEY_SLASH, ['!', '§', '¡', '\0']),
+];
+
+/// Keysym names of ASCII punctuation (and the euro sign); letters and digits
+/// are named by themselves.
+const ASCII_NAMES: &[(&str, char)] = &[
+    ("space", ' '),
+    ("exclam", '!'),
+    ("quotedbl", '"'),
+    ("numbersign", '#'),
+    ("dollar", '$'),
+    ("percent", '%'),
+    ("ampersand", '&'),
+    ("apostrophe", '\''),
+    ("parenleft", '('),
+    ("parenright", ')'),
+    ("asterisk", '*'),
+    ("plus", '+'),
+    ("comma", ','),
+    ("minus", '-'),
+    ("period", '.'),
+    ("slash", '/'),
+    ("colon", ':'),
+    ("semicolon", ';'),
+    ("less", '<'),
+    ("equal", '='),
+    ("greater", '>'),
+    ("question", '?'),
+    ("at", '@'),
+    ("bracketleft", '['),
+    ("backslash", '\\'),
+    ("bracketright", ']'),
+    ("asciicircum", '^'),
+    ("underscore", '_'),
+    ("grave", '`'),
+    ("braceleft", '{'),
+    ("bar", '|'),
+    ("braceright", '}'),
+    ("asciitilde", '~'),
+    ("EuroSign", '€'),
+];
+
+/// Keysym names for U+00A0..=U+00FF, in code point order.
+const LATIN1_NAMES: &[&str; 96] = &[
+    "nobreakspace",
+    "exclamdown",
+    "cent",
+    "sterling",
+    "currency",
+    "yen",
+    "brokenbar",
+    "section",
+    "diaeresis",
+    "copyright",
+    "ordfeminine",
+    "guillemotleft",
+    "notsign",
+    "hyphen",
+    "registered",
+    "macron",
+    "degree",
+    "plusminus",
+    "twosuperior",
+    "threesuperior",
+    "acute",
+    "mu",
+    "paragraph",
+    "periodcentered",
+    "cedilla",
+    "onesuperior",
+    "masculine",
+    "guillemotright",
+    "onequarter",
+    "onehalf",
+    "threequarters",
+    "questiondown",
+    "Agrave",
+    "Aacute",
+    "Acircumflex",
+    "Atilde",
+    "Adiaeresis",
+    "Aring",
+    "AE",
+    "Ccedilla",
+    "Egrave",
+    "Eacute",
+    "Ecircumflex",
+    "Ediaeresis",
+    "Igrave",
+    "Iacute",
+    "Icircumflex",
+    "Idiaeresis",
+    "ETH",
+    "Ntilde",
+    "Ograve",
+    "Oacute",
+    "Ocircumflex",
+    "Otilde",
+    "Odiaeresis",
+    "multiply",
+    "Oslash",
+    "Ugrave",
+    "Uacute",
+    "Ucircumflex",
+    "Udiaeresis",
+    "Yacute",
+    "THORN",
+    "ssharp",
+    "agrave",
+    "aacute",
+    "acircumflex",
+    "atilde",
+    "adiaeresis",
+    "aring",
+    "ae",
+    "ccedilla",
+    "egrave",
+    "eacute",
+    "ecircumflex",
+    "ediaeresis",
+    "igrave",
+    "iacute",
+    "icircumflex",
+    "idiaeresis",
+    "eth",
+    "ntilde",
+    "ograve",
+    "oacute",
+    "ocircumflex",
+    "otilde",
+    "odiaeresis",
+    "division",
+    "oslash",
+    "ugrave",
+    "uacute",
+    "ucircumflex",
+    "udiaeresis",
+    "yacute",
+    "thorn",
+    "ydiaeresis",
+];
+
+impl Keymap {
+    /// A built-in layout ("us", "de", "fr") or the path of a compiled XKB keymap
+    /// (`xkbcli compile-keymap --layout ...` or `xkbcomp $DISPLAY out.xkb`).
+    pub fn load(name: &str) -> Result<Self> {
+        match name {
+            "us" => Ok(Self::us()),
+            "de" => Ok(Self::from_layout(DE)),
+            "fr" => Ok(Self::from_layout(FR)),
+            path if path.contains('/') => {
+                let text = fs::read_to_string(path)
+                    .with_context(|| format!("reading keymap {}", Path::new(path).display()))?;
+                Self::parse_xkb(&text).with_context(|| format!("parsing keymap {path}"))
+            }
+            other => bail!(
+                "Unknown keymap '{other}'. Use \"us\", \"de\", \"fr\", or the path to a compiled XKB keymap."
+            ),
+        }
+    }
+
+    pub fn us() -> Self {
+        let keys = (' '..='~')
+            .chain(['\n', '\t'])
+            .filter_map(|ch| {
+                let (key, shift) = crate::uinput::char_to_key(ch)?;
+                Some((ch, (key, if shift { Level::Shift } else { Level::Base })))
+            })
+            .collect();
+        Self { keys }
+    }
+
+    fn from_layout(layout: Layout) -> Self {
+        let mut keymap = Self {
+            keys: HashMap::new(),
+        };
+        for (key, chars) in COMMON.iter().chain(layout) {
+            keymap.insert(*key, chars);
+        }
+        keymap
+    }
+
+    /// Records the characters of one key, keeping the first (easiest) way to
+    /// type a character that appears on several keys.
+    fn insert(&mut self, key: Key, chars: &[char; 4]) {
+        for (ch, level) in chars.iter().zip(LEVELS) {
+            if *ch != '\0' {
+                self.keys.entry(*ch).or_insert((key, level));
+            }
+        }
+    }
+
+    /// Key and modifiers that type `ch`, if the layout has it.
+    pub fn get(&self, ch: char) -> Option<(Key, Level)> {
+        self.keys.get(&ch).copied()
+    }
+
+    /// Reads the `xkb_keycodes` and first-group `xkb_symbols` of a compiled keymap.
+    fn parse_xkb(text: &str) -> Result<Self> {
+        let mut codes: HashMap<&str, u16> = HashMap::new();
+        for line in text.lines().map(str::trim) {
+            // <AC01> = 38;  (X keycodes are evdev codes plus 8)
+            let Some((name, code)) = line.split_once('=') else {
+                continue;
+            };
+            let name = name.trim();
+            if !(name.starts_with('<') && name.ends_with('>')) {
+                continue;
+            }
+            if let Ok(code) = code.trim().trim_end_matches(';').trim().parse::<u16>() {
+                if code >= 8 {
+                    codes.insert(name, code - 8);
+                }
+            }
+        }
+
+        let mut keymap = Self::from_layout(&[]);
+        for line in text.lines().map(str::trim) {
+            // key <AC01> { [ a, A, ae, AE ] };
+            let Some(rest) = line.strip_prefix("key ") else {
+                continue;
+            };
+            let Some((name, rest)) = rest.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let rest = rest
+                .split_once("symbols[Group1]")
+                .map_or(rest, |(_, symbols)| symbols);
+            let (Some(code), Some(start), Some(end)) =
+                (codes.get(name.trim()), rest.find('['), rest.find(']'))
+            else {
+                continue;
+            };
+            if end < start {
+                continue;
+            }
+            let mut chars = ['\0'; 4];
+            for (slot, symbol) in chars.iter_mut().zip(rest[start + 1..end].split(',')) {
+                *slot = keysym_char(symbol.trim()).unwrap_or('\0');
+            }
+            keymap.insert(Key::new(*code), &chars);
+        }
+        if keymap.keys.len() <= COMMON.len() {
+            bail!("no key symbols found; expected a compiled keymap with xkb_keycodes and xkb_symbols");
+        }
+        Ok(keymap)
+    }
+}
+
+/// Character produced by an XKB keysym name ("a", "eacute", "U20AC", "0x1000e9").
+fn keysym_char(name: &str) -> Option<char> {
+    let mut chars = name.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return ch.is_ascii_alphanumeric().then_some(ch);
+    }
+    if let Some((_, ch)) = ASCII_NAMES.iter().find(|(keysym, _)| *keysym == name) {
+        return Some(*ch);
+    }
+    if let Some(index) = LATIN1_NAMES.iter().position(|keysym| *keysym == name) {
+        return char::from_u32(0xa0 + index as u32);
+    }
+    let code = if let Some(hex) = name.strip_prefix("0x100") {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(hex) = name.strip_prefix('U') {
+        u32::from_str_radix(hex, 16).ok()?
+    } else {
+        return None;
+    };
+    char::from_u32(code).filter(|ch| !ch.is_control())
+}
+
+/// Compose-key accents: the key typed before a base letter and the letters it
+/// produces, position by position.
+const COMPOSE_ACCENTS: &[(char, &str, &str)] = &[
+    ('\'', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('"', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+    (',', "cC", "çÇ"),
+    ('o', "aA", "åÅ"),
+];
+
+/// Other characters with a standard two-key compose sequence.
+const COMPOSE_SYMBOLS: &[(char, [char; 2])] = &[
+    ('ß', ['s', 's']),
+    ('æ', ['a', 'e']),
+    ('Æ', ['A', 'E']),
+    ('œ', ['o', 'e']),
+    ('Œ', ['O', 'E']),
+    ('ø', ['/', 'o']),
+    ('Ø', ['/', 'O']),
+    ('«', ['<', '<']),
+    ('»', ['>', '>']),
+    ('¡', ['!', '!']),
+    ('¿', ['?', '?']),
+    ('°', ['o', 'o']),
+    ('€', ['=', 'e']),
+    ('£', ['L', '-']),
+];
+
+/// The two characters typed after the compose key to produce `ch` (X11's default
+/// compose table), for Latin characters the layout has no key for.
+pub fn compose_sequence(ch: char) -> Option<[char; 2]> {
+    if let Some((_, sequence)) = COMPOSE_SYMBOLS.iter().find(|(symbol, _)| *symbol == ch) {
+        return Some(*sequence);
+    }
+    COMPOSE_ACCENTS
+        .iter()
+        .find_map(|(accent, bases, composed)| {
+            let index = composed.chars().position(|c| c == ch)?;
+            Some([*accent, bases.chars().nth(index)?])
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compose_sequence, keysym_char, Keymap, Level};
+    use evdev::Key;
+
+    #[test]
+    fn built_in_layouts_place_characters() {
+        let de = Keymap::load("de").unwrap();
+        assert_eq!(de.get('z'), Some((Key::KEY_Y, Level::Base)));
+        assert_eq!(de.get('Ü'), Some((Key::KEY_LEFTBRACE, Level::Shift)));
+        assert_eq!(de.get('@'), Some((Key::KEY_Q, Level::AltGr)));
+        let fr = Keymap::load("fr").unwrap();
+        assert_eq!(fr.get('1'), Some((Key::KEY_1, Level::Shift)));
+        assert_eq!(fr.get('é'), Some((Key::KEY_2, Level::Base)));
+        assert_eq!(Keymap::us().get('?'), Some((Key::KEY_SLASH, Level::Shift)));
+        assert!(Keymap::load("xx").is_err());
+    }
+
+    #[test]
+    fn parses_compiled_xkb_keymaps() {
+        let text = r#"
+xkb_keymap {
+xkb_keycodes "evdev+aliases(qwerty)" {
+    <AD01> = 24;
+    <AC10> = 47;
+};
+xkb_symbols "pc+es" {
+    key <AD01> { [ q, Q, at, Greek_OMEGA ] };
+    key <AC10> { type= "FOUR_LEVEL_SEMIALPHABETIC", symbols[Group1]= [ ntilde, Ntilde, asciitilde, dead_doubleacute ] };
+};
+};
+"#;
+        let keymap = Keymap::parse_xkb(text).unwrap();
+        assert_eq!(keymap.get('ñ'), Some((Key::KEY_SEMICOLON, Level::Base)));
+        assert_eq!(keymap.get('@'), Some((Key::KEY_Q, Level::AltGr)));
+        assert_eq!(keymap.get('~'), Some((Key::KEY_SEMICOLON, Level::AltGr)));
+        assert_eq!(keysym_char("U20AC"), Some('€'));
+    }
+
+    #[test]
+    fn finds_compose_sequences_for_latin_characters() {
+        assert_eq!(compose_sequence('é'), Some(['\'', 'e']));
+        assert_eq!(compose_sequence('Ü'), Some(['"', 'U']));
+        assert_eq!(compose_sequence('ñ'), Some(['~', 'n']));
+        assert_eq!(compose_sequence('ç'), Some([',', 'c']));
+        assert_eq!(compose_sequence('ß'), Some(['s', 's']));
+        assert_eq!(compose_sequence('你'), None);
+    }
+}
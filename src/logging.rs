@@ -0,0 +1,278 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::config::LoggingConfig;
+
+/// Forwards every record to stderr (unless a full-screen UI owns the terminal) and,
+/// when enabled, to a rotating log file.
+struct Logger {
+    stderr: env_logger::Logger,
+    to_stderr: bool,
+    file: Option<env_logger::Logger>,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.to_stderr {
+            self.stderr.log(record);
+        }
+        if let Some(file) = &self.file {
+            file.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(file) = &self.file {
+            file.flush();
+        }
+    }
+}
+
+/// Modules that `[logging] levels` can tune, by their name without the `whisp::` prefix.
+pub const TARGETS: &[&str] = &[
+    "audio",
+    "hotkey",
+    "transcriber",
+    "output",
+    "postprocess",
+    "history",
+    "config",
+    "uinput",
+    "portal",
+    "atspi",
+    "obs",
+    "speech",
+];
+
+/// Checks `[logging] levels` and turns it into `(log target, level)` pairs.
+pub fn parse_levels(levels: &BTreeMap<String, String>) -> Result<Vec<(String, log::LevelFilter)>> {
+    levels
+        .iter()
+        .map(|(target, level)| {
+            if !TARGETS.contains(&target.as_str()) {
+                bail!(
+                    "Unknown logging.levels target '{target}'. Available: {}",
+                    TARGETS.join(", ")
+                );
+            }
+            let Ok(level) = log::LevelFilter::from_str(level) else {
+                bail!("logging.levels.{target} must be off, error, warn, info, debug, or trace");
+            };
+            Ok((format!("whisp::{target}"), level))
+        })
+        .collect()
+}
+
+/// Config levels apply on top of the `info` default; `RUST_LOG` still overrides both.
+fn builder(levels: &[(String, log::LevelFilter)]) -> env_logger::Builder {
+    let mut builder = env_logger::Builder::new();
+    builder.filter_level(log::LevelFilter::Info);
+    for (target, level) in levels {
+        builder.filter_module(target, *level);
+    }
+    builder.parse_env(env_logger::Env::default());
+    builder
+}
+
+/// Set by `privacy = true`: transcript text is replaced by its length wherever it
+/// would be logged or displayed.
+static PRIVACY: AtomicBool = AtomicBool::new(false);
+
+pub fn set_privacy(enabled: bool) {
+    PRIVACY.store(enabled, Ordering::Relaxed);
+}
+
+/// Transcript text as it may appear in logs; format with `{}`.
+pub fn redact(text: &str) -> Redacted<'_> {
+    Redacted(text)
+}
+
+pub struct Redacted<'a>(&'a str);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_redacted(f, self.0, PRIVACY.load(Ordering::Relaxed))
+    }
+}
+
+fn write_redacted(f: &mut impl fmt::Write, text: &str, private: bool) -> fmt::Result {
+    if private {
+        write!(f, "[{} chars]", text.chars().count())
+    } else {
+        f.write_str(text)
+    }
+}
+
+/// Installs the global logger. Returns the log file path when file output is enabled.
+/// With `to_stderr` false, records only go to the log file (if any).
+pub fn init(config: &LoggingConfig, to_stderr: bool) -> Result<Option<PathBuf>> {
+    let levels = parse_levels(&config.levels)?;
+    let stderr = builder(&levels).build();
+    let max_level = stderr.filter();
+
+    let (file, path) = if config.file {
+        let path = config.file_path();
+        let writer = RotatingFile::open(&path, config.max_size_kb * 1024, config.max_files)
+            .with_context(|| format!("opening log file {}", path.display()))?;
+        let logger = builder(&levels)
+            .target(env_logger::Target::Pipe(Box::new(writer)))
+            .write_style(env_logger::WriteStyle::Never)
+            .build();
+        (Some(logger), Some(path))
+    } else {
+        (None, None)
+    };
+
+    log::set_boxed_logger(Box::new(Logger {
+        stderr,
+        to_stderr,
+        file,
+    }))
+    .context("installing logger")?;
+    log::set_max_level(max_level);
+    Ok(path)
+}
+
+/// Append-only log file that rotates `whisp.log` -> `whisp.log.1` -> ... once it
+/// exceeds `max_bytes`, keeping at most `max_files` rotated copies.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let len = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes,
+            max_files,
+            file,
+            len,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files == 0 {
+            self.file = File::create(&self.path)?;
+            self.len = 0;
+            return Ok(());
+        }
+
+        for index in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, index);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, index + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.len > 0 && self.len + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_levels, rotated_path, write_redacted, RotatingFile};
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::io::Write;
+
+    #[test]
+    fn redacts_text_to_its_length() {
+        let mut out = String::new();
+        write_redacted(&mut out, "my PIN is 1234", true).unwrap();
+        assert_eq!(out, "[14 chars]");
+        out.clear();
+        write_redacted(&mut out, "hello", false).unwrap();
+        assert_eq!(out, "hello");
+    }
+
+    #[test]
+    fn maps_module_levels_to_targets() {
+        let levels = BTreeMap::from([
+            ("transcriber".to_string(), "debug".to_string()),
+            ("hotkey".to_string(), "WARN".to_string()),
+        ]);
+        assert_eq!(
+            parse_levels(&levels).unwrap(),
+            vec![
+                ("whisp::hotkey".to_string(), log::LevelFilter::Warn),
+                ("whisp::transcriber".to_string(), log::LevelFilter::Debug),
+            ]
+        );
+        let typo = BTreeMap::from([("audoi".to_string(), "debug".to_string())]);
+        assert!(parse_levels(&typo).is_err());
+        let level = BTreeMap::from([("audio".to_string(), "loud".to_string())]);
+        assert!(parse_levels(&level).is_err());
+    }
+
+    #[test]
+    fn rotates_and_caps_old_files() {
+        let dir = std::env::temp_dir().join(format!("whisp-log-test-{}", std::process::id()));
+        let path = dir.join("whisp.log");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut file = RotatingFile::open(&path, 10, 2).expect("open log file");
+        for line in ["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.write_all(line.as_bytes()).expect("write log line");
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "dddddddd\n");
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "cccccccc\n"
+        );
+        assert_eq!(
+            fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "bbbbbbbb\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,30 +1,96 @@
+#[cfg(feature = "atspi")]
+mod atspi;
 mod audio;
+mod build_info;
+mod check;
+mod cloud;
 mod config;
+mod eval;
+mod export;
+mod history;
 mod hotkey;
+mod keymap;
+mod logging;
+mod media;
+mod meeting;
+mod metrics;
+#[cfg(feature = "obs")]
+mod obs;
 mod output;
+mod picker;
+#[cfg(feature = "portal")]
+mod portal;
+mod postprocess;
+mod service;
+mod session;
+#[cfg(feature = "settings-gui")]
+mod settings;
+mod setup;
+mod speech;
 mod transcriber;
+mod tui;
 mod uinput;
 mod util;
+#[cfg(feature = "vosk")]
+mod vosk;
 
 use anyhow::{bail, Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{mpsc, Arc};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Typing speed used to estimate how much typing dictation replaced.
+const TYPING_WPM: u64 = 40;
+/// Key events buffered while the main loop is busy.
+const HOTKEY_CAPACITY: usize = 64;
+/// Time to focus the target window before `--emit` types.
+const EMIT_DELAY_SECS: u64 = 3;
+/// How long shutdown waits for queued recordings to be transcribed and typed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `whisp config get <key>` / `whisp config set <key> <value>`.
+#[derive(Debug)]
+enum ConfigCommand {
+    Get(String),
+    Set(String, String),
+}
 
 #[derive(Default, Debug)]
 struct CliOptions {
     show_help: bool,
     show_version: bool,
     list_hotkeys: bool,
+    available: bool,
     list_audio_devices: bool,
+    list_keyboards: bool,
+    list_models: bool,
+    pick_device: bool,
     write_default_config: bool,
+    setup: bool,
+    install_service: bool,
+    settings: bool,
     force: bool,
     config_path: Option<PathBuf>,
     check_only: bool,
+    deep: bool,
+    json: bool,
     predownload_model: bool,
+    check_model_updates: bool,
+    update_model: bool,
+    show_stats: bool,
+    tui: bool,
+    export_format: Option<String>,
+    since: Option<String>,
+    transcribe_file: Option<PathBuf>,
+    file_format: Option<String>,
+    meeting: bool,
+    notes: Option<PathBuf>,
+    config_command: Option<ConfigCommand>,
+    emit_text: Option<String>,
+    eval_dir: Option<PathBuf>,
 }
 
 fn print_help() {
@@ -36,23 +102,65 @@ USAGE:
 
 OPTIONS:
     --help, -h                   Show this help message
-    --version, -V                Show version information
+    --version, -V                Show version information (add --json for build details)
     --list-hotkeys               List all recognized evdev key names
+    --available                  With --list-hotkeys, only keys your keyboards can send, per device
     --list-audio-devices         List available input source names for config
+    --list-keyboards             List keyboards with the name, phys, and uniq usable in config
+    --list-models                List model presets with languages, sizes, and cache status
+    --pick-device                Choose the microphone from a list with live level meters
     --write-default-config       Write default config to --config path (or default path)
-    --force                      Overwrite file when used with --write-default-config
+    --setup                      Interactively choose hotkey, microphone, model, and mode
+    --settings                   Open the settings window (builds with --features settings-gui)
+    --install-service            Install, enable, and start the systemd user service
+    --force                      Overwrite file with --write-default-config, --setup, or --install-service
     --config <path>              Override config file path
     --check                      Validate dependencies, config, and model availability
+    --deep                       With --check, also transcribe a sample and dry-run output
+    --json                       With --check, print each check's status, detail, and fix as JSON;
+                                 with --version, print build metadata and enabled features
     --predownload-model          Download model files and exit
+    --check-model-updates        Report whether the model repo has a newer revision
+    --update-model               Download the newest model revision and pin it
+    --tui                        Run with a full-screen dashboard instead of log output
+    --stats                      Show daily dictation usage from the history file
+    --export <md|srt|vtt|txt>    Print transcript history in the given format
+    --since <when>               Limit --export to today, yesterday, <N>d, or YYYY-MM-DD
+    --transcribe <file>          Transcribe an audio or video file (via ffmpeg) and print the text
+    --format <txt|srt|vtt>       With --transcribe, print timed subtitles instead of plain text
+    config get <key>             Print a config value (dotted keys for tables: logging.file)
+    config set <key> <value>     Change one config value, keeping the file's comments and layout
+    --eval <dir>                 Report word and character error rates on recordings with .txt references
+    --emit <text>                Type text through post-processing and output as if dictated
+    --meeting                    Transcribe the microphone and system audio into meeting notes
+    --notes <file>               With --meeting, the Markdown file to append notes to
 
 EXAMPLES:
     whisp
     whisp --list-hotkeys
+    whisp --list-hotkeys --available
     whisp --list-audio-devices
+    whisp --list-keyboards
+    whisp --list-models
+    whisp --pick-device
+    whisp --setup
+    whisp --install-service
     whisp --write-default-config --config ~/.config/whisp/config.toml
     whisp --config ~/.config/whisp/config.toml
     whisp --check
+    whisp --check --deep
+    whisp --check --json
     whisp --predownload-model
+    whisp --check-model-updates
+    whisp --tui
+    whisp --stats
+    whisp --export md --since today
+    whisp --transcribe recording.mkv --format srt > recording.srt
+    whisp --eval ~/whisp-samples --config whisper.toml
+    whisp --emit "Hello world. Send it."
+    whisp --meeting --notes standup.md
+    whisp config set dictation_mode code
+    whisp config get history.enabled
 
 CONFIGURATION:
     Default config: ~/.config/whisp/config.toml
@@ -74,15 +182,85 @@ fn parse_args() -> Result<CliOptions> {
             "--help" | "-h" => opts.show_help = true,
             "--version" | "-V" => opts.show_version = true,
             "--list-hotkeys" => opts.list_hotkeys = true,
+            "--available" => opts.available = true,
             "--list-audio-devices" => opts.list_audio_devices = true,
+            "--list-keyboards" => opts.list_keyboards = true,
+            "--list-models" => opts.list_models = true,
+            "--pick-device" => opts.pick_device = true,
             "--write-default-config" => opts.write_default_config = true,
+            "--setup" => opts.setup = true,
+            "--install-service" => opts.install_service = true,
+            "--settings" => opts.settings = true,
             "--force" => opts.force = true,
             "--check" => opts.check_only = true,
+            "--deep" => opts.deep = true,
+            "--json" => opts.json = true,
             "--predownload-model" => opts.predownload_model = true,
+            "--check-model-updates" => opts.check_model_updates = true,
+            "--update-model" => opts.update_model = true,
+            "--stats" => opts.show_stats = true,
+            "--tui" => opts.tui = true,
+            "--export" => {
+                let Some(format) = args.next() else {
+                    bail!("--export requires a format: md, srt, vtt, or txt");
+                };
+                opts.export_format = Some(format);
+            }
+            "--transcribe" => {
+                let Some(path) = args.next() else {
+                    bail!("--transcribe requires an audio or video file");
+                };
+                opts.transcribe_file = Some(PathBuf::from(path));
+            }
+            "--format" => {
+                let Some(format) = args.next() else {
+                    bail!("--format requires a format: txt, srt, or vtt");
+                };
+                opts.file_format = Some(format);
+            }
+            "config" => {
+                let command = match args.next().as_deref() {
+                    Some("get") => args.next().map(ConfigCommand::Get),
+                    Some("set") => match (args.next(), args.next()) {
+                        (Some(key), Some(value)) => Some(ConfigCommand::Set(key, value)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                let Some(command) = command else {
+                    bail!("Usage: whisp config get <key> | whisp config set <key> <value>");
+                };
+                opts.config_command = Some(command);
+            }
+            "--emit" => {
+                let Some(text) = args.next() else {
+                    bail!("--emit requires the text to type");
+                };
+                opts.emit_text = Some(text);
+            }
+            "--eval" => {
+                let Some(dir) = args.next() else {
+                    bail!("--eval requires a directory of audio files with .txt references");
+                };
+                opts.eval_dir = Some(PathBuf::from(dir));
+            }
+            "--meeting" => opts.meeting = true,
+            "--notes" => {
+                let Some(path) = args.next() else {
+                    bail!("--notes requires a file path");
+                };
+                opts.notes = Some(PathBuf::from(path));
+            }
+            "--since" => {
+                let Some(since) = args.next() else {
+                    bail!("--since requires a value: today, yesterday, <N>d, or YYYY-MM-DD");
+                };
+                opts.since = Some(since);
+            }
             "--config" => {
                 let Some(path) = args.next() else {
                     bail!(
-                        "The --config flag now requires a file path. Interactive setup was removed.\n\
+                        "The --config flag requires a file path. For interactive setup, run `whisp --setup`.\n\
                          Example: whisp --config ~/.config/whisp/config.toml"
                     );
                 };
@@ -104,29 +282,44 @@ fn parse_args() -> Result<CliOptions> {
         }
     }
 
-    if opts.force && !opts.write_default_config {
-        bail!("--force is only valid with --write-default-config");
+    if opts.force && !opts.write_default_config && !opts.setup && !opts.install_service {
+        bail!("--force is only valid with --write-default-config, --setup, or --install-service");
     }
 
-    Ok(opts)
-}
+    if opts.available && !opts.list_hotkeys {
+        bail!("--available is only valid with --list-hotkeys");
+    }
 
-fn check_runtime_deps(config: &config::Config) -> Result<()> {
-    let mut missing: Vec<String> = Vec::new();
+    if opts.deep && !opts.check_only {
+        bail!("--deep is only valid with --check");
+    }
 
-    if !uinput::is_available() {
-        missing.push(
-            "/dev/uinput is not accessible. Ensure user is in the 'input' group (or 'uinput' group on some distros)".to_string(),
-        );
+    if opts.json && !opts.check_only && !opts.show_version {
+        bail!("--json is only valid with --check or --version");
     }
 
-    if !config.audio_device.is_empty() && !util::has_command("pactl") {
-        missing.push(
-            "pactl (pulseaudio-utils or pipewire-pulse) is required when audio_device is set"
-                .to_string(),
-        );
+    if opts.since.is_some() && opts.export_format.is_none() {
+        bail!("--since is only valid with --export");
+    }
+
+    if opts.file_format.is_some() && opts.transcribe_file.is_none() {
+        bail!("--format is only valid with --transcribe");
+    }
+
+    if opts.notes.is_some() && !opts.meeting {
+        bail!("--notes is only valid with --meeting");
     }
 
+    Ok(opts)
+}
+
+fn check_runtime_deps(config: &config::Config) -> Result<()> {
+    let missing: Vec<String> = check::runtime_deps(config)
+        .into_iter()
+        .filter(|check| check.status == check::Status::Fail)
+        .map(|check| format!("{}. {}", check.detail, check.fix.unwrap_or_default()))
+        .collect();
+
     if !missing.is_empty() {
         anyhow::bail!(
             "Missing requirements:\n  - {}\n\nFix and try again.",
@@ -137,11 +330,383 @@ fn check_runtime_deps(config: &config::Config) -> Result<()> {
     Ok(())
 }
 
-fn run_check(config: &config::Config) -> Result<()> {
+/// Phrase run through the output pipeline by `--check --deep`.
+const DRY_RUN_PHRASE: &str = "testing whisp output one two three";
+
+fn run_check(config: &config::Config, deep: bool) -> Result<()> {
     check_runtime_deps(config)?;
     let paths = config::resolve_model_paths(config)?;
-    transcriber::validate_model(&paths)?;
-    println!("whisp check OK");
+    let (provider, reason) = config.provider.resolve();
+    println!("Provider: {} -> {provider} ({reason})", config.provider);
+    if !deep {
+        transcriber::validate_model(&paths, config.provider)?;
+        println!("whisp check OK");
+        return Ok(());
+    }
+
+    let sample = transcriber::transcribe_sample(&paths, config.provider)
+        .context("end-to-end transcription failed")?;
+    println!(
+        "Model: loaded in {}ms, transcribed {:.1}s of audio in {}ms",
+        sample.load.as_millis(),
+        sample.audio_secs,
+        sample.inference.as_millis()
+    );
+    println!("Transcription: {:?}", sample.text);
+    println!(
+        "Output (dry run): {DRY_RUN_PHRASE:?} -> {}",
+        output::dry_run(DRY_RUN_PHRASE, &output_options(config)?)
+    );
+    println!("whisp deep check OK");
+    Ok(())
+}
+
+/// `--check --json`: runs every check, including loading the config, and reports
+/// each one instead of stopping at the first failure.
+fn run_check_json(config_path: Option<&Path>, deep: bool) -> Result<()> {
+    let mut checks = Vec::new();
+    match config::load_config(config_path) {
+        Ok(loaded) => {
+            checks.push(check::Check::ok(
+                "config",
+                loaded.path.display().to_string(),
+            ));
+            diagnose(&loaded.config, deep, &mut checks);
+        }
+        Err(err) => checks.push(check::Check::fail(
+            "config",
+            format!("{err:#}"),
+            "Fix the config file, or run `whisp --setup` to write a new one",
+        )),
+    }
+    check::report_json(&checks)
+}
+
+fn diagnose(config: &config::Config, deep: bool, checks: &mut Vec<check::Check>) {
+    checks.extend(check::runtime_deps(config));
+    let paths = match config::resolve_model_paths(config) {
+        Ok(paths) => {
+            checks.push(check::Check::ok("model-files", paths.location()));
+            paths
+        }
+        Err(err) => {
+            checks.push(check::Check::fail(
+                "model-files",
+                format!("{err:#}"),
+                "Run `whisp --predownload-model` with network access, or set model_dir",
+            ));
+            return;
+        }
+    };
+    let (provider, reason) = config.provider.resolve();
+    checks.push(check::Check::ok(
+        "provider",
+        format!("{} -> {provider} ({reason})", config.provider),
+    ));
+    if !deep {
+        checks.push(match transcriber::validate_model(&paths, config.provider) {
+            Ok(()) => check::Check::ok("model-load", "model loads"),
+            Err(err) => check::Check::fail(
+                "model-load",
+                format!("{err:#}"),
+                "Delete the model cache and run `whisp --predownload-model`",
+            ),
+        });
+        return;
+    }
+    checks.push(
+        match transcriber::transcribe_sample(&paths, config.provider) {
+            Ok(sample) => check::Check::ok(
+                "transcription",
+                format!(
+                    "loaded in {}ms, transcribed {:.1}s of audio in {}ms",
+                    sample.load.as_millis(),
+                    sample.audio_secs,
+                    sample.inference.as_millis()
+                ),
+            ),
+            Err(err) => check::Check::fail(
+                "transcription",
+                format!("{err:#}"),
+                "Delete the model cache and run `whisp --predownload-model`",
+            ),
+        },
+    );
+    checks.push(match output_options(config) {
+        Ok(options) => check::Check::ok(
+            "output",
+            format!(
+                "{DRY_RUN_PHRASE:?} -> {:?}",
+                output::dry_run(DRY_RUN_PHRASE, &options)
+            ),
+        ),
+        Err(err) => check::Check::fail("output", format!("{err:#}"), "Fix the config file"),
+    });
+}
+
+/// The configured keyboard: the RemoteDesktop portal, or whisp's uinput device.
+fn open_keyboard(config: &config::Config) -> Result<output::Keyboard> {
+    if config.portal {
+        return portal_keyboard();
+    }
+    let mut vkbd = uinput::VirtualKeyboard::new()
+        .context("failed to initialize virtual keyboard (/dev/uinput)")?;
+    vkbd.set_unicode_input(config.unicode_input);
+    vkbd.set_keymap(keymap::Keymap::load(&config.keymap)?);
+    if !config.compose_key.is_empty() {
+        vkbd.set_compose_key(Some(hotkey::parse_hotkey(&config.compose_key)?));
+    }
+    if config.emoji && !config.unicode_input {
+        log::warn!("emoji = true has no effect without unicode_input = true");
+    }
+    Ok(output::Keyboard::Uinput(vkbd))
+}
+
+/// `--emit`: types `text` as if it had just been transcribed, through the same
+/// post-processing and output worker, to debug typing without speaking.
+fn emit(config: &config::Config, text: &str) -> Result<()> {
+    let options = output_options(config)?;
+    println!("Output: {}", output::dry_run(text, &options));
+    let keyboard = open_keyboard(config)?;
+    for remaining in (1..=EMIT_DELAY_SECS).rev() {
+        eprint!("\rTyping in {remaining}s; focus the target window...");
+        thread::sleep(Duration::from_secs(1));
+    }
+    eprintln!();
+    let (tx, rx) = mpsc::sync_channel(1);
+    let stats = Arc::new(Mutex::new(metrics::SessionStats::default()));
+    let worker = output::spawn_worker(
+        keyboard,
+        options,
+        rx,
+        stats.clone(),
+        None,
+        password_guard(config)?,
+        None,
+    );
+    tx.send(output::OutputCommand::Emit(transcriber::Transcript {
+        text: text.to_string(),
+        metrics: metrics::UtteranceMetrics::default(),
+        continuation: false,
+    }))?;
+    drop(tx);
+    if worker.join().is_err() {
+        bail!("Output thread panicked");
+    }
+    if stats.lock().unwrap().errors > 0 {
+        bail!("Typing failed; see the log above");
+    }
+    Ok(())
+}
+
+#[cfg(feature = "portal")]
+fn portal_keyboard() -> Result<output::Keyboard> {
+    Ok(output::Keyboard::Portal(portal::Keyboard::connect()?))
+}
+
+#[cfg(not(feature = "portal"))]
+fn portal_keyboard() -> Result<output::Keyboard> {
+    bail!("This build of whisp has no portal support. Rebuild with `cargo build --release --features portal`.");
+}
+
+#[cfg(feature = "atspi")]
+fn password_guard(config: &config::Config) -> Result<Option<output::PasswordGuard>> {
+    if !config.password_guard {
+        return Ok(None);
+    }
+    let focus = atspi::FocusWatch::spawn()?;
+    Ok(Some(Box::new(move || focus.is_password())))
+}
+
+#[cfg(not(feature = "atspi"))]
+fn password_guard(_config: &config::Config) -> Result<Option<output::PasswordGuard>> {
+    Ok(None)
+}
+
+#[cfg(feature = "obs")]
+fn obs_captions(config: &config::Config) -> Option<output::CaptionSink> {
+    if !config.obs.enabled {
+        return None;
+    }
+    let captions = obs::Captions::spawn(&config.obs);
+    Some(Box::new(move |text| captions.send(text)))
+}
+
+#[cfg(not(feature = "obs"))]
+fn obs_captions(_config: &config::Config) -> Option<output::CaptionSink> {
+    None
+}
+
+fn output_options(config: &config::Config) -> Result<output::OutputOptions> {
+    Ok(output::OutputOptions {
+        smart_join: config.smart_join,
+        dictation_mode: config.dictation_mode,
+        postprocess: postprocess::Options {
+            voice_commands: config.voice_commands,
+            emoji: config.emoji,
+            censor: config.censor,
+            dictionary: postprocess::Dictionary::new(&config.dictionary),
+            redactor: postprocess::Redactor::new(&config.redact)?,
+            strip_non_speech: config.strip_non_speech,
+            punctuate: config.punctuate,
+            phrases: postprocess::Phrases::new(&config.phrases),
+        },
+        chunking: output::Chunking {
+            size: config.type_chunk_size,
+            delay: Duration::from_millis(config.type_chunk_delay_ms),
+        },
+        length_limit: output::LengthLimit {
+            max_chars: config.max_output_chars,
+            action: config.on_long_output,
+        },
+        spacing: Duration::from_millis(config.emit_spacing_ms),
+        template: config.output_template.clone(),
+        control_chars: if config.terminal_safe {
+            postprocess::ControlChars::TERMINAL
+        } else {
+            postprocess::ControlChars {
+                newlines: config.newlines,
+                tabs: config.tabs,
+            }
+        },
+        announce: config.announce,
+        read_back: config.read_back,
+        voice: config.voice(),
+    })
+}
+
+/// Hands a finished recording to the transcriber, dropping it if the queue is full.
+fn queue_recording(
+    audio_tx: &mpsc::SyncSender<transcriber::AudioJob>,
+    stats: &Mutex<metrics::SessionStats>,
+    audio: Vec<f32>,
+) {
+    if let Err(mpsc::TrySendError::Full(_)) = audio_tx.try_send(transcriber::AudioJob::new(audio)) {
+        log::warn!("Transcription is falling behind, dropping this recording");
+        stats.lock().unwrap().record_error();
+    }
+}
+
+fn print_stats(config: &config::Config) -> Result<()> {
+    let path = config.history.file_path();
+    if !path.exists() {
+        if !config.history.enabled {
+            bail!("History is disabled. Set `enabled = true` under [history] in the config to start recording usage.");
+        }
+        println!("No history recorded yet at {}", path.display());
+        return Ok(());
+    }
+
+    let entries = history::load(&path, config.history.secret()?.as_ref())?;
+    let days = history::daily_usage(&entries);
+    let mut total = history::DailyUsage::default();
+
+    println!(
+        "{:<12} {:>10} {:>8} {:>10}",
+        "Date", "Utterances", "Words", "Audio"
+    );
+    for (date, day) in &days {
+        println!(
+            "{:<12} {:>10} {:>8} {:>9.1}m",
+            date.to_string(),
+            day.utterances,
+            day.words,
+            day.audio_secs / 60.0
+        );
+        total.utterances += day.utterances;
+        total.words += day.words;
+        total.audio_secs += day.audio_secs;
+    }
+    println!(
+        "{:<12} {:>10} {:>8} {:>9.1}m",
+        "Total",
+        total.utterances,
+        total.words,
+        total.audio_secs / 60.0
+    );
+    println!(
+        "\nRoughly {} minutes of typing replaced (at {TYPING_WPM} wpm).",
+        total.words / TYPING_WPM
+    );
+    Ok(())
+}
+
+fn export_history(config: &config::Config, format: &str, since: Option<&str>) -> Result<()> {
+    let format = export::parse_format(format)?;
+    let since = since
+        .map(|value| export::parse_since(value, chrono::Local::now().date_naive()))
+        .transpose()?;
+
+    let path = config.history.file_path();
+    if !path.exists() {
+        bail!(
+            "No history file at {}. Set `enabled = true` under [history] to start recording transcripts.",
+            path.display()
+        );
+    }
+
+    let entries: Vec<history::Entry> = history::load(&path, config.history.secret()?.as_ref())?
+        .into_iter()
+        .filter(|entry| since.is_none_or(|since| entry.timestamp.date_naive() >= since))
+        .collect();
+    print!("{}", export::render(&entries, format));
+    Ok(())
+}
+
+fn transcribe_file(config: &config::Config, path: &Path, format: Option<&str>) -> Result<()> {
+    let format = match format.map(export::parse_format).transpose()? {
+        None => export::ExportFormat::Text,
+        Some(export::ExportFormat::Markdown) => bail!("--format takes txt, srt, or vtt"),
+        Some(format) => format,
+    };
+    let audio = media::decode(path)?;
+    let segments = media::segments(&audio);
+    log::info!(
+        "Decoded {:.1}s of audio with {} speech segment(s)",
+        audio.len() as f64 / audio::SAMPLE_RATE as f64,
+        segments.len()
+    );
+    let paths = config::resolve_model_paths(config)?;
+    let clips: Vec<&[f32]> = segments
+        .iter()
+        .map(|&(start, end)| &audio[start..end])
+        .collect();
+    let texts = transcriber::transcribe_clips(&paths, config.provider, &clips, |done| {
+        eprint!("\rTranscribed {done}/{} segments", clips.len());
+    })?;
+    eprintln!();
+
+    let options = postprocess::Options {
+        voice_commands: false,
+        ..output_options(config)?.postprocess
+    };
+    let cues: Vec<export::Cue> = segments
+        .iter()
+        .zip(texts)
+        .map(|(&(start, end), text)| export::Cue {
+            start_ms: media::millis(start),
+            end_ms: media::millis(end),
+            text: postprocess::process(&text, postprocess::DictationMode::Text, &options),
+        })
+        .filter(|cue| !cue.text.is_empty())
+        .collect();
+    print!("{}", export::render_cues(&cues, format));
+    Ok(())
+}
+
+fn run_config_command(config_path: Option<&Path>, command: &ConfigCommand) -> Result<()> {
+    let loaded = config::load_config(config_path)?;
+    match command {
+        ConfigCommand::Get(key) => match config::get_value(&loaded.config, key)? {
+            toml::Value::String(value) => println!("{value}"),
+            toml::Value::Table(table) => print!("{}", toml::to_string(&table)?),
+            value => println!("{value}"),
+        },
+        ConfigCommand::Set(key, value) => {
+            config::update_config(&loaded.path, &[(key, config::parse_cli_value(value))])?;
+            println!("Set {key} in {}", loaded.path.display());
+        }
+    }
     Ok(())
 }
 
@@ -151,38 +716,163 @@ fn print_audio_devices() -> Result<()> {
     for source in devices {
         println!("  {}  ({})", source.name, source.description);
     }
+    let monitors = audio::list_monitor_sources()?;
+    println!(
+        "\nSystem audio, to transcribe what is playing (`audio_device = \"{}\"` follows the default output):",
+        audio::DEFAULT_MONITOR
+    );
+    for source in monitors {
+        println!("  {}  ({})", source.name, source.description);
+    }
     Ok(())
 }
 
-fn main() -> Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+/// `--list-hotkeys --available`: the keys each physical device can send.
+fn print_available_hotkeys() {
+    let devices = hotkey::list_available_hotkeys();
+    if devices.is_empty() {
+        println!("No readable keyboards found. Is your user in the 'input' group?");
+        return;
+    }
+    for (keyboard, keys) in devices {
+        println!("{}  ({})", keyboard.path.display(), keyboard.name);
+        let mut line = String::new();
+        for key in keys {
+            if !line.is_empty() && line.len() + key.len() > 76 {
+                println!("   {line}");
+                line.clear();
+            }
+            line.push(' ');
+            line.push_str(&key);
+        }
+        println!("   {line}");
+    }
+}
+
+fn print_keyboards() {
+    let keyboards = hotkey::list_keyboards();
+    if keyboards.is_empty() {
+        println!("No readable keyboards found. Is your user in the 'input' group?");
+        return;
+    }
+    println!("Keyboards (use any of name, phys, or uniq in `keyboards = [...]`):");
+    for keyboard in keyboards {
+        println!("  {}", keyboard.path.display());
+        println!("    name: {}", keyboard.name);
+        if !keyboard.phys.is_empty() {
+            println!("    phys: {}", keyboard.phys);
+        }
+        if !keyboard.uniq.is_empty() {
+            println!("    uniq: {}", keyboard.uniq);
+        }
+    }
+}
 
+fn print_models() {
+    println!("Model presets (use `model = \"<name>\"` and `precision = \"<precision>\"`):");
+    let mut current = "";
+    for model in config::list_models() {
+        if model.name != current {
+            current = model.name;
+            println!("\n  {}", model.name);
+            println!("    {}", model.description);
+            println!("    Languages: {}", model.languages);
+        }
+        let status = match model.cached_bytes {
+            Some(bytes) => format!("cached, {} MB on disk", bytes / 1_000_000),
+            None => "not downloaded".to_string(),
+        };
+        println!(
+            "    {:<5} ~{} MB download, {status}",
+            model.precision.to_string(),
+            model.download_mb
+        );
+    }
+    println!("\nCache: {}", config::model_cache_hint().display());
+}
+
+fn main() -> Result<()> {
     let cli = parse_args()?;
     if cli.show_help {
         print_help();
         return Ok(());
     }
+    if cli.show_version && cli.json {
+        println!("{}", serde_json::to_string_pretty(&build_info::current())?);
+        return Ok(());
+    }
     if cli.show_version {
         println!("whisp {VERSION}");
         return Ok(());
     }
+    if cli.list_hotkeys && cli.available {
+        print_available_hotkeys();
+        return Ok(());
+    }
     if cli.list_hotkeys {
         for key in hotkey::list_supported_hotkeys() {
             println!("{key}");
         }
         return Ok(());
     }
+    if cli.list_keyboards {
+        print_keyboards();
+        return Ok(());
+    }
     if cli.list_audio_devices {
         print_audio_devices()?;
         return Ok(());
     }
+    if cli.list_models {
+        print_models();
+        return Ok(());
+    }
     if cli.write_default_config {
         let path = config::write_default_config(cli.config_path.as_deref(), cli.force)?;
         println!("Wrote default config to {}", path.display());
         return Ok(());
     }
+    if cli.settings {
+        #[cfg(feature = "settings-gui")]
+        return settings::run(cli.config_path.as_deref());
+        #[cfg(not(feature = "settings-gui"))]
+        bail!("This build of whisp has no settings window. Rebuild with `cargo build --release --features settings-gui`.");
+    }
+    if let Some(command) = &cli.config_command {
+        return run_config_command(cli.config_path.as_deref(), command);
+    }
+    if cli.pick_device {
+        match picker::run(cli.config_path.as_deref())? {
+            Some((path, label)) => println!("Saved audio_device ({label}) to {}", path.display()),
+            None => println!("No change"),
+        }
+        return Ok(());
+    }
+    if cli.setup {
+        let path = setup::run(cli.config_path.as_deref(), cli.force)?;
+        println!(
+            "\nWrote config to {}. Run `whisp` to start.",
+            path.display()
+        );
+        return Ok(());
+    }
+    if cli.check_only && cli.json {
+        return run_check_json(cli.config_path.as_deref(), cli.deep);
+    }
+    if cli.install_service {
+        let path = service::install(cli.config_path.as_deref(), cli.force)?;
+        println!(
+            "whisp is running as a user service ({}). Follow it with `journalctl --user -u whisp.service -f`.",
+            path.display()
+        );
+        return Ok(());
+    }
 
     let loaded = config::load_config(cli.config_path.as_deref())?;
+    logging::set_privacy(loaded.config.privacy);
+    if let Some(path) = logging::init(&loaded.config.logging, !cli.tui)? {
+        log::info!("Logging to {}", path.display());
+    }
     if loaded.created {
         log::info!(
             "Created default config at {}",
@@ -194,16 +884,86 @@ fn main() -> Result<()> {
 
     if cli.predownload_model {
         let _ = config::resolve_model_paths(&loaded.config)?;
-        println!(
-            "Model '{}' is available in cache: {}",
-            loaded.config.model,
-            config::model_cache_hint().display()
-        );
+        if loaded.config.model_dir.is_empty() {
+            println!(
+                "Model '{}' is available in cache: {}",
+                loaded.config.model,
+                config::model_cache_hint().display()
+            );
+        } else {
+            println!(
+                "Model '{}' is available in {}",
+                loaded.config.model, loaded.config.model_dir
+            );
+        }
+        if config::resolve_second_pass_paths(&loaded.config)?.is_some() {
+            println!(
+                "Second-pass model '{}' is available in cache",
+                loaded.config.second_pass_model
+            );
+        }
+        return Ok(());
+    }
+
+    if cli.check_model_updates {
+        let status = config::check_model_update(&loaded.config)?;
+        match status.pinned {
+            Some(pinned) if pinned == status.latest => {
+                println!("{} is up to date ({pinned})", status.repo)
+            }
+            Some(pinned) => println!(
+                "{} has a newer revision: {pinned} -> {}\nRun `whisp --update-model` to download it.",
+                status.repo, status.latest
+            ),
+            None => println!(
+                "{} is not downloaded yet; latest revision is {}",
+                status.repo, status.latest
+            ),
+        }
+        return Ok(());
+    }
+
+    if cli.update_model {
+        let _ = config::update_model(&loaded.config)?;
+        println!("Model '{}' is at the latest revision", loaded.config.model);
         return Ok(());
     }
 
     if cli.check_only {
-        run_check(&loaded.config)?;
+        run_check(&loaded.config, cli.deep)?;
+        return Ok(());
+    }
+
+    if cli.show_stats {
+        print_stats(&loaded.config)?;
+        return Ok(());
+    }
+
+    if let Some(format) = cli.export_format.as_deref() {
+        export_history(&loaded.config, format, cli.since.as_deref())?;
+        return Ok(());
+    }
+
+    if let Some(path) = cli.transcribe_file.as_deref() {
+        transcribe_file(&loaded.config, path, cli.file_format.as_deref())?;
+        return Ok(());
+    }
+
+    if let Some(dir) = cli.eval_dir.as_deref() {
+        return eval::run(&loaded.config, dir);
+    }
+
+    if let Some(text) = cli.emit_text.as_deref() {
+        return emit(&loaded.config, text);
+    }
+
+    if cli.meeting {
+        let options = postprocess::Options {
+            voice_commands: false,
+            ..output_options(&loaded.config)?.postprocess
+        };
+        let notes = cli.notes.unwrap_or_else(meeting::default_notes_path);
+        meeting::run(&loaded.config, &options, &notes)?;
         return Ok(());
     }
 
@@ -218,49 +978,163 @@ fn main() -> Result<()> {
     let paths = config::resolve_model_paths(&loaded.config)?;
     log::info!("Model resolved");
 
-    let audio_capture = audio::AudioCapture::new(&loaded.config.audio_device)?;
-    let mut vkbd = uinput::VirtualKeyboard::new()
-        .context("failed to initialize virtual keyboard (/dev/uinput)")?;
+    // Kept alive until exit: dropping it unloads the echo-cancel module.
+    let echo_cancel = if loaded.config.echo_cancel {
+        Some(audio::EchoCancel::setup(&loaded.config.audio_device)?)
+    } else {
+        None
+    };
+    let audio_capture = if let Some(echo_cancel) = &echo_cancel {
+        audio::AudioCapture::new(&echo_cancel.source)?
+    } else if loaded.config.audio_devices.is_empty() {
+        audio::AudioCapture::new(&loaded.config.audio_device)?
+    } else {
+        audio::AudioCapture::new_mixed(&loaded.config.audio_devices, loaded.config.mic_mix)?
+    };
+    let keyboard = open_keyboard(&loaded.config)?;
 
     let shutdown = Arc::new(AtomicBool::new(false));
     let shutdown_handler = shutdown.clone();
     ctrlc::set_handler(move || {
+        if shutdown_handler.swap(true, Ordering::SeqCst) {
+            // Second Ctrl+C: stop waiting for queued work.
+            std::process::exit(130);
+        }
         log::info!("Shutting down...");
-        shutdown_handler.store(true, Ordering::SeqCst);
     })?;
 
-    let (hotkey_tx, hotkey_rx) = mpsc::channel();
-    let (audio_tx, audio_rx) = mpsc::channel::<Vec<f32>>();
-    let (text_tx, text_rx) = mpsc::channel::<String>();
+    // Bounded so a slow transcriber can't grow memory without limit. Hotkey listeners
+    // and the transcriber block when full; the main loop never does (see below).
+    let (hotkey_tx, hotkey_rx) = mpsc::sync_channel(HOTKEY_CAPACITY);
+    let (audio_tx, audio_rx) = mpsc::sync_channel::<transcriber::AudioJob>(transcriber::MAX_QUEUE);
+    let (output_tx, output_rx) =
+        mpsc::sync_channel::<output::OutputCommand>(output::COMMAND_CAPACITY);
+
+    if loaded.config.portal {
+        #[cfg(feature = "portal")]
+        portal::spawn_shortcuts(
+            &loaded.config.hotkey_bindings(),
+            &loaded.config.hotkey_modifiers,
+            hotkey_tx,
+        )?;
+    } else {
+        hotkey::spawn_listener(
+            &loaded.config.hotkey_bindings(),
+            &loaded.config.listener_options(),
+            hotkey_tx,
+        )?;
+    }
+    let second_pass = config::resolve_second_pass_paths(&loaded.config)?
+        .map(|paths| transcriber::SecondPass::spawn(&paths, loaded.config.provider))
+        .transpose()?;
+    let stats = Arc::new(Mutex::new(metrics::SessionStats::default()));
+    let transcriber_thread = transcriber::spawn_worker(
+        paths,
+        audio_rx,
+        output_tx.clone(),
+        stats.clone(),
+        transcriber::Options {
+            provider: loaded.config.provider,
+            warmup: loaded.config.warmup,
+            queue_policy: loaded.config.queue_policy,
+            timeout: match loaded.config.transcribe_timeout_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            progressive: loaded.config.progressive_output,
+        },
+        second_pass,
+    )?;
 
-    hotkey::spawn_listener(&loaded.config.hotkey, hotkey_tx)?;
-    transcriber::spawn_worker(paths, audio_rx, text_tx)?;
+    let history = if loaded.config.history.enabled && loaded.config.privacy {
+        log::info!("History is off in privacy mode");
+        None
+    } else if loaded.config.history.enabled {
+        let path = loaded.config.history.file_path();
+        log::info!("Recording history to {}", path.display());
+        Some(history::History::open(
+            &path,
+            loaded.config.history.secret()?.as_ref(),
+        )?)
+    } else {
+        None
+    };
+    let output_thread = output::spawn_worker(
+        keyboard,
+        output_options(&loaded.config)?,
+        output_rx,
+        stats.clone(),
+        history,
+        password_guard(&loaded.config)?,
+        obs_captions(&loaded.config),
+    );
 
-    std::thread::spawn(move || {
-        for text in text_rx {
-            log::info!("Transcribed: {text}");
-            if let Err(err) = output::emit_text(&text, &mut vkbd) {
-                log::error!("Failed to emit output text: {err}");
-            }
+    let paused = Arc::new(AtomicBool::new(false));
+    let dashboard = if cli.tui {
+        Some(tui::spawn(tui::Dashboard {
+            hotkey: loaded.config.hotkey.clone(),
+            model: loaded.config.model.clone(),
+            stats: stats.clone(),
+            audio: audio_capture.buffer.clone(),
+            paused: paused.clone(),
+            shutdown: shutdown.clone(),
+            output_tx: output_tx.clone(),
+        }))
+    } else {
+        let verb = match loaded.config.recording_mode {
+            hotkey::RecordingMode::Hold => "Hold",
+            hotkey::RecordingMode::TapToggle => "Hold or tap",
+            hotkey::RecordingMode::Toggle => "Press",
+        };
+        println!(
+            "whisp ready. {verb} {} to record. Press Ctrl+C to exit.",
+            loaded.config.hotkey
+        );
+        None
+    };
+
+    let session_locked = if loaded.config.ignore_when_locked {
+        let locked = util::watch_session_lock();
+        match locked {
+            Some(_) => log::info!(
+                "ignore_when_locked: hotkeys are ignored while logind reports the session locked"
+            ),
+            None => log::warn!(
+                "Can't read the session lock state from logind; hotkeys will work while the screen is locked"
+            ),
         }
-    });
+        locked
+    } else {
+        None
+    };
 
-    println!(
-        "whisp ready. Hold {} to record. Press Ctrl+C to exit.",
-        loaded.config.hotkey
+    let mut session = session::Session::new(
+        session::Options {
+            debounce: Duration::from_millis(loaded.config.debounce_ms),
+            mode: loaded.config.recording_mode,
+            hold_threshold: Duration::from_millis(loaded.config.hold_threshold_ms),
+            merge_window: Duration::from_millis(loaded.config.merge_within_ms),
+        },
+        Instant::now(),
     );
 
-    let debounce = Duration::from_millis(loaded.config.debounce_ms);
-    let mut recording = false;
-    let mut record_start = Instant::now();
-    let mut last_stop = Instant::now() - debounce;
-
+    let mut worker_stopped = false;
     loop {
         if shutdown.load(Ordering::SeqCst) {
             break;
         }
+        if transcriber_thread.is_finished() {
+            log::error!("Transcription worker stopped; no further recordings can be transcribed");
+            worker_stopped = true;
+            break;
+        }
+
+        if let Some(audio) = session.take_expired(Instant::now()) {
+            queue_recording(&audio_tx, &stats, audio);
+        }
 
-        let event = match hotkey_rx.recv_timeout(Duration::from_millis(100)) {
+        let timeout = session.timeout(Instant::now(), Duration::from_millis(100));
+        let event = match hotkey_rx.recv_timeout(timeout) {
             Ok(event) => event,
             Err(mpsc::RecvTimeoutError::Timeout) => continue,
             Err(mpsc::RecvTimeoutError::Disconnected) => {
@@ -269,38 +1143,116 @@ fn main() -> Result<()> {
             }
         };
 
-        match event {
-            hotkey::HotkeyEvent::Pressed => {
-                if recording {
-                    continue;
-                }
-                if last_stop.elapsed() < debounce {
-                    continue;
+        if let hotkey::HotkeyEvent::Pressed(action) = event {
+            if session_locked
+                .as_ref()
+                .is_some_and(|locked| locked.load(Ordering::Relaxed))
+            {
+                log::info!("Session is locked, ignoring {action:?} hotkey");
+                continue;
+            }
+        }
+
+        let transition = match event {
+            hotkey::HotkeyEvent::Pressed(hotkey::Action::Undo) => {
+                output::send_control(&output_tx, output::OutputCommand::UndoLast);
+                continue;
+            }
+            hotkey::HotkeyEvent::Pressed(hotkey::Action::ToggleNumeric) => {
+                output::send_control(&output_tx, output::OutputCommand::ToggleNumeric);
+                continue;
+            }
+            hotkey::HotkeyEvent::Pressed(hotkey::Action::Retry) => {
+                output::send_control(&output_tx, output::OutputCommand::RetryFailed);
+                continue;
+            }
+            hotkey::HotkeyEvent::Released(
+                hotkey::Action::Undo | hotkey::Action::ToggleNumeric | hotkey::Action::Retry,
+            ) => continue,
+            hotkey::HotkeyEvent::Pressed(hotkey::Action::Record) => {
+                let paused = paused.load(Ordering::SeqCst);
+                let transition = session.press(Instant::now(), paused);
+                if paused && transition == session::Transition::Ignore && !session.is_recording() {
+                    log::info!("Paused, ignoring record hotkey");
                 }
-                audio_capture.start_recording();
-                record_start = Instant::now();
-                recording = true;
-                log::info!("Recording...");
+                transition
+            }
+            hotkey::HotkeyEvent::Released(hotkey::Action::Record) => {
+                session.release(Instant::now())
             }
-            hotkey::HotkeyEvent::Released => {
-                if !recording {
-                    continue;
+        };
+
+        match transition {
+            session::Transition::Ignore => continue,
+            session::Transition::Latch => {
+                log::info!("Tapped: recording until the next tap");
+                continue;
+            }
+            session::Transition::Start { continuing } => {
+                audio_capture.start_recording();
+                if continuing {
+                    log::info!("Recording (continuing the previous recording)...");
+                } else {
+                    log::info!("Recording...");
                 }
-                recording = false;
-                let audio = audio_capture.stop_recording();
-                last_stop = Instant::now();
-                let duration = record_start.elapsed();
-                if audio.is_empty() {
-                    log::info!("No audio captured");
-                    continue;
+                if loaded.config.announce {
+                    speech::say("Recording", speech::Priority::Important);
                 }
-                log::info!("Captured {:.2}s of audio", duration.as_secs_f64());
-                let _ = audio_tx.send(audio);
+                continue;
             }
+            session::Transition::Stop => {}
+        }
+
+        let audio = audio_capture.stop_recording();
+        let now = Instant::now();
+        if loaded.config.announce {
+            speech::say("Stopped", speech::Priority::Important);
+        }
+        log::info!(
+            "Captured {:.2}s of audio",
+            session.duration(now).as_secs_f64()
+        );
+        match session.stop(audio, now) {
+            session::Stopped::Queue(audio) => queue_recording(&audio_tx, &stats, audio),
+            session::Stopped::Held => {}
+            session::Stopped::Empty => log::info!("No audio captured"),
         }
     }
 
+    // No new recordings from here on; let queued ones finish, bounded by SHUTDOWN_TIMEOUT.
+    shutdown.store(true, Ordering::SeqCst);
+    if session.is_recording() {
+        audio_capture.stop_recording();
+        log::info!("Discarding the recording in progress");
+    }
+    if let Some(audio) = session.take_held() {
+        queue_recording(&audio_tx, &stats, audio);
+    }
     drop(audio_tx);
+    if let Some(dashboard) = dashboard {
+        match dashboard.join() {
+            Ok(result) => result?,
+            Err(_) => log::error!("Dashboard thread panicked"),
+        }
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    let pending = stats.lock().unwrap().queue_depth;
+    if pending > 0 {
+        log::info!("Finishing {pending} queued recording(s) (Ctrl+C again to quit now)");
+    }
+    // The output thread stops once the transcriber and this loop drop their senders.
+    drop(output_tx);
+    if !util::join_until(transcriber_thread, deadline) || !util::join_until(output_thread, deadline)
+    {
+        log::warn!(
+            "Gave up waiting for pending transcriptions after {}s",
+            SHUTDOWN_TIMEOUT.as_secs()
+        );
+    }
+    println!("{}", stats.lock().unwrap());
+    if worker_stopped {
+        bail!("The transcription worker stopped unexpectedly; see the log above");
+    }
     log::info!("Goodbye!");
 
     Ok(())
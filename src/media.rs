@@ -0,0 +1,262 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::audio::SAMPLE_RATE;
+
+/// Frame length used to find pauses.
+const FRAME_MS: usize = 30;
+/// A pause at least this long ends a segment.
+const MIN_PAUSE_MS: usize = 500;
+/// Segments are cut at this length even without a pause, which also keeps them
+/// inside Whisper's 30s window.
+const MAX_SEGMENT_MS: usize = 20_000;
+/// Shorter bursts are clicks and breaths rather than speech.
+const MIN_SEGMENT_MS: usize = 200;
+/// Audio kept before and after each segment so word edges aren't clipped.
+const PADDING_MS: usize = 150;
+
+/// Decodes any audio or video file ffmpeg can read to 16kHz mono samples.
+pub fn decode(path: &Path) -> Result<Vec<f32>> {
+    if !path.is_file() {
+        bail!("{} is not a file", path.display());
+    }
+    let output = Command::new("ffmpeg")
+        .args(["-nostdin", "-v", "error", "-i"])
+        .arg(path)
+        .args([
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            &SAMPLE_RATE.to_string(),
+            "-f",
+            "f32le",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .context("running ffmpeg (install ffmpeg to transcribe files)")?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg could not decode {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect())
+}
+
+fn samples(ms: usize) -> usize {
+    ms * SAMPLE_RATE as usize / 1000
+}
+
+/// Splits audio into speech segments at pauses, as sample ranges.
+///
+/// Frames louder than a threshold derived from the recording's own noise floor
+/// count as speech, so quiet and loud recordings both segment sensibly.
+pub fn segments(audio: &[f32]) -> Vec<(usize, usize)> {
+    let frame = samples(FRAME_MS);
+    let levels: Vec<f32> = audio
+        .chunks(frame)
+        .map(|chunk| (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt())
+        .collect();
+    if levels.is_empty() {
+        return Vec::new();
+    }
+    let mut sorted = levels.clone();
+    sorted.sort_by(f32::total_cmp);
+    let floor = sorted[sorted.len() / 5];
+    let threshold = (floor * 3.0).max(0.005);
+
+    let pause_frames = MIN_PAUSE_MS / FRAME_MS;
+    let max_frames = MAX_SEGMENT_MS / FRAME_MS;
+    let mut ranges = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    for (index, level) in levels.iter().enumerate() {
+        let speech = *level >= threshold;
+        current = match current {
+            None if speech => Some((index, index)),
+            None => None,
+            Some((start, last)) if index - start >= max_frames => {
+                ranges.push((start, last));
+                speech.then_some((index, index))
+            }
+            Some((start, _)) if speech => Some((start, index)),
+            Some((start, last)) if index - last >= pause_frames => {
+                ranges.push((start, last));
+                None
+            }
+            ongoing => ongoing,
+        };
+    }
+    ranges.extend(current);
+
+    let padding = samples(PADDING_MS);
+    ranges
+        .into_iter()
+        .filter(|(start, last)| (last - start + 1) * FRAME_MS >= MIN_SEGMENT_MS)
+        .map(|(start, last)| {
+            (
+                (start * frame).saturating_sub(padding),
+                ((last + 1) * frame + padding).min(audio.len()),
+            )
+        })
+        .collect()
+}
+
+/// Cuts live audio into speech segments at pauses, for `whisp --meeting`.
+///
+/// Like [`segments`], but the speech threshold follows a running noise floor
+/// since the whole recording isn't available up front.
+pub struct Segmenter {
+    /// Samples not yet making up a whole frame.
+    partial: Vec<f32>,
+    /// The current segment, or the padding kept ahead of the next one.
+    audio: Vec<f32>,
+    /// Stream position of `audio[0]`, in samples.
+    offset: usize,
+    speech_frames: usize,
+    silent_frames: usize,
+    floor: Option<f32>,
+}
+
+impl Default for Segmenter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Segmenter {
+    pub fn new() -> Self {
+        Self {
+            partial: Vec::new(),
+            audio: Vec::new(),
+            offset: 0,
+            speech_frames: 0,
+            silent_frames: 0,
+            floor: None,
+        }
+    }
+
+    /// Adds samples and returns any segments they complete, with their start position.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<(usize, Vec<f32>)> {
+        let frame = self::samples(FRAME_MS);
+        self.partial.extend_from_slice(samples);
+        let mut done = Vec::new();
+        let mut start = 0;
+        while self.partial.len() - start >= frame {
+            let chunk = self.partial[start..start + frame].to_vec();
+            start += frame;
+            done.extend(self.frame(&chunk));
+        }
+        self.partial.drain(..start);
+        done
+    }
+
+    /// Ends the stream, returning the segment in progress if it holds speech.
+    pub fn finish(&mut self) -> Option<(usize, Vec<f32>)> {
+        let segment = self.cut();
+        self.partial.clear();
+        segment
+    }
+
+    fn frame(&mut self, chunk: &[f32]) -> Option<(usize, Vec<f32>)> {
+        let level = (chunk.iter().map(|s| s * s).sum::<f32>() / chunk.len() as f32).sqrt();
+        let floor = match self.floor {
+            Some(floor) if level < floor => level,
+            // Rises slowly, so steady background noise is learned but speech isn't.
+            Some(floor) => floor + (level - floor) * 0.002,
+            None => level,
+        };
+        self.floor = Some(floor);
+        let speech = level >= (floor * 3.0).max(0.005);
+
+        self.audio.extend_from_slice(chunk);
+        if self.speech_frames == 0 {
+            if speech {
+                self.speech_frames = 1;
+                self.silent_frames = 0;
+            } else {
+                // Only the padding ahead of the next segment is kept.
+                let excess = self.audio.len().saturating_sub(self::samples(PADDING_MS));
+                self.audio.drain(..excess);
+                self.offset += excess;
+            }
+            return None;
+        }
+        if speech {
+            self.speech_frames += 1;
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames += 1;
+        }
+        let frames = self.audio.len() / self::samples(FRAME_MS);
+        if self.silent_frames >= MIN_PAUSE_MS / FRAME_MS || frames >= MAX_SEGMENT_MS / FRAME_MS {
+            return self.cut();
+        }
+        None
+    }
+
+    /// Closes the current segment, trimming trailing silence down to the padding.
+    fn cut(&mut self) -> Option<(usize, Vec<f32>)> {
+        let speech_frames = std::mem::take(&mut self.speech_frames);
+        let silent = self.silent_frames * self::samples(FRAME_MS);
+        self.silent_frames = 0;
+        let end = self.audio.len() - silent.saturating_sub(self::samples(PADDING_MS));
+        let audio: Vec<f32> = self.audio.drain(..end).collect();
+        let start = self.offset;
+        self.offset += end;
+        (speech_frames * FRAME_MS >= MIN_SEGMENT_MS).then_some((start, audio))
+    }
+}
+
+/// Sample offset as milliseconds.
+pub fn millis(sample: usize) -> i64 {
+    (sample as u64 * 1000 / SAMPLE_RATE as u64) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{millis, samples, segments, Segmenter};
+
+    #[test]
+    fn splits_speech_at_pauses() {
+        let tone = |ms| vec![0.3f32; samples(ms)];
+        let quiet = |ms| vec![0.0f32; samples(ms)];
+        let audio = [
+            quiet(1000),
+            tone(1200),
+            quiet(300),
+            tone(600),
+            quiet(1500),
+            tone(900),
+            quiet(1000),
+            tone(60),
+            quiet(1000),
+        ]
+        .concat();
+        let ranges: Vec<(i64, i64)> = segments(&audio)
+            .into_iter()
+            .map(|(start, end)| (millis(start), millis(end)))
+            .collect();
+        // The 300ms gap is too short to split on and the 60ms click is dropped.
+        assert_eq!(ranges, vec![(840, 3270), (4440, 5670)]);
+
+        let mut segmenter = Segmenter::new();
+        let mut live = Vec::new();
+        for chunk in audio.chunks(samples(100)) {
+            live.extend(segmenter.push(chunk));
+        }
+        live.extend(segmenter.finish());
+        let live: Vec<(i64, i64)> = live
+            .into_iter()
+            .map(|(start, clip)| (millis(start), millis(start + clip.len())))
+            .collect();
+        assert_eq!(live, ranges);
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::{bail, Context, Result};
+use chrono::Local;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use crate::audio::{self, SourceStream};
+use crate::config::{self, Config};
+use crate::media::Segmenter;
+use crate::postprocess::{self, DictationMode};
+use crate::transcriber::ClipTranscriber;
+use crate::util;
+
+/// A finished speech segment and who it came from.
+struct Segment {
+    speaker: &'static str,
+    audio: Vec<f32>,
+}
+
+/// Live captures of one source, cut into segments as it goes.
+struct Channel {
+    speaker: &'static str,
+    segmenter: Arc<Mutex<Segmenter>>,
+    stream: SourceStream,
+}
+
+impl Channel {
+    fn open(device: &str, speaker: &'static str, tx: mpsc::Sender<Segment>) -> Result<Self> {
+        let segmenter = Arc::new(Mutex::new(Segmenter::new()));
+        let pushed = segmenter.clone();
+        let stream = SourceStream::spawn(device, move |samples| {
+            let done = pushed.lock().unwrap().push(samples);
+            for (_, audio) in done {
+                let _ = tx.send(Segment { speaker, audio });
+            }
+        })
+        .with_context(|| format!("capturing {speaker} from {device:?}"))?;
+        Ok(Self {
+            speaker,
+            segmenter,
+            stream,
+        })
+    }
+
+    /// Stops capture and returns the segment in progress, if any.
+    fn close(self) -> Option<Segment> {
+        let speaker = self.speaker;
+        drop(self.stream);
+        let (_, audio) = self.segmenter.lock().unwrap().finish()?;
+        Some(Segment { speaker, audio })
+    }
+}
+
+/// Where notes go when `--notes` isn't given: a new file per meeting next to the history.
+pub fn default_notes_path() -> PathBuf {
+    config::default_history_path()
+        .with_file_name("meetings")
+        .join(format!("{}.md", Local::now().format("%Y-%m-%d-%H%M%S")))
+}
+
+/// Transcribes the microphone and system audio until Ctrl+C, appending each line
+/// to a Markdown notes file tagged with who spoke: "You" for the microphone,
+/// "Others" for everything playing through the default output.
+pub fn run(config: &Config, options: &postprocess::Options, notes: &Path) -> Result<()> {
+    if !util::has_command("parec") {
+        bail!("--meeting requires parec (install pulseaudio-utils or pipewire-pulse)");
+    }
+    let paths = config::resolve_model_paths(config)?;
+    let mut transcriber = ClipTranscriber::new(&paths, config.provider)?;
+
+    if let Some(dir) = notes.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(notes)
+        .with_context(|| format!("opening {}", notes.display()))?;
+    writeln!(
+        file,
+        "# Meeting notes, {}\n",
+        Local::now().format("%Y-%m-%d %H:%M")
+    )?;
+
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_handler = shutdown.clone();
+    ctrlc::set_handler(move || {
+        if shutdown_handler.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    })?;
+
+    let (tx, rx) = mpsc::channel();
+    let channels = [
+        Channel::open(&config.audio_device, "You", tx.clone())?,
+        Channel::open(audio::DEFAULT_MONITOR, "Others", tx)?,
+    ];
+    eprintln!(
+        "Taking meeting notes in {} (Ctrl+C to stop)",
+        notes.display()
+    );
+
+    let mut write = |segment: Segment| -> Result<()> {
+        let text = transcriber.transcribe(&segment.audio)?;
+        let text = postprocess::process(&text, DictationMode::Text, options);
+        if text.is_empty() {
+            return Ok(());
+        }
+        let line = format!(
+            "- **{} {}:** {text}",
+            Local::now().format("%H:%M:%S"),
+            segment.speaker
+        );
+        println!("{line}");
+        writeln!(file, "{line}")?;
+        Ok(())
+    };
+
+    while !shutdown.load(Ordering::SeqCst) {
+        match rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(segment) => write(segment)?,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => bail!("audio capture stopped"),
+        }
+    }
+
+    eprintln!("Finishing the last segments...");
+    let last: Vec<Segment> = channels.into_iter().filter_map(Channel::close).collect();
+    for segment in rx.try_iter().chain(last) {
+        write(segment)?;
+    }
+    eprintln!("Notes saved to {}", notes.display());
+    Ok(())
+}
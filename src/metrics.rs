@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Duration;
+
+use crate::logging;
+
+/// Timing breakdown for a single utterance, from hotkey release to typed output.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UtteranceMetrics {
+    /// Length of the captured audio.
+    pub audio_secs: f64,
+    /// Time spent waiting in the transcription queue.
+    pub queue_wait_ms: u64,
+    /// Time spent in model inference.
+    pub transcribe_ms: u64,
+    /// Inference time divided by audio duration (below 1.0 is faster than real time).
+    pub real_time_factor: f64,
+    /// Time spent injecting the text through the virtual keyboard.
+    pub output_ms: u64,
+}
+
+impl UtteranceMetrics {
+    pub fn new(audio_secs: f64, queue_wait: Duration, transcribe: Duration) -> Self {
+        let real_time_factor = if audio_secs > 0.0 {
+            transcribe.as_secs_f64() / audio_secs
+        } else {
+            0.0
+        };
+        Self {
+            audio_secs,
+            queue_wait_ms: queue_wait.as_millis() as u64,
+            transcribe_ms: transcribe.as_millis() as u64,
+            real_time_factor,
+            output_ms: 0,
+        }
+    }
+
+    /// Time from hotkey release until the text finished typing.
+    pub fn end_to_end_ms(&self) -> u64 {
+        self.queue_wait_ms + self.transcribe_ms + self.output_ms
+    }
+}
+
+/// Number of recent transcriptions kept for the dashboard.
+const RECENT_LIMIT: usize = 20;
+
+/// Running totals for the current session, printed on shutdown, plus the live
+/// pipeline state shown by `--tui`.
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    pub utterances: u64,
+    pub audio_secs: f64,
+    pub latency_ms_total: u64,
+    pub words: u64,
+    pub errors: u64,
+    /// Most recent transcriptions with their end-to-end latency, newest last.
+    pub recent: VecDeque<(String, u64)>,
+    /// Recordings waiting for the transcriber, including the one in progress.
+    pub queue_depth: usize,
+}
+
+impl SessionStats {
+    pub fn record_utterance(&mut self, text: &str, metrics: &UtteranceMetrics) {
+        self.utterances += 1;
+        self.audio_secs += metrics.audio_secs;
+        self.latency_ms_total += metrics.end_to_end_ms();
+        self.words += text.split_whitespace().count() as u64;
+        if self.recent.len() == RECENT_LIMIT {
+            self.recent.pop_front();
+        }
+        self.recent
+            .push_back((logging::redact(text).to_string(), metrics.end_to_end_ms()));
+    }
+
+    pub fn record_error(&mut self) {
+        self.errors += 1;
+    }
+
+    pub fn average_latency_ms(&self) -> u64 {
+        self.latency_ms_total
+            .checked_div(self.utterances)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for SessionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Session summary:")?;
+        writeln!(f, "  Utterances:      {}", self.utterances)?;
+        writeln!(f, "  Audio:           {:.1}s", self.audio_secs)?;
+        writeln!(f, "  Average latency: {}ms", self.average_latency_ms())?;
+        writeln!(f, "  Words emitted:   {}", self.words)?;
+        write!(f, "  Errors:          {}", self.errors)
+    }
+}
+
+impl fmt::Display for UtteranceMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "audio={:.2}s queue={}ms transcribe={}ms rtf={:.3} output={}ms",
+            self.audio_secs,
+            self.queue_wait_ms,
+            self.transcribe_ms,
+            self.real_time_factor,
+            self.output_ms
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SessionStats, UtteranceMetrics};
+    use std::time::Duration;
+
+    #[test]
+    fn computes_real_time_factor() {
+        let metrics =
+            UtteranceMetrics::new(2.0, Duration::from_millis(5), Duration::from_millis(500));
+        assert_eq!(metrics.queue_wait_ms, 5);
+        assert_eq!(metrics.transcribe_ms, 500);
+        assert!((metrics.real_time_factor - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn zero_length_audio_has_zero_rtf() {
+        let metrics = UtteranceMetrics::new(0.0, Duration::ZERO, Duration::from_millis(10));
+        assert_eq!(metrics.real_time_factor, 0.0);
+    }
+
+    #[test]
+    fn session_stats_accumulate() {
+        let mut stats = SessionStats::default();
+        let mut metrics =
+            UtteranceMetrics::new(1.5, Duration::from_millis(10), Duration::from_millis(200));
+        metrics.output_ms = 90;
+        stats.record_utterance("hello there world", &metrics);
+        stats.record_utterance("again", &metrics);
+        stats.record_error();
+
+        assert_eq!(stats.utterances, 2);
+        assert_eq!(stats.words, 4);
+        assert_eq!(stats.errors, 1);
+        assert_eq!(stats.average_latency_ms(), 300);
+        assert!((stats.audio_secs - 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn keeps_only_recent_transcriptions() {
+        let mut stats = SessionStats::default();
+        let metrics = UtteranceMetrics::default();
+        for index in 0..=super::RECENT_LIMIT {
+            stats.record_utterance(&format!("utterance {index}"), &metrics);
+        }
+        assert_eq!(stats.recent.len(), super::RECENT_LIMIT);
+        assert_eq!(stats.recent.front().unwrap().0, "utterance 1");
+    }
+}
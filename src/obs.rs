@@ -0,0 +1,147 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::net::TcpStream;
+use std::sync::mpsc;
+use std::thread;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+use crate::config::{ObsConfig, ObsTarget};
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// obs-websocket 5 opcodes.
+const OP_HELLO: u64 = 0;
+const OP_IDENTIFY: u64 = 1;
+const OP_IDENTIFIED: u64 = 2;
+const OP_REQUEST: u64 = 6;
+const OP_REQUEST_RESPONSE: u64 = 7;
+
+/// Sends transcriptions to OBS over obs-websocket, as stream captions or as the
+/// text of a text source. Runs on its own thread so a slow or closed OBS never
+/// holds up typing; the connection is (re)opened on demand.
+pub struct Captions {
+    tx: mpsc::Sender<String>,
+}
+
+impl Captions {
+    pub fn spawn(config: &ObsConfig) -> Self {
+        let config = config.clone();
+        let (tx, rx) = mpsc::channel::<String>();
+        thread::spawn(move || {
+            let mut socket: Option<Socket> = None;
+            for text in rx {
+                let sent = match socket.as_mut() {
+                    Some(socket) => send(socket, &config, &text),
+                    None => connect(&config).and_then(|mut connected| {
+                        log::info!("Connected to OBS at {}", config.url);
+                        let sent = send(&mut connected, &config, &text);
+                        socket = Some(connected);
+                        sent
+                    }),
+                };
+                if let Err(err) = sent {
+                    log::warn!("Could not send captions to OBS: {err:#}");
+                    socket = None;
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn send(&self, text: &str) {
+        let _ = self.tx.send(text.to_string());
+    }
+}
+
+fn connect(config: &ObsConfig) -> Result<Socket> {
+    let (mut socket, _) = tungstenite::connect(config.url.as_str())
+        .with_context(|| format!("connecting to {} (is obs-websocket enabled?)", config.url))?;
+    let hello = receive(&mut socket, OP_HELLO)?;
+    let mut identify = json!({ "rpcVersion": 1, "eventSubscriptions": 0 });
+    if let Some(auth) = hello.get("authentication") {
+        let (Some(challenge), Some(salt)) = (auth["challenge"].as_str(), auth["salt"].as_str())
+        else {
+            bail!("OBS sent an authentication request without a challenge");
+        };
+        if config.password.is_empty() {
+            bail!("OBS requires a password; set [obs] password");
+        }
+        identify["authentication"] = authentication(&config.password, salt, challenge).into();
+    }
+    write(&mut socket, OP_IDENTIFY, identify)?;
+    receive(&mut socket, OP_IDENTIFIED).context("OBS rejected the connection (wrong password?)")?;
+    Ok(socket)
+}
+
+fn send(socket: &mut Socket, config: &ObsConfig, text: &str) -> Result<()> {
+    let (request_type, data) = match config.target {
+        ObsTarget::Captions => ("SendStreamCaption", json!({ "captionText": text })),
+        ObsTarget::TextSource => (
+            "SetInputSettings",
+            json!({ "inputName": config.source, "inputSettings": { "text": text } }),
+        ),
+    };
+    write(
+        socket,
+        OP_REQUEST,
+        json!({ "requestType": request_type, "requestId": "whisp", "requestData": data }),
+    )?;
+    let response = receive(socket, OP_REQUEST_RESPONSE)?;
+    let status = &response["requestStatus"];
+    if status["result"].as_bool() != Some(true) {
+        bail!(
+            "{request_type} failed: {}",
+            status["comment"].as_str().unwrap_or("no reason given")
+        );
+    }
+    Ok(())
+}
+
+/// The Identify secret: base64(sha256(base64(sha256(password + salt)) + challenge)).
+fn authentication(password: &str, salt: &str, challenge: &str) -> String {
+    let secret = BASE64.encode(Sha256::digest(format!("{password}{salt}")));
+    BASE64.encode(Sha256::digest(format!("{secret}{challenge}")))
+}
+
+fn write(socket: &mut Socket, op: u64, data: Value) -> Result<()> {
+    let message = json!({ "op": op, "d": data }).to_string();
+    socket.send(Message::Text(message))?;
+    Ok(())
+}
+
+/// Reads messages until one with opcode `op` arrives, skipping events and pings.
+fn receive(socket: &mut Socket, op: u64) -> Result<Value> {
+    loop {
+        let text = match socket.read()? {
+            Message::Text(text) => text,
+            Message::Close(_) => return Err(anyhow!("OBS closed the connection")),
+            _ => continue,
+        };
+        let mut message: Value = serde_json::from_str(&text).context("parsing OBS message")?;
+        if message["op"].as_u64() == Some(op) {
+            return Ok(message["d"].take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::authentication;
+
+    #[test]
+    fn computes_identify_secret() {
+        // Example from the obs-websocket 5 protocol documentation.
+        assert_eq!(
+            authentication(
+                "supersecretpassword",
+                "lM1GncleQOaCu9lT1yeUZhFYnqhsLLP1G5lAGo3ixaI=",
+                "+IxH4CnCiqpX1rM9scsNynZzbOe4KhDeYcTNS3PDaeY="
+            ),
+            "1Ct943GAT+6YQUUX47Ia/ncufilbe6+oD6lY+5kaCu4="
+        );
+    }
+}
@@ -1,9 +1,685 @@
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use crate::history::{self, History};
+use crate::logging;
+use crate::metrics::SessionStats;
+#[cfg(feature = "portal")]
+use crate::portal;
+use crate::postprocess::{self, DictationMode, EndAction, VoiceCommand};
+use crate::speech::{self, ReadBack, Voice};
+use crate::transcriber::Transcript;
 use crate::uinput::VirtualKeyboard;
+use crate::util;
 
-pub fn emit_text(text: &str, vkbd: &mut VirtualKeyboard) -> Result<()> {
-    vkbd.type_text(text)?;
-    log::info!("Output: typed {} chars via uinput", text.len());
-    Ok(())
+/// Requests handled by the output thread, in order of arrival.
+pub enum OutputCommand {
+    /// Type a finished transcription into the focused window.
+    Emit(Transcript),
+    /// Erase the most recently typed transcription.
+    UndoLast,
+    /// Switch numeric dictation on or off.
+    ToggleNumeric,
+    /// Type the transcription whose typing last failed.
+    RetryFailed,
+}
+
+/// Commands the output thread may fall behind by. The transcriber blocks when the
+/// channel is full; interactive senders use [`send_control`] and drop instead.
+pub const COMMAND_CAPACITY: usize = 32;
+
+/// Sends a command from an interactive thread (hotkeys, dashboard) without waiting
+/// for the output thread, which may be busy typing a long transcription.
+pub fn send_control(tx: &mpsc::SyncSender<OutputCommand>, command: OutputCommand) {
+    if let Err(mpsc::TrySendError::Full(_)) = tx.try_send(command) {
+        log::warn!("Output is busy, ignoring command");
+    }
+}
+
+/// Reports whether the focused widget is a password field, so typing is refused.
+pub type PasswordGuard = Box<dyn Fn() -> bool + Send>;
+/// Receives each typed transcription as well, e.g. to show it as live captions.
+pub type CaptionSink = Box<dyn Fn(&str) + Send>;
+
+/// Placeholder for the transcription in `OutputOptions::template`.
+pub const TEXT_PLACEHOLDER: &str = "{text}";
+
+/// Utterances emitted further apart than this are treated as unrelated for smart joining.
+const SMART_JOIN_WINDOW: Duration = Duration::from_secs(60);
+
+/// Options that shape how transcriptions are typed.
+pub struct OutputOptions {
+    /// Join back-to-back utterances with a single space and consistent capitalization.
+    pub smart_join: bool,
+    /// How transcriptions are interpreted before typing.
+    pub dictation_mode: DictationMode,
+    pub postprocess: postprocess::Options,
+    pub chunking: Chunking,
+    pub length_limit: LengthLimit,
+    /// Minimum pause between one emission finishing and the next starting.
+    pub spacing: Duration,
+    /// Wraps each transcription; `{text}` is replaced with the processed text.
+    pub template: String,
+    /// What happens to line breaks, tabs, and other control characters before typing.
+    pub control_chars: postprocess::ControlChars,
+    /// Speak each typed transcription through speech-dispatcher.
+    pub announce: bool,
+    pub read_back: ReadBack,
+    pub voice: Voice,
+}
+
+/// Splits typing into chunks with a pause in between, for apps that drop
+/// characters when a long burst of key events arrives at once.
+#[derive(Debug, Clone, Copy)]
+pub struct Chunking {
+    /// Characters per chunk; 0 types everything in one go.
+    pub size: usize,
+    pub delay: Duration,
+}
+
+/// What to do with a transcription longer than `LengthLimit::max_chars`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LongOutput {
+    /// Type the first `max_chars` characters, cut at a word boundary.
+    #[default]
+    Truncate,
+    /// Type nothing until the retry key is pressed to confirm.
+    Confirm,
+}
+
+/// Guards against a runaway transcription dumping a wall of text.
+#[derive(Debug, Clone, Copy)]
+pub struct LengthLimit {
+    /// Longest transcription typed as-is; 0 disables the limit.
+    pub max_chars: usize,
+    pub action: LongOutput,
+}
+
+/// Where key events are sent.
+pub enum Keyboard {
+    /// The native /dev/uinput virtual keyboard.
+    Uinput(VirtualKeyboard),
+    /// The RemoteDesktop portal, for sandboxes without device access.
+    #[cfg(feature = "portal")]
+    Portal(portal::Keyboard),
+}
+
+impl Keyboard {
+    fn type_text(&mut self, text: &str) -> Result<usize> {
+        match self {
+            Keyboard::Uinput(vkbd) => vkbd.type_text(text),
+            #[cfg(feature = "portal")]
+            Keyboard::Portal(keyboard) => keyboard.type_text(text),
+        }
+    }
+
+    fn backspace(&mut self, count: usize) -> Result<()> {
+        match self {
+            Keyboard::Uinput(vkbd) => vkbd.backspace(count),
+            #[cfg(feature = "portal")]
+            Keyboard::Portal(keyboard) => keyboard.backspace(count),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Keyboard::Uinput(_) => "uinput",
+            #[cfg(feature = "portal")]
+            Keyboard::Portal(_) => "the RemoteDesktop portal",
+        }
+    }
+}
+
+pub fn emit_text(text: &str, keyboard: &mut Keyboard, chunking: Chunking) -> Result<usize> {
+    let mut typed = 0;
+    for (index, chunk) in chunks(text, chunking.size).into_iter().enumerate() {
+        if index > 0 {
+            thread::sleep(chunking.delay);
+        }
+        typed += keyboard.type_text(chunk)?;
+    }
+    log::info!("Output: typed {typed} chars via {}", keyboard.name());
+    Ok(typed)
+}
+
+/// Cuts `text` to at most `max_chars` characters, at the last word boundary if
+/// there is one in the second half.
+fn truncate(text: &str, max_chars: usize) -> &str {
+    let Some((end, _)) = text.char_indices().nth(max_chars) else {
+        return text;
+    };
+    let cut = &text[..end];
+    match cut.rfind(char::is_whitespace) {
+        Some(space) if space >= end / 2 => cut[..space].trim_end(),
+        _ => cut,
+    }
+}
+
+/// Splits `text` into pieces of at most `size` characters (one piece when `size` is 0).
+fn chunks(text: &str, size: usize) -> Vec<&str> {
+    if size == 0 {
+        return vec![text];
+    }
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (count, (index, _)) in text.char_indices().enumerate() {
+        if count > 0 && count % size == 0 {
+            pieces.push(&text[start..index]);
+            start = index;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+}
+
+struct Worker {
+    keyboard: Keyboard,
+    options: OutputOptions,
+    stats: Arc<Mutex<SessionStats>>,
+    history: Option<History>,
+    /// Characters typed by the most recent emission, erased by `UndoLast`.
+    last_typed: usize,
+    /// Last character of the previous emission and when it was typed, for smart joining.
+    last_tail: Option<(char, Instant)>,
+    /// Numeric mode toggled on at runtime, overriding the configured dictation mode.
+    numeric: bool,
+    /// Text that failed to type or awaits confirmation, kept for `RetryFailed`.
+    failed: Option<Pending>,
+    /// When the previous emission finished, for `OutputOptions::spacing`.
+    last_emitted: Option<Instant>,
+    password_guard: Option<PasswordGuard>,
+    captions: Option<CaptionSink>,
+}
+
+/// A processed transcription ready to be typed.
+struct Pending {
+    /// Exactly what to type, after joining.
+    text: String,
+    end_action: Option<EndAction>,
+    transcript: Transcript,
+    started: Instant,
+}
+
+impl Worker {
+    fn handle(&mut self, command: OutputCommand) {
+        match command {
+            OutputCommand::Emit(transcript) => self.emit(transcript),
+            OutputCommand::UndoLast => self.undo_last(),
+            OutputCommand::ToggleNumeric => self.toggle_numeric(),
+            OutputCommand::RetryFailed => self.retry_failed(),
+        }
+    }
+
+    fn toggle_numeric(&mut self) {
+        self.numeric = !self.numeric;
+        log::info!(
+            "Numeric mode {}",
+            if self.numeric { "enabled" } else { "disabled" }
+        );
+    }
+
+    fn dictation_mode(&self) -> DictationMode {
+        if self.numeric {
+            DictationMode::Numeric
+        } else {
+            self.options.dictation_mode
+        }
+    }
+
+    fn emit(&mut self, mut transcript: Transcript) {
+        log::info!("Transcribed: {}", logging::redact(&transcript.text));
+        self.pace();
+        let started = Instant::now();
+        let (text, end_action) =
+            match prepare(&transcript.text, self.dictation_mode(), &self.options) {
+                Prepared::Command(VoiceCommand::ToggleNumeric) => return self.toggle_numeric(),
+                Prepared::Command(VoiceCommand::UndoLast) => return self.undo_last(),
+                Prepared::Discard => {
+                    log::info!("Utterance discarded by voice command");
+                    return;
+                }
+                Prepared::Text { text, end_action } => (text, end_action),
+            };
+        if text.is_empty() && end_action.is_none() {
+            log::debug!("Nothing to type after post-processing");
+            return;
+        }
+        let templated = self.options.template != TEXT_PLACEHOLDER;
+        transcript.text = text;
+        let text = match self.last_tail {
+            // The rest of a recording typed progressively always joins its start.
+            Some((tail, _)) if transcript.continuation => join_text(tail, &transcript.text),
+            // A template defines its own separators, so it is typed as-is.
+            Some((tail, at))
+                if self.options.smart_join && !templated && at.elapsed() < SMART_JOIN_WINDOW =>
+            {
+                join_text(tail, &transcript.text)
+            }
+            _ => transcript.text.clone(),
+        };
+        let limit = self.options.length_limit;
+        let count = text.chars().count();
+        let text = if limit.max_chars > 0 && count > limit.max_chars {
+            match limit.action {
+                LongOutput::Truncate => {
+                    log::warn!(
+                        "Transcription is {count} characters; typing only the first {}",
+                        limit.max_chars
+                    );
+                    util::notify(
+                        "whisp",
+                        &format!("Long transcription cut to {} characters", limit.max_chars),
+                    );
+                    truncate(&text, limit.max_chars).to_string()
+                }
+                LongOutput::Confirm => {
+                    log::warn!(
+                        "Transcription is {count} characters (max_output_chars is {}); press the retry key to type it",
+                        limit.max_chars
+                    );
+                    util::notify(
+                        "whisp",
+                        &format!(
+                            "{count} characters transcribed. Press the retry key to type them."
+                        ),
+                    );
+                    self.hold(Pending {
+                        text,
+                        end_action,
+                        transcript,
+                        started,
+                    });
+                    return;
+                }
+            }
+        } else {
+            text
+        };
+        self.deliver(Pending {
+            text,
+            end_action,
+            transcript,
+            started,
+        });
+    }
+
+    /// Waits out the rest of `spacing` since the previous emission. Commands are
+    /// handled one at a time in arrival order, so this is all the pacing needed.
+    fn pace(&self) {
+        if let Some(at) = self.last_emitted {
+            let wait = self.options.spacing.saturating_sub(at.elapsed());
+            if !wait.is_zero() {
+                thread::sleep(wait);
+            }
+        }
+    }
+
+    /// Types a prepared transcription (read back first with `ReadBack::Before`), then
+    /// speaks and records it. On failure the text is held, so `RetryFailed` can type
+    /// it later.
+    fn deliver(&mut self, pending: Pending) {
+        if self.password_guard.as_ref().is_some_and(|guard| guard()) {
+            log::warn!(
+                "A password field has focus; not typing the transcription. Move focus and press the retry key to type it."
+            );
+            util::notify("whisp", "Not typing into a password field");
+            if self.options.announce {
+                speech::say("Password field, not typing", speech::Priority::Important);
+            }
+            self.hold(pending);
+            return;
+        }
+        if self.options.read_back == ReadBack::Before {
+            if let Err(err) = self.options.voice.speak(&pending.transcript.text) {
+                log::warn!("Read-back failed: {err:#}");
+            }
+        }
+        let result = emit_text(&pending.text, &mut self.keyboard, self.options.chunking);
+        self.last_emitted = Some(Instant::now());
+        match result {
+            Ok(typed) => {
+                self.last_typed = typed;
+                self.last_tail = pending
+                    .text
+                    .chars()
+                    .last()
+                    .map(|tail| (tail, Instant::now()));
+            }
+            Err(err) => {
+                log::error!(
+                    "Failed to emit output text: {err}. The text is kept; press the retry key (F5 in --tui) to type it again."
+                );
+                self.stats.lock().unwrap().record_error();
+                if self.options.announce {
+                    speech::say("Typing failed", speech::Priority::Important);
+                }
+                self.hold(pending);
+                return;
+            }
+        }
+        let Pending {
+            end_action,
+            mut transcript,
+            started,
+            ..
+        } = pending;
+        if end_action == Some(EndAction::Submit) {
+            if let Err(err) = self.keyboard.type_text("\n") {
+                log::error!("Failed to press Enter: {err}");
+            }
+            // Submitted text can no longer be erased or joined onto.
+            self.last_typed = 0;
+            self.last_tail = None;
+        }
+        if self.options.read_back == ReadBack::After {
+            let voice = self.options.voice.clone();
+            let text = transcript.text.clone();
+            thread::spawn(move || {
+                if let Err(err) = voice.speak(&text) {
+                    log::warn!("Read-back failed: {err:#}");
+                }
+            });
+        } else if self.options.announce && self.options.read_back == ReadBack::Off {
+            speech::say(&transcript.text, speech::Priority::Message);
+        }
+        if let Some(captions) = &self.captions {
+            captions(&transcript.text);
+        }
+        transcript.metrics.output_ms = started.elapsed().as_millis() as u64;
+        log::info!("Utterance metrics: {}", transcript.metrics);
+        self.stats
+            .lock()
+            .unwrap()
+            .record_utterance(&transcript.text, &transcript.metrics);
+        if let Some(history) = self.history.as_mut() {
+            let entry = history::Entry::new(&transcript.text, &transcript.metrics);
+            if let Err(err) = history.append(&entry) {
+                log::warn!("Failed to record history: {err:#}");
+            }
+        }
+    }
+
+    /// Keeps `pending` for `RetryFailed`. Only one transcription is held, so an
+    /// older one still waiting is dropped.
+    fn hold(&mut self, pending: Pending) {
+        if let Some(previous) = self.failed.replace(pending) {
+            log::warn!(
+                "Dropped a held transcription that was never typed: {}",
+                logging::redact(&previous.transcript.text)
+            );
+        }
+    }
+
+    fn retry_failed(&mut self) {
+        let Some(mut pending) = self.failed.take() else {
+            log::info!("Retry: nothing to type");
+            return;
+        };
+        log::info!("Retry: typing the held transcription");
+        self.pace();
+        pending.started = Instant::now();
+        self.deliver(pending);
+    }
+
+    fn undo_last(&mut self) {
+        if self.last_typed == 0 {
+            log::info!("Undo: nothing to erase");
+            return;
+        }
+        match self.keyboard.backspace(self.last_typed) {
+            Ok(()) => log::info!("Undo: erased {} chars", self.last_typed),
+            Err(err) => log::error!("Undo failed: {err}"),
+        }
+        self.last_typed = 0;
+        self.last_tail = None;
+    }
+}
+
+/// What an utterance turns into before anything is typed.
+enum Prepared {
+    /// A whole-utterance voice command; nothing is typed.
+    Command(VoiceCommand),
+    /// Dropped by "scratch that" at the end.
+    Discard,
+    /// Processed and templated text, plus any trailing action.
+    Text {
+        text: String,
+        end_action: Option<EndAction>,
+    },
+}
+
+/// Applies voice commands, post-processing, and the template to a transcription.
+fn prepare(text: &str, mode: DictationMode, options: &OutputOptions) -> Prepared {
+    let stripped;
+    let text = if options.postprocess.strip_non_speech {
+        stripped = postprocess::strip_non_speech(text);
+        stripped.as_str()
+    } else {
+        text
+    };
+    let mut body = text;
+    let mut end_action = None;
+    if options.postprocess.voice_commands && mode != DictationMode::Phrases {
+        if let Some(command) = postprocess::voice_command(text) {
+            return Prepared::Command(command);
+        }
+        let (rest, action) = postprocess::split_end_action(text);
+        if action == Some(EndAction::Discard) {
+            return Prepared::Discard;
+        }
+        body = rest;
+        end_action = action;
+    }
+    let mut text = postprocess::process(body, mode, &options.postprocess);
+    if options.template != TEXT_PLACEHOLDER && !text.is_empty() {
+        text = options.template.replace(TEXT_PLACEHOLDER, &text);
+    }
+    if end_action == Some(EndAction::NewParagraph) {
+        text.push_str("\n\n");
+    }
+    text = options.control_chars.apply(&text);
+    Prepared::Text { text, end_action }
+}
+
+/// Describes what typing `text` would produce, without touching the keyboard.
+pub fn dry_run(text: &str, options: &OutputOptions) -> String {
+    match prepare(text, options.dictation_mode, options) {
+        Prepared::Command(command) => format!("(voice command {command:?})"),
+        Prepared::Discard => "(discarded)".to_string(),
+        Prepared::Text { text, end_action } => match end_action {
+            Some(EndAction::Submit) => format!("{text:?} then Enter"),
+            _ => format!("{text:?}"),
+        },
+    }
+}
+
+/// Prepares `text` to follow directly after previously typed text ending in `tail`:
+/// inserts exactly one separating space, drops a duplicated leading punctuation mark,
+/// and matches capitalization to whether the previous sentence was finished.
+fn join_text(tail: char, text: &str) -> String {
+    let text = text.trim_start();
+    let Some(first) = text.chars().next() else {
+        return String::new();
+    };
+    if tail.is_whitespace() {
+        return text.to_string();
+    }
+
+    let sentence_ended = matches!(tail, '.' | '!' | '?');
+    if first.is_ascii_punctuation() && !matches!(first, '"' | '\'' | '(' | '[') {
+        if first != tail && !sentence_ended {
+            // Punctuation attaches directly to the previous word: "word" + ", more".
+            return text.to_string();
+        }
+        // "done." followed by ". Next" -> keep only one mark.
+        let rest = text[first.len_utf8()..].trim_start();
+        return if rest.is_empty() {
+            String::new()
+        } else {
+            join_text(tail, rest)
+        };
+    }
+
+    let mut joined = String::with_capacity(text.len() + 1);
+    joined.push(' ');
+    if sentence_ended {
+        joined.extend(first.to_uppercase());
+        joined.push_str(&text[first.len_utf8()..]);
+    } else if starts_with_capitalized_common_word(text) {
+        joined.extend(first.to_lowercase());
+        joined.push_str(&text[first.len_utf8()..]);
+    } else {
+        joined.push_str(text);
+    }
+    joined
+}
+
+/// True when the text starts with a capitalized word that the model most likely
+/// capitalized only because it began the utterance ("The", but not "I", "NASA", or "I'm").
+fn starts_with_capitalized_common_word(text: &str) -> bool {
+    let word: String = text
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '\'')
+        .collect();
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return false;
+    };
+    if !first.is_uppercase() || word == "I" || word.starts_with("I'") {
+        return false;
+    }
+    chars.all(|c| !c.is_uppercase())
+}
+
+/// Spawns the output thread, which types transcriptions and handles undo requests.
+pub fn spawn_worker(
+    keyboard: Keyboard,
+    options: OutputOptions,
+    rx: mpsc::Receiver<OutputCommand>,
+    stats: Arc<Mutex<SessionStats>>,
+    history: Option<History>,
+    password_guard: Option<PasswordGuard>,
+    captions: Option<CaptionSink>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut worker = Worker {
+            keyboard,
+            options,
+            stats,
+            history,
+            last_typed: 0,
+            last_tail: None,
+            numeric: false,
+            failed: None,
+            last_emitted: None,
+            password_guard,
+            captions,
+        };
+        for command in rx {
+            worker.handle(command);
+        }
+        if let Some(history) = &worker.history {
+            if let Err(err) = history.sync() {
+                log::warn!("{err:#}");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        chunks, dry_run, join_text, truncate, Chunking, LengthLimit, LongOutput, OutputOptions,
+    };
+    use crate::postprocess::{self, DictationMode};
+    use crate::speech::{ReadBack, Voice};
+    use std::time::Duration;
+
+    #[test]
+    fn truncates_at_word_boundaries() {
+        assert_eq!(truncate("one two three four", 11), "one two");
+        assert_eq!(truncate("supercalifragilistic", 5), "super");
+        assert_eq!(truncate("héllo", 10), "héllo");
+    }
+
+    fn options(postprocess: postprocess::Options) -> OutputOptions {
+        OutputOptions {
+            smart_join: false,
+            dictation_mode: DictationMode::Text,
+            postprocess,
+            chunking: Chunking {
+                size: 0,
+                delay: Duration::ZERO,
+            },
+            length_limit: LengthLimit {
+                max_chars: 0,
+                action: LongOutput::Truncate,
+            },
+            spacing: Duration::ZERO,
+            template: "- {text}".into(),
+            control_chars: Default::default(),
+            announce: false,
+            read_back: ReadBack::Off,
+            voice: Voice::SpeechDispatcher,
+        }
+    }
+
+    #[test]
+    fn dry_run_applies_commands_and_template() {
+        let options = options(postprocess::Options {
+            voice_commands: true,
+            ..Default::default()
+        });
+        assert_eq!(
+            dry_run("Buy milk. Send it.", &options),
+            "\"- Buy milk.\" then Enter"
+        );
+        assert_eq!(
+            dry_run("Scratch that.", &options),
+            "(voice command UndoLast)"
+        );
+    }
+
+    #[test]
+    fn end_phrases_are_typed_without_voice_commands() {
+        let options = options(Default::default());
+        assert_eq!(
+            dry_run("The draft is done, so I'll send it.", &options),
+            "\"- The draft is done, so I'll send it.\""
+        );
+        assert_eq!(dry_run("Scratch that.", &options), "\"- Scratch that.\"");
+    }
+
+    #[test]
+    fn splits_text_into_character_chunks() {
+        assert_eq!(chunks("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(chunks("héllo", 2), vec!["hé", "ll", "o"]);
+        assert_eq!(chunks("abc", 0), vec!["abc"]);
+        assert_eq!(chunks("abc", 3), vec!["abc"]);
+    }
+
+    #[test]
+    fn joins_after_finished_sentence() {
+        assert_eq!(join_text('.', "Next sentence."), " Next sentence.");
+        assert_eq!(join_text('.', "next sentence."), " Next sentence.");
+    }
+
+    #[test]
+    fn continues_unfinished_sentence_in_lowercase() {
+        assert_eq!(join_text('d', "The rest."), " the rest.");
+        assert_eq!(join_text(',', "I think so."), " I think so.");
+        assert_eq!(join_text('d', "NASA launched."), " NASA launched.");
+    }
+
+    #[test]
+    fn avoids_double_punctuation_and_spaces() {
+        assert_eq!(join_text('.', ". Then more."), " Then more.");
+        assert_eq!(join_text('d', ", and more."), ", and more.");
+        assert_eq!(join_text(' ', "Hello."), "Hello.");
+        assert_eq!(join_text('.', "."), "");
+    }
 }
@@ -0,0 +1,132 @@
+use anyhow::{bail, Result};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::audio::{self, SourceMonitor};
+use crate::config;
+
+const REFRESH: Duration = Duration::from_millis(100);
+const METER_WIDTH: usize = 20;
+
+struct Choice {
+    /// `audio_device` value; empty for the system default.
+    name: String,
+    label: String,
+    /// None when `parec` could not open the source.
+    monitor: Option<SourceMonitor>,
+}
+
+/// `whisp --pick-device`: shows every input source with a live level meter and
+/// writes the selected one to `audio_device`. Returns the config path and the
+/// chosen source's description, or None if the user cancelled.
+pub fn run(path_override: Option<&Path>) -> Result<Option<(PathBuf, String)>> {
+    let loaded = config::load_config(path_override)?;
+    let mut choices = vec![Choice {
+        name: String::new(),
+        label: "System default".into(),
+        monitor: SourceMonitor::spawn("@DEFAULT_SOURCE@").ok(),
+    }];
+    for source in audio::list_input_sources()? {
+        let monitor = SourceMonitor::spawn(&source.name).ok();
+        choices.push(Choice {
+            name: source.name,
+            label: source.description,
+            monitor,
+        });
+    }
+    if choices.iter().all(|choice| choice.monitor.is_none()) {
+        bail!("Could not read any input source. Install pulseaudio-utils or pipewire-pulse for parec.");
+    }
+
+    let mut state = ListState::default().with_selected(Some(
+        choices
+            .iter()
+            .position(|choice| choice.name == loaded.config.audio_device)
+            .unwrap_or(0),
+    ));
+    let mut terminal = ratatui::init();
+    let picked = pick(&mut terminal, &choices, &mut state);
+    ratatui::restore();
+
+    let Some(index) = picked? else {
+        return Ok(None);
+    };
+    let choice = &choices[index];
+    config::update_config(
+        &loaded.path,
+        &[("audio_device", choice.name.clone().into())],
+    )?;
+    Ok(Some((loaded.path, choice.label.clone())))
+}
+
+fn pick(
+    terminal: &mut DefaultTerminal,
+    choices: &[Choice],
+    state: &mut ListState,
+) -> Result<Option<usize>> {
+    loop {
+        terminal.draw(|frame| draw(frame, choices, state))?;
+        if !event::poll(REFRESH)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => state.select_previous(),
+            KeyCode::Down | KeyCode::Char('j') => state.select_next(),
+            KeyCode::Enter => return Ok(state.selected()),
+            KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, choices: &[Choice], state: &mut ListState) {
+    let [list_area, help_area] =
+        Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+
+    let items: Vec<ListItem> = choices
+        .iter()
+        .map(|choice| {
+            let meter = match &choice.monitor {
+                Some(monitor) => {
+                    let filled =
+                        ((monitor.level() * METER_WIDTH as f32).round() as usize).min(METER_WIDTH);
+                    format!(
+                        "[{}{}]",
+                        "#".repeat(filled),
+                        " ".repeat(METER_WIDTH - filled)
+                    )
+                }
+                None => format!("[{:^METER_WIDTH$}]", "unavailable"),
+            };
+            ListItem::new(Line::from(vec![
+                meter.fg(Color::Cyan),
+                format!("  {}", choice.label).into(),
+            ]))
+        })
+        .collect();
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::bordered().title(" Choose a microphone (speak to test) "))
+            .highlight_symbol("> ")
+            .highlight_style(Color::Yellow),
+        list_area,
+        state,
+    );
+    frame.render_widget(
+        Paragraph::new("Up/Down select   Enter save to config   Esc cancel").fg(Color::DarkGray),
+        help_area,
+    );
+}
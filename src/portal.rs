@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Context, Result};
+use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+use ashpd::desktop::remote_desktop::{DeviceType, KeyState, RemoteDesktop};
+use ashpd::desktop::{PersistMode, Session};
+use futures_lite::{future, StreamExt};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::hotkey::{Action, HotkeyEvent};
+
+const KEYSYM_BACKSPACE: i32 = 0xff08;
+const KEYSYM_TAB: i32 = 0xff09;
+const KEYSYM_RETURN: i32 = 0xff0d;
+
+/// Portal shortcut id and description for each action.
+fn shortcut_id(action: Action) -> (&'static str, &'static str) {
+    match action {
+        Action::Record => ("record", "Hold to record"),
+        Action::Undo => ("undo", "Erase the last transcription"),
+        Action::ToggleNumeric => ("toggle-numeric", "Toggle numeric dictation"),
+        Action::Retry => ("retry", "Type the last failed transcription again"),
+    }
+}
+
+/// Suggests an XDG shortcuts trigger ("Insert", "F13", "CTRL") for an evdev key
+/// name; the desktop lets the user pick another.
+fn preferred_trigger(name: &str) -> String {
+    match name {
+        "leftctrl" | "rightctrl" => "CTRL".into(),
+        "leftshift" | "rightshift" => "SHIFT".into(),
+        "leftalt" | "rightalt" => "ALT".into(),
+        "leftmeta" | "rightmeta" => "LOGO".into(),
+        name if name.chars().count() == 1 => name.into(),
+        name => {
+            let mut chars = name.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Suggested trigger for the record key, with `modifiers` ("LOGO+Insert").
+fn record_trigger(name: &str, modifiers: &[String]) -> String {
+    modifiers
+        .iter()
+        .map(String::as_str)
+        .chain([name])
+        .map(preferred_trigger)
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// Binds the hotkeys through the GlobalShortcuts portal and forwards their
+/// activation as hotkey events, in place of `hotkey::spawn_listener`.
+pub fn spawn_shortcuts(
+    bindings: &[(Action, &str)],
+    modifiers: &[String],
+    tx: mpsc::SyncSender<HotkeyEvent>,
+) -> Result<()> {
+    let shortcuts: Vec<NewShortcut> = bindings
+        .iter()
+        .map(|(action, name)| {
+            let (id, description) = shortcut_id(*action);
+            let trigger = match action {
+                Action::Record => record_trigger(name, modifiers),
+                _ => preferred_trigger(name),
+            };
+            NewShortcut::new(id, description).preferred_trigger(trigger.as_str())
+        })
+        .collect();
+    let actions: Vec<Action> = bindings.iter().map(|(action, _)| *action).collect();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    thread::spawn(move || {
+        future::block_on(async move {
+            let setup = async {
+                let portal = GlobalShortcuts::new().await?;
+                let session = portal.create_session().await?;
+                portal
+                    .bind_shortcuts(&session, &shortcuts, None)
+                    .await?
+                    .response()?;
+                let activated = portal.receive_activated().await?;
+                let deactivated = portal.receive_deactivated().await?;
+                Ok::<_, ashpd::Error>((portal, session, activated, deactivated))
+            };
+            let (_portal, _session, activated, deactivated) = match setup.await {
+                Ok(bound) => {
+                    let _ = ready_tx.send(Ok(()));
+                    bound
+                }
+                Err(err) => {
+                    let _ = ready_tx.send(Err(err));
+                    return;
+                }
+            };
+            let pressed = activated.map(|signal| (signal.shortcut_id().to_string(), true));
+            let released = deactivated.map(|signal| (signal.shortcut_id().to_string(), false));
+            let mut signals = pressed.or(released);
+            while let Some((id, down)) = signals.next().await {
+                let Some(action) = actions.iter().find(|a| shortcut_id(**a).0 == id) else {
+                    continue;
+                };
+                let event = if down {
+                    HotkeyEvent::Pressed(*action)
+                } else {
+                    HotkeyEvent::Released(*action)
+                };
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+            log::warn!("GlobalShortcuts portal session ended");
+        });
+    });
+    ready_rx
+        .recv()
+        .map_err(|_| anyhow!("GlobalShortcuts portal thread exited"))?
+        .context("binding hotkeys through the GlobalShortcuts portal")
+}
+
+/// Restore token that lets later runs skip the RemoteDesktop permission dialog.
+fn restore_token_path() -> PathBuf {
+    crate::config::default_log_path().with_file_name("portal-restore-token")
+}
+
+/// Types text through the RemoteDesktop portal instead of /dev/uinput.
+pub struct Keyboard {
+    portal: RemoteDesktop<'static>,
+    session: Session<'static, RemoteDesktop<'static>>,
+}
+
+impl Keyboard {
+    /// Starts a keyboard-only RemoteDesktop session. The first run shows the
+    /// desktop's permission dialog; the grant is remembered until revoked.
+    pub fn connect() -> Result<Self> {
+        let token_path = restore_token_path();
+        let token = fs::read_to_string(&token_path).ok();
+        future::block_on(async {
+            let portal = RemoteDesktop::new().await?;
+            let session = portal.create_session().await?;
+            portal
+                .select_devices(
+                    &session,
+                    DeviceType::Keyboard.into(),
+                    token.as_deref().map(str::trim),
+                    PersistMode::ExplicitlyRevoked,
+                )
+                .await?;
+            let selected = portal.start(&session, None).await?.response()?;
+            if let Some(token) = selected.restore_token() {
+                if let Some(parent) = token_path.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                if let Err(err) = fs::write(&token_path, token) {
+                    log::warn!("Could not save {}: {err}", token_path.display());
+                }
+            }
+            Ok::<_, ashpd::Error>(Self { portal, session })
+        })
+        .context("starting a RemoteDesktop portal session for typing")
+    }
+
+    /// Types each character as a keysym, so any Unicode character works.
+    /// Returns the number of characters typed.
+    pub fn type_text(&mut self, text: &str) -> Result<usize> {
+        let mut typed = 0;
+        for ch in text.chars() {
+            self.tap(keysym(ch))?;
+            typed += 1;
+        }
+        Ok(typed)
+    }
+
+    pub fn backspace(&mut self, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.tap(KEYSYM_BACKSPACE)?;
+        }
+        Ok(())
+    }
+
+    fn tap(&self, keysym: i32) -> Result<()> {
+        future::block_on(async {
+            self.portal
+                .notify_keyboard_keysym(&self.session, keysym, KeyState::Pressed)
+                .await?;
+            self.portal
+                .notify_keyboard_keysym(&self.session, keysym, KeyState::Released)
+                .await
+        })
+        .context("sending key through the RemoteDesktop portal")
+    }
+}
+
+/// X11 keysym for a character: Latin-1 maps directly, the rest use the Unicode range.
+fn keysym(ch: char) -> i32 {
+    match ch {
+        '\n' => KEYSYM_RETURN,
+        '\t' => KEYSYM_TAB,
+        ' '..='~' | '\u{a0}'..='\u{ff}' => ch as i32,
+        _ => 0x0100_0000 | ch as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keysym, preferred_trigger, record_trigger};
+
+    #[test]
+    fn maps_characters_to_keysyms() {
+        assert_eq!(keysym('a'), 0x61);
+        assert_eq!(keysym('é'), 0xe9);
+        assert_eq!(keysym('\n'), 0xff0d);
+        assert_eq!(keysym('€'), 0x0100_20ac);
+    }
+
+    #[test]
+    fn suggests_triggers_from_key_names() {
+        assert_eq!(preferred_trigger("insert"), "Insert");
+        assert_eq!(preferred_trigger("f13"), "F13");
+        assert_eq!(preferred_trigger("a"), "a");
+        assert_eq!(preferred_trigger("rightctrl"), "CTRL");
+        assert_eq!(
+            record_trigger("insert", &["leftmeta".into()]),
+            "LOGO+Insert"
+        );
+    }
+}
@@ -0,0 +1,1582 @@
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::LazyLock;
+
+use crate::config::RedactConfig;
+use crate::logging;
+
+/// How transcribed speech is turned into typed text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DictationMode {
+    /// Type the transcription as-is.
+    Text,
+    /// Interpret NATO alphabet words, digits, and symbol names ("alpha seven dash" -> "a7-").
+    Spelling,
+    /// Emit spoken numbers and symbols literally ("four two dot one" -> "42.1").
+    Numeric,
+    /// Text, plus leading phrases that produce Markdown structure ("heading two ...", "bullet ...").
+    Markdown,
+    /// Identifiers and operators for editors and terminals ("snake case user id equals" -> "user_id =").
+    Code,
+    /// Only the configured `phrases`: each utterance is snapped to the closest one or dropped.
+    Phrases,
+}
+
+/// What to do with profanity in transcriptions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Censor {
+    /// Type words as transcribed.
+    #[default]
+    Off,
+    /// Keep the first letter and replace the rest with asterisks ("d***").
+    Mask,
+    /// Drop the word entirely.
+    Remove,
+}
+
+/// Whole-utterance commands that change dictation state instead of typing text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoiceCommand {
+    ToggleNumeric,
+    /// "scratch that" on its own: erase the previous emission.
+    UndoLast,
+}
+
+/// Action requested by a command phrase at the end of an utterance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndAction {
+    /// "send it": type the text, then press Enter.
+    Submit,
+    /// "new paragraph": type the text followed by a blank line.
+    NewParagraph,
+    /// "scratch that": drop the whole utterance.
+    Discard,
+}
+
+/// Trailing phrases recognized by `split_end_action`, as normalized words.
+const END_ACTIONS: &[(&[&str], EndAction)] = &[
+    (&["send", "it"], EndAction::Submit),
+    (&["press", "enter"], EndAction::Submit),
+    (&["new", "paragraph"], EndAction::NewParagraph),
+    (&["scratch", "that"], EndAction::Discard),
+];
+
+/// Post-processing settings that apply regardless of the dictation mode.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Interpret spoken formatting commands such as "all caps" and "no space".
+    pub voice_commands: bool,
+    /// Replace spoken emoji names ("thumbs up emoji", "smiley face") with the emoji.
+    pub emoji: bool,
+    pub censor: Censor,
+    pub dictionary: Dictionary,
+    pub redactor: Redactor,
+    /// Drop annotations like "[MUSIC]", "(laughs)" and "♪" before anything else.
+    pub strip_non_speech: bool,
+    /// Capitalize and end with punctuation text the model left as a lowercase stream.
+    pub punctuate: bool,
+    pub phrases: Phrases,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            voice_commands: false,
+            emoji: false,
+            censor: Censor::Off,
+            dictionary: Dictionary::default(),
+            redactor: Redactor::default(),
+            strip_non_speech: false,
+            punctuate: false,
+            phrases: Phrases::default(),
+        }
+    }
+}
+
+/// User word list that fixes the spelling and casing of frequently used terms
+/// ("github" -> "GitHub"), matched case-insensitively on whole words.
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    /// Normalized spoken words and their replacement, longest phrase first.
+    entries: Vec<(Vec<String>, String)>,
+}
+
+impl Dictionary {
+    pub fn new(entries: &BTreeMap<String, String>) -> Self {
+        let mut entries: Vec<(Vec<String>, String)> = entries
+            .iter()
+            .map(|(spoken, written)| (words(spoken), written.clone()))
+            .filter(|(spoken, _)| !spoken.is_empty())
+            .collect();
+        entries.sort_by_key(|(spoken, _)| std::cmp::Reverse(spoken.len()));
+        Self { entries }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        if self.entries.is_empty() {
+            return text.to_string();
+        }
+        replace_phrases(text, |words| {
+            self.entries
+                .iter()
+                .find(|(spoken, _)| words.starts_with(spoken))
+                .map(|(spoken, written)| (spoken.len(), written.as_str()))
+        })
+    }
+}
+
+/// Fixed utterances for `dictation_mode = "phrases"`, for command-and-control setups
+/// where nothing outside the list should ever be typed.
+#[derive(Debug, Clone, Default)]
+pub struct Phrases {
+    /// Normalized spoken form and the phrase as configured.
+    entries: Vec<(String, String)>,
+}
+
+impl Phrases {
+    pub fn new(phrases: &[String]) -> Self {
+        let entries = phrases
+            .iter()
+            .map(|phrase| (words(phrase).join(" "), phrase.clone()))
+            .filter(|(spoken, _)| !spoken.is_empty())
+            .collect();
+        Self { entries }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The phrase closest to `text`, allowing about one misheard character in four.
+    pub fn closest(&self, text: &str) -> Option<&str> {
+        let spoken = words(text).join(" ");
+        if spoken.is_empty() {
+            return None;
+        }
+        self.entries
+            .iter()
+            .map(|(phrase, written)| (edit_distance(&spoken, phrase), phrase, written))
+            .filter(|(distance, phrase, _)| *distance <= phrase.chars().count() / 4)
+            .min_by_key(|(distance, ..)| *distance)
+            .map(|(.., written)| written.as_str())
+    }
+}
+
+/// Levenshtein distance between two strings, in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// What happens to line breaks or tabs in typed text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Breaks {
+    /// Type them as Enter or Tab.
+    #[default]
+    Keep,
+    /// Replace each run with a single space.
+    Space,
+    /// Drop them.
+    Remove,
+}
+
+/// Handling of control characters before typing. Control characters other than
+/// line breaks and tabs have no key to type and are always dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ControlChars {
+    /// `\n` and `\r`.
+    pub newlines: Breaks,
+    pub tabs: Breaks,
+}
+
+impl ControlChars {
+    /// `terminal_safe = true`: nothing that could press Enter or trigger completion.
+    pub const TERMINAL: Self = Self {
+        newlines: Breaks::Space,
+        tabs: Breaks::Space,
+    };
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut pending_space = false;
+        for ch in text.chars() {
+            let breaks = match ch {
+                '\n' | '\r' => self.newlines,
+                '\t' => self.tabs,
+                _ if ch.is_control() => continue,
+                _ => {
+                    if pending_space && ch != ' ' && !out.is_empty() && !out.ends_with(' ') {
+                        out.push(' ');
+                    }
+                    pending_space = false;
+                    out.push(ch);
+                    continue;
+                }
+            };
+            match breaks {
+                Breaks::Keep => out.push(ch),
+                Breaks::Space => pending_space = true,
+                Breaks::Remove => {}
+            }
+        }
+        out
+    }
+}
+
+/// Annotations recognizers emit for sounds that aren't speech.
+const NON_SPEECH_TAGS: &str = "music|blank_audio|laughs|applause|inaudible";
+/// A known tag in brackets, parentheses or asterisks ("[MUSIC]", "(laughs)"), and
+/// music notes with any lyrics between them. Other bracketed text is dictation.
+static NON_SPEECH: LazyLock<Regex> = LazyLock::new(|| {
+    let tag = format!(r"\s*(?:{NON_SPEECH_TAGS})\s*");
+    Regex::new(&format!(
+        r"(?i)\[{tag}\]|\({tag}\)|\*{tag}\*|[♪♫♬](?:[^♪♫♬]*[♪♫♬])?"
+    ))
+    .expect("valid non-speech pattern")
+});
+/// Space left before punctuation once an annotation between them is removed.
+static SPACE_BEFORE_PUNCTUATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s+([.,!?;:])").expect("valid punctuation pattern"));
+
+/// Removes non-speech annotations the recognizer sometimes emits, so "[MUSIC]" or
+/// "(laughs)" never get typed.
+pub fn strip_non_speech(text: &str) -> String {
+    if !NON_SPEECH.is_match(text) {
+        return text.to_string();
+    }
+    let stripped = NON_SPEECH.replace_all(text, " ");
+    let collapsed = stripped.split_whitespace().collect::<Vec<_>>().join(" ");
+    SPACE_BEFORE_PUNCTUATION
+        .replace_all(&collapsed, "$1")
+        .trim_start_matches(|c: char| ".,!?;:".contains(c) || c.is_whitespace())
+        .to_string()
+}
+
+/// Written ("jo@example.com") or spoken ("jo at example dot com") email addresses.
+const EMAIL_PATTERN: &str = r"(?i)\b[a-z0-9._%+-]+(?:@|\s+at\s+)[a-z0-9-]+(?:(?:\.|\s+dot\s+)[a-z0-9-]+)*(?:\.|\s+dot\s+)[a-z]{2,}\b";
+/// Runs of 13-19 digits, optionally grouped with spaces or dashes.
+const CARD_PATTERN: &str = r"\b\d(?:[ -]?\d){12,18}\b";
+
+/// Masks sensitive text (card numbers, email addresses, user patterns) before it
+/// is typed or recorded.
+#[derive(Debug, Clone, Default)]
+pub struct Redactor {
+    cards: Option<Regex>,
+    patterns: Vec<Regex>,
+    mask: String,
+}
+
+impl Redactor {
+    pub fn new(config: &RedactConfig) -> Result<Self> {
+        let mut patterns = Vec::new();
+        if config.emails {
+            patterns.push(Regex::new(EMAIL_PATTERN)?);
+        }
+        for pattern in &config.patterns {
+            let regex = Regex::new(pattern)
+                .with_context(|| format!("Invalid redact pattern '{pattern}'"))?;
+            patterns.push(regex);
+        }
+        let cards = if config.credit_cards {
+            Some(Regex::new(CARD_PATTERN)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            cards,
+            patterns,
+            mask: config.mask.clone(),
+        })
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if let Some(cards) = &self.cards {
+            text = cards
+                .replace_all(&text, |caps: &regex::Captures| {
+                    if luhn_valid(&caps[0]) {
+                        self.mask.clone()
+                    } else {
+                        caps[0].to_string()
+                    }
+                })
+                .into_owned();
+        }
+        for pattern in &self.patterns {
+            text = pattern.replace_all(&text, self.mask.as_str()).into_owned();
+        }
+        text
+    }
+}
+
+/// Luhn checksum over the digits of `number`, which all card numbers satisfy.
+fn luhn_valid(number: &str) -> bool {
+    let sum: u32 = number
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .rev()
+        .enumerate()
+        .map(|(i, digit)| match i % 2 {
+            0 => digit,
+            _ if digit * 2 > 9 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Spoken emoji phrases, as normalized words, and their replacements.
+const EMOJI: &[(&[&str], &str)] = &[
+    (&["thumbs", "up", "emoji"], "\u{1F44D}"),
+    (&["thumbs", "down", "emoji"], "\u{1F44E}"),
+    (&["smiley", "face"], "\u{1F642}"),
+    (&["smiley", "emoji"], "\u{1F642}"),
+    (&["winking", "face"], "\u{1F609}"),
+    (&["sad", "face"], "\u{1F641}"),
+    (&["laughing", "emoji"], "\u{1F602}"),
+    (&["heart", "emoji"], "\u{2764}\u{FE0F}"),
+    (&["fire", "emoji"], "\u{1F525}"),
+    (&["party", "emoji"], "\u{1F389}"),
+    (&["rocket", "emoji"], "\u{1F680}"),
+    (&["thinking", "emoji"], "\u{1F914}"),
+    (&["eyes", "emoji"], "\u{1F440}"),
+    (&["clapping", "emoji"], "\u{1F44F}"),
+    (&["check", "mark", "emoji"], "\u{2705}"),
+];
+
+/// Words filtered by `censor`, in normalized form.
+const PROFANITY: &[&str] = &[
+    "fuck",
+    "fucking",
+    "fucked",
+    "fucker",
+    "motherfucker",
+    "shit",
+    "shitty",
+    "bullshit",
+    "bitch",
+    "bastard",
+    "asshole",
+    "damn",
+    "goddamn",
+    "dick",
+    "piss",
+    "pissed",
+    "crap",
+    "cunt",
+];
+
+/// Spoken prefixes that switch a single text-mode utterance into spelling mode,
+/// when voice commands are on.
+const SPELL_PREFIXES: &[&str] = &["spell", "spelling"];
+/// Spoken prefixes that switch a single text-mode utterance into numeric mode,
+/// when voice commands are on.
+const NUMERIC_PREFIXES: &[&str] = &["number", "numbers", "numeric"];
+
+/// Applies voice commands and the active dictation mode to a transcription, then
+/// masks anything the redactor matches.
+pub fn process(text: &str, mode: DictationMode, options: &Options) -> String {
+    options.redactor.apply(&transform(text, mode, options))
+}
+
+fn transform(text: &str, mode: DictationMode, options: &Options) -> String {
+    if mode == DictationMode::Phrases {
+        return match options.phrases.closest(text) {
+            Some(phrase) => phrase.to_string(),
+            None => {
+                log::info!(
+                    "Ignored '{}': no configured phrase is close",
+                    logging::redact(text)
+                );
+                String::new()
+            }
+        };
+    }
+    if options.voice_commands && mode == DictationMode::Text {
+        if let Some(rest) = strip_command(text, SPELL_PREFIXES) {
+            if is_spellable(rest) {
+                return spell(rest);
+            }
+        }
+        if let Some(rest) = strip_command(text, NUMERIC_PREFIXES) {
+            if is_numeric(rest) {
+                return numeric(rest);
+            }
+        }
+    }
+    match mode {
+        DictationMode::Text => plain_text(text, options),
+        DictationMode::Markdown => markdown(text, options),
+        DictationMode::Code => code(text, options),
+        DictationMode::Spelling => spell(text),
+        DictationMode::Numeric => numeric(text),
+        DictationMode::Phrases => unreachable!("handled above"),
+    }
+}
+
+/// Words that open a question, for choosing the closing mark in [`punctuate`].
+const QUESTION_WORDS: &[&str] = &[
+    "who", "what", "when", "where", "why", "how", "which", "is", "are", "am", "do", "does", "did",
+    "can", "could", "would", "will", "should", "shall", "have", "has",
+];
+
+/// Rule-based punctuation and casing for output without any: capitalizes the first
+/// word and "I", and ends the sentence with a period or question mark. Text that
+/// already has capitals or sentence punctuation is left as the model cased it.
+fn punctuate(text: &str) -> String {
+    let text = text.trim();
+    if text.is_empty()
+        || text.chars().any(char::is_uppercase)
+        || text.contains(['.', '!', '?', ',', ';', ':'])
+    {
+        return text.to_string();
+    }
+    let mut tokens: Vec<String> = text
+        .split_whitespace()
+        .map(|token| match token {
+            "i" | "i'm" | "i've" | "i'll" | "i'd" => capitalize(token),
+            _ => token.to_string(),
+        })
+        .collect();
+    let question = QUESTION_WORDS.contains(&tokens[0].as_str());
+    tokens[0] = capitalize(&tokens[0]);
+    let mut sentence = tokens.join(" ");
+    if sentence.ends_with(char::is_alphanumeric) {
+        sentence.push(if question { '?' } else { '.' });
+    }
+    sentence
+}
+
+/// Text-mode processing: punctuation, dictionary, case commands, emoji, and the
+/// profanity filter.
+fn plain_text(text: &str, options: &Options) -> String {
+    let text = if options.punctuate {
+        punctuate(text)
+    } else {
+        text.to_string()
+    };
+    let text = options.dictionary.apply(&text);
+    let text = if options.voice_commands {
+        apply_case_commands(&text)
+    } else {
+        text
+    };
+    let text = if options.emoji {
+        substitute_emoji(&text)
+    } else {
+        text
+    };
+    censor(&text, options.censor)
+}
+
+/// Leading phrases that start a Markdown block, as normalized words, and the marker they produce.
+const MARKDOWN_BLOCKS: &[(&[&str], &str)] = &[
+    (&["bullet", "point"], "- "),
+    (&["bullet"], "- "),
+    (&["numbered", "item"], "1. "),
+    (&["task"], "- [ ] "),
+    (&["quote"], "> "),
+];
+
+/// Markdown mode: "heading <1-6> ...", "bullet ...", "numbered item ...", "task ...",
+/// and "quote ..." become single-line blocks; "code block" and "horizontal rule" on
+/// their own insert a fence or rule. Anything else is processed as plain text.
+fn markdown(text: &str, options: &Options) -> String {
+    match words(text).join(" ").as_str() {
+        "code block" => return "```\n".into(),
+        "horizontal rule" => return "---\n".into(),
+        _ => {}
+    }
+    let heading = strip_command(text, &["heading", "header"]).and_then(|rest| {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let level: usize = number_word(&normalize_word(&rest[..end]))?.parse().ok()?;
+        (1..=6).contains(&level).then(|| (level, &rest[end..]))
+    });
+    if let Some((level, rest)) = heading {
+        let title = plain_text(rest.trim(), options);
+        let title = title.trim_end_matches('.');
+        return format!("{} {}\n", "#".repeat(level), capitalize(title));
+    }
+    for (phrase, marker) in MARKDOWN_BLOCKS {
+        if let Some(rest) = strip_phrase(text, phrase) {
+            return format!("{marker}{}\n", capitalize(&plain_text(rest, options)));
+        }
+    }
+    plain_text(text, options)
+}
+
+/// Masks or removes profanity. Removed words pass their trailing punctuation on to
+/// the previous word (replacing a comma there) so sentences stay terminated.
+fn censor(text: &str, mode: Censor) -> String {
+    if mode == Censor::Off {
+        return text.to_string();
+    }
+    let mut out: Vec<String> = Vec::new();
+    for token in text.split_whitespace() {
+        let (lead, word, trail) = split_punctuation(token);
+        if !PROFANITY.contains(&word.to_lowercase().as_str()) {
+            out.push(token.to_string());
+            continue;
+        }
+        match mode {
+            Censor::Mask => {
+                let mut chars = word.chars();
+                let first = chars.next().map(String::from).unwrap_or_default();
+                let stars = "*".repeat(chars.count());
+                out.push(format!("{lead}{first}{stars}{trail}"));
+            }
+            _ => {
+                let trail = trail.trim_start_matches(['\'', '"']);
+                if let Some(previous) = out.last_mut() {
+                    if !trail.is_empty() {
+                        previous.truncate(previous.trim_end_matches([',', ';', ':']).len());
+                    }
+                    previous.push_str(trail);
+                }
+            }
+        }
+    }
+    out.join(" ")
+}
+
+/// Replaces spoken emoji phrases, keeping punctuation around the phrase:
+/// "Great job, thumbs up emoji." -> "Great job, 👍."
+fn substitute_emoji(text: &str) -> String {
+    replace_phrases(text, |words| {
+        EMOJI
+            .iter()
+            .find(|(phrase, _)| starts_with_phrase(words, phrase))
+            .map(|(phrase, emoji)| (phrase.len(), *emoji))
+    })
+}
+
+/// Replaces word sequences chosen by `find`, which receives the normalized words from
+/// the current position onward and returns how many to replace and with what.
+/// Punctuation before the first and after the last replaced word is kept.
+fn replace_phrases<'a>(text: &str, find: impl Fn(&[String]) -> Option<(usize, &'a str)>) -> String {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let words: Vec<String> = tokens.iter().map(|token| normalize_word(token)).collect();
+    let mut out: Vec<String> = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        match find(&words[index..]) {
+            Some((len, replacement)) if len > 0 => {
+                let (lead, _, _) = split_punctuation(tokens[index]);
+                let (_, _, trail) = split_punctuation(tokens[index + len - 1]);
+                out.push(format!("{lead}{replacement}{trail}"));
+                index += len;
+            }
+            _ => {
+                out.push(tokens[index].to_string());
+                index += 1;
+            }
+        }
+    }
+    out.join(" ")
+}
+
+/// Recognizes utterances that are entirely a state-changing command ("numeric mode").
+pub fn voice_command(text: &str) -> Option<VoiceCommand> {
+    let phrase = words(text).join(" ");
+    match phrase.as_str() {
+        "numeric mode" | "number mode" | "numbers mode" => Some(VoiceCommand::ToggleNumeric),
+        "scratch that" => Some(VoiceCommand::UndoLast),
+        _ => None,
+    }
+}
+
+/// Splits a trailing command phrase ("..., send it.") off an utterance, returning
+/// the remaining text without the punctuation the model placed before the phrase.
+/// Callers only apply it with `voice_commands` on, since these phrases also end
+/// ordinary sentences.
+pub fn split_end_action(text: &str) -> (&str, Option<EndAction>) {
+    let tokens: Vec<(usize, &str)> = text
+        .split_whitespace()
+        .map(|token| (token.as_ptr() as usize - text.as_ptr() as usize, token))
+        .collect();
+    for (phrase, action) in END_ACTIONS {
+        let Some(start) = tokens.len().checked_sub(phrase.len()) else {
+            continue;
+        };
+        let matches = tokens[start..]
+            .iter()
+            .zip(phrase.iter())
+            .all(|((_, token), word)| normalize_word(token) == *word);
+        if matches {
+            let rest = text[..tokens[start].0]
+                .trim_end()
+                .trim_end_matches([',', ';', ':']);
+            return (rest, Some(*action));
+        }
+    }
+    (text, None)
+}
+
+/// Splits an utterance into normalized words, dropping punctuation-only tokens.
+fn words(text: &str) -> Vec<String> {
+    tokens(text)
+        .map(normalize_word)
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// Splits on whitespace and commas, which the model inserts between spoken items.
+fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+}
+
+/// Returns a symbol the model already rendered as a bare token, e.g. "-" or "/".
+fn bare_symbol(token: &str) -> Option<&str> {
+    let symbol = token.trim();
+    (symbol.len() == 1 && "-_./@+#:".contains(symbol)).then_some(symbol)
+}
+
+/// Lowercases a spoken word and strips surrounding punctuation added by the model.
+fn normalize_word(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+/// Returns the text following a leading command word, if the utterance starts with one.
+fn strip_command<'a>(text: &'a str, commands: &[&str]) -> Option<&'a str> {
+    let trimmed = text.trim_start();
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let first = normalize_word(&trimmed[..end]);
+    commands
+        .contains(&first.as_str())
+        .then(|| trimmed[end..].trim_start())
+}
+
+/// True when the normalized `words` begin with every word of `phrase`.
+fn starts_with_phrase(words: &[String], phrase: &[&str]) -> bool {
+    words.len() >= phrase.len() && words.iter().zip(phrase).all(|(word, p)| word == p)
+}
+
+/// Returns the text following a leading multi-word phrase, if the utterance starts with it.
+fn strip_phrase<'a>(text: &'a str, phrase: &[&str]) -> Option<&'a str> {
+    phrase
+        .iter()
+        .try_fold(text, |rest, word| strip_command(rest, &[word]))
+}
+
+/// Formatting applied to the words covered by a case-control command.
+#[derive(Debug, Clone, Copy)]
+enum CaseStyle {
+    Upper,
+    Lower,
+    Capitalized,
+    Camel,
+}
+
+/// Applies inline case-control commands:
+/// - "all caps X", "no caps X", "capitalize X" restyle the next word,
+/// - "camel case ..." and "title case ..." restyle the rest of the sentence,
+/// - "... next <N> words" after any of those limits it to N words,
+/// - "no space" joins the surrounding words.
+fn apply_case_commands(text: &str) -> String {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let words: Vec<String> = tokens.iter().map(|token| normalize_word(token)).collect();
+    let mut out: Vec<String> = Vec::with_capacity(tokens.len());
+    let mut glue_next = false;
+    let mut changed = false;
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let next = words.get(i + 1).map(String::as_str);
+        // (style, command length in words, whether the default span is the whole sentence)
+        let command = match (words[i].as_str(), next) {
+            ("all", Some("caps")) => Some((CaseStyle::Upper, 2, false)),
+            ("no", Some("caps")) => Some((CaseStyle::Lower, 2, false)),
+            ("capitalize", _) => Some((CaseStyle::Capitalized, 1, false)),
+            ("camel", Some("case")) => Some((CaseStyle::Camel, 2, true)),
+            ("title", Some("case")) => Some((CaseStyle::Capitalized, 2, true)),
+            ("no", Some("space")) => {
+                glue_next = true;
+                changed = true;
+                i += 2;
+                continue;
+            }
+            _ => None,
+        };
+        let Some((style, command_len, whole_sentence)) = command else {
+            push_token(&mut out, tokens[i].to_string(), &mut glue_next);
+            i += 1;
+            continue;
+        };
+
+        changed = true;
+        i += command_len;
+        let count = match spoken_word_count(&words[i..]) {
+            Some((count, len)) => {
+                i += len;
+                count
+            }
+            None if whole_sentence => sentence_len(&tokens[i..]),
+            None => 1,
+        };
+        let end = (i + count).min(tokens.len());
+        if end > i {
+            push_token(&mut out, restyle(&tokens[i..end], style), &mut glue_next);
+        }
+        i = end;
+    }
+
+    if changed {
+        out.join(" ")
+    } else {
+        text.to_string()
+    }
+}
+
+fn push_token(out: &mut Vec<String>, token: String, glue_next: &mut bool) {
+    match out.last_mut() {
+        Some(last) if *glue_next => {
+            // Commas around "no space" are the model's punctuation, not the user's.
+            last.truncate(last.trim_end_matches(',').len());
+            last.push_str(&token);
+        }
+        _ => out.push(token),
+    }
+    *glue_next = false;
+}
+
+/// Parses "next <N> words", returning N and the number of words consumed.
+fn spoken_word_count(words: &[String]) -> Option<(usize, usize)> {
+    let [next, count, unit, ..] = words else {
+        return None;
+    };
+    if next != "next" || !matches!(unit.as_str(), "word" | "words") {
+        return None;
+    }
+    let count = number_word(count).unwrap_or(count).parse().ok()?;
+    Some((count, 3))
+}
+
+/// Number of tokens up to and including the one that ends the sentence or clause.
+fn sentence_len(tokens: &[&str]) -> usize {
+    tokens
+        .iter()
+        .position(|token| token.ends_with(['.', '!', '?', ',', ';', ':']))
+        .map_or(tokens.len(), |index| index + 1)
+}
+
+/// Splits a token into leading punctuation, the word itself, and trailing punctuation.
+fn split_punctuation(token: &str) -> (&str, &str, &str) {
+    let start = token.find(char::is_alphanumeric).unwrap_or(token.len());
+    let end = token.rfind(char::is_alphanumeric).map_or(start, |index| {
+        index + token[index..].chars().next().map_or(1, char::len_utf8)
+    });
+    (&token[..start], &token[start..end], &token[end..])
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn restyle(tokens: &[&str], style: CaseStyle) -> String {
+    match style {
+        CaseStyle::Upper => tokens.join(" ").to_uppercase(),
+        CaseStyle::Lower => tokens.join(" ").to_lowercase(),
+        CaseStyle::Capitalized => tokens
+            .iter()
+            .map(|token| {
+                let (lead, word, trail) = split_punctuation(token);
+                format!("{lead}{}{trail}", capitalize(word))
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+        CaseStyle::Camel => {
+            let (lead, _, _) = split_punctuation(tokens[0]);
+            let (_, _, trail) = split_punctuation(tokens[tokens.len() - 1]);
+            let mut joined = String::from(lead);
+            for (index, token) in tokens.iter().enumerate() {
+                let word = split_punctuation(token).1.to_lowercase();
+                if index == 0 {
+                    joined.push_str(&word);
+                } else {
+                    joined.push_str(&capitalize(&word));
+                }
+            }
+            joined.push_str(trail);
+            joined
+        }
+    }
+}
+
+/// How an identifier command joins its words.
+#[derive(Debug, Clone, Copy)]
+enum IdentifierStyle {
+    Snake,
+    Constant,
+    Kebab,
+    Camel,
+    Pascal,
+}
+
+/// Identifier commands in code mode, as normalized words.
+const IDENTIFIER_COMMANDS: &[(&[&str], IdentifierStyle)] = &[
+    (&["snake", "case"], IdentifierStyle::Snake),
+    (&["constant", "case"], IdentifierStyle::Constant),
+    (&["screaming", "snake", "case"], IdentifierStyle::Constant),
+    (&["kebab", "case"], IdentifierStyle::Kebab),
+    (&["camel", "case"], IdentifierStyle::Camel),
+    (&["pascal", "case"], IdentifierStyle::Pascal),
+];
+
+/// Spoken operators and punctuation in code mode, with whether a space goes before
+/// and after the symbol. Longer phrases come before their prefixes.
+const CODE_SYMBOLS: &[(&[&str], &str, bool, bool)] = &[
+    (&["double", "equals"], "==", true, true),
+    (&["triple", "equals"], "===", true, true),
+    (&["not", "equals"], "!=", true, true),
+    (&["equals"], "=", true, true),
+    (&["plus", "equals"], "+=", true, true),
+    (&["minus", "equals"], "-=", true, true),
+    (&["plus"], "+", true, true),
+    (&["minus"], "-", true, true),
+    (&["times"], "*", true, true),
+    (&["divided", "by"], "/", true, true),
+    (&["greater", "than", "or", "equal"], ">=", true, true),
+    (&["greater", "than"], ">", true, true),
+    (&["less", "than", "or", "equal"], "<=", true, true),
+    (&["less", "than"], "<", true, true),
+    (&["logical", "and"], "&&", true, true),
+    (&["logical", "or"], "||", true, true),
+    (&["fat", "arrow"], "=>", true, true),
+    (&["arrow"], "->", true, true),
+    (&["open", "paren"], "(", false, false),
+    (&["close", "paren"], ")", false, true),
+    (&["open", "bracket"], "[", false, false),
+    (&["close", "bracket"], "]", false, true),
+    (&["open", "brace"], "{", true, true),
+    (&["close", "brace"], "}", true, true),
+    (&["double", "colon"], "::", false, false),
+    (&["colon"], ":", false, true),
+    (&["semicolon"], ";", false, true),
+    (&["comma"], ",", false, true),
+    (&["dot"], ".", false, false),
+    (&["bang"], "!", true, false),
+    (&["underscore"], "_", false, false),
+];
+
+/// Code mode: identifier commands ("snake case user id" -> "user_id") take the
+/// following words up to the next operator, or "next <N> words"; spoken operators
+/// become symbols; other words are typed lowercase without the model's punctuation.
+/// The dictionary and profanity filter then apply to the words left standing alone.
+fn code(text: &str, options: &Options) -> String {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|token| match normalize_word(token) {
+            word if word.is_empty() => token.to_string(),
+            word => word,
+        })
+        .collect();
+    // Each piece carries whether it wants a space before and after it.
+    let mut pieces: Vec<(String, bool, bool)> = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        if let Some((len, symbol, before, after)) = code_symbol(&words[i..]) {
+            pieces.push((symbol.to_string(), before, after));
+            i += len;
+            continue;
+        }
+        let command = IDENTIFIER_COMMANDS
+            .iter()
+            .find(|(phrase, _)| starts_with_phrase(&words[i..], phrase));
+        if let Some((phrase, style)) = command {
+            i += phrase.len();
+            let end = match spoken_word_count(&words[i..]) {
+                Some((count, len)) => {
+                    i += len;
+                    (i + count).min(words.len())
+                }
+                None => (i..words.len())
+                    .find(|&j| code_symbol(&words[j..]).is_some())
+                    .unwrap_or(words.len()),
+            };
+            if end > i {
+                pieces.push((identifier(&words[i..end], *style), true, true));
+            }
+            i = end;
+            continue;
+        }
+        let word = &words[i];
+        if word.chars().any(char::is_alphanumeric) {
+            let word = number_word(word).unwrap_or(word);
+            pieces.push((word.to_string(), true, true));
+        } else if word.chars().all(|c| "=+-*/<>!&|".contains(c)) {
+            // An operator the model already wrote as a symbol.
+            pieces.push((word.clone(), true, true));
+        }
+        i += 1;
+    }
+
+    let mut out = String::new();
+    let mut space_after = false;
+    for (piece, before, after) in pieces {
+        if space_after && before {
+            out.push(' ');
+        }
+        out.push_str(&piece);
+        space_after = after;
+    }
+    censor(&options.dictionary.apply(&out), options.censor)
+}
+
+/// Matches a spoken operator at the start of `words`: (words consumed, symbol, space before, space after).
+fn code_symbol(words: &[String]) -> Option<(usize, &'static str, bool, bool)> {
+    CODE_SYMBOLS
+        .iter()
+        .find(|(phrase, ..)| starts_with_phrase(words, phrase))
+        .map(|(phrase, symbol, before, after)| (phrase.len(), *symbol, *before, *after))
+}
+
+fn identifier(words: &[String], style: IdentifierStyle) -> String {
+    match style {
+        IdentifierStyle::Snake => words.join("_"),
+        IdentifierStyle::Constant => words.join("_").to_uppercase(),
+        IdentifierStyle::Kebab => words.join("-"),
+        IdentifierStyle::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| {
+                if index == 0 {
+                    word.clone()
+                } else {
+                    capitalize(word)
+                }
+            })
+            .collect(),
+        IdentifierStyle::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+    }
+}
+
+/// True when every word is a spelling word, digit, or single letter, so ordinary
+/// sentences that merely start with "spell" are left alone.
+fn is_spellable(text: &str) -> bool {
+    let words = words(&text.to_lowercase().replace("x-ray", "xray"));
+    !words.is_empty()
+        && words.iter().all(|word| {
+            spell_word(word).is_some()
+                || matches!(word.as_str(), "capital" | "uppercase" | "cap")
+                || word.chars().all(|c| c.is_ascii_digit())
+                || (word.len() == 1 && word.chars().all(|c| c.is_ascii_alphabetic()))
+        })
+}
+
+fn spell(text: &str) -> String {
+    let mut out = String::new();
+    let mut capitalize_next = false;
+    let normalized = text.to_lowercase().replace("x-ray", "xray");
+    for token in tokens(&normalized) {
+        let word = normalize_word(token);
+        if word.is_empty() {
+            out.push_str(bare_symbol(token).unwrap_or_default());
+            continue;
+        }
+        if matches!(word.as_str(), "capital" | "uppercase" | "cap") {
+            capitalize_next = true;
+            continue;
+        }
+        let piece = spell_word(&word).map(str::to_string).unwrap_or(word);
+        if capitalize_next {
+            out.push_str(&piece.to_uppercase());
+            capitalize_next = false;
+        } else {
+            out.push_str(&piece);
+        }
+    }
+    out
+}
+
+/// True when every word is a number word, digit string, or symbol name.
+fn is_numeric(text: &str) -> bool {
+    let words = words(text);
+    !words.is_empty()
+        && words.iter().all(|word| {
+            number_word(word).is_some()
+                || tens_word(word).is_some()
+                || symbol_word(word).is_some()
+                || word.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+}
+
+fn numeric(text: &str) -> String {
+    let mut out = String::new();
+    let mut tokens = tokens(text).peekable();
+    while let Some(token) = tokens.next() {
+        let word = normalize_word(token);
+        if word.is_empty() {
+            out.push_str(bare_symbol(token).unwrap_or_default());
+            continue;
+        }
+        if let Some(tens) = tens_word(&word) {
+            // "forty two" -> "42", "forty" -> "40".
+            let unit = tokens
+                .peek()
+                .and_then(|next| digit_word(&normalize_word(next)))
+                .filter(|unit| *unit != "0");
+            match unit {
+                Some(unit) => {
+                    out.push_str(tens);
+                    out.push_str(unit);
+                    tokens.next();
+                }
+                None => {
+                    out.push_str(tens);
+                    out.push('0');
+                }
+            }
+        } else if let Some(value) = number_word(&word).or_else(|| symbol_word(&word)) {
+            out.push_str(value);
+        } else if word.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            out.push_str(&word);
+        } else {
+            log::debug!("Numeric mode: ignoring word '{}'", logging::redact(&word));
+        }
+    }
+    out
+}
+
+/// Maps a single spoken spelling word to its character(s).
+fn spell_word(word: &str) -> Option<&'static str> {
+    nato_letter(word)
+        .or_else(|| digit_word(word))
+        .or_else(|| symbol_word(word))
+}
+
+fn nato_letter(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "alpha" | "alfa" => "a",
+        "bravo" => "b",
+        "charlie" => "c",
+        "delta" => "d",
+        "echo" => "e",
+        "foxtrot" => "f",
+        "golf" => "g",
+        "hotel" => "h",
+        "india" => "i",
+        "juliet" | "juliett" => "j",
+        "kilo" => "k",
+        "lima" => "l",
+        "mike" => "m",
+        "november" => "n",
+        "oscar" => "o",
+        "papa" => "p",
+        "quebec" => "q",
+        "romeo" => "r",
+        "sierra" => "s",
+        "tango" => "t",
+        "uniform" => "u",
+        "victor" => "v",
+        "whiskey" | "whisky" => "w",
+        "xray" => "x",
+        "yankee" => "y",
+        "zulu" => "z",
+        _ => return None,
+    })
+}
+
+fn digit_word(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "zero" => "0",
+        "one" => "1",
+        "two" => "2",
+        "three" => "3",
+        "four" => "4",
+        "five" => "5",
+        "six" => "6",
+        "seven" => "7",
+        "eight" => "8",
+        "nine" | "niner" => "9",
+        _ => return None,
+    })
+}
+
+/// Digits plus the teens and "oh", which only make sense when entering numbers.
+fn number_word(word: &str) -> Option<&'static str> {
+    if let Some(digit) = digit_word(word) {
+        return Some(digit);
+    }
+    Some(match word {
+        "oh" => "0",
+        "ten" => "10",
+        "eleven" => "11",
+        "twelve" => "12",
+        "thirteen" => "13",
+        "fourteen" => "14",
+        "fifteen" => "15",
+        "sixteen" => "16",
+        "seventeen" => "17",
+        "eighteen" => "18",
+        "nineteen" => "19",
+        _ => return None,
+    })
+}
+
+/// Leading digit of a tens word ("forty" -> "4").
+fn tens_word(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "twenty" => "2",
+        "thirty" => "3",
+        "forty" => "4",
+        "fifty" => "5",
+        "sixty" => "6",
+        "seventy" => "7",
+        "eighty" => "8",
+        "ninety" => "9",
+        _ => return None,
+    })
+}
+
+fn symbol_word(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "dash" | "hyphen" | "minus" => "-",
+        "underscore" => "_",
+        "dot" | "period" | "point" => ".",
+        "slash" => "/",
+        "at" => "@",
+        "plus" => "+",
+        "hash" | "pound" => "#",
+        "colon" => ":",
+        "space" => " ",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        split_end_action, strip_non_speech, voice_command, Breaks, Censor, ControlChars,
+        DictationMode, Dictionary, EndAction, Options, Phrases, Redactor, VoiceCommand,
+    };
+    use crate::config::RedactConfig;
+    use std::collections::BTreeMap;
+
+    fn process(text: &str, mode: DictationMode) -> String {
+        super::process(text, mode, &Options::default())
+    }
+
+    fn with_commands(text: &str, mode: DictationMode) -> String {
+        let options = Options {
+            voice_commands: true,
+            ..Options::default()
+        };
+        super::process(text, mode, &options)
+    }
+
+    #[test]
+    fn spells_nato_words_digits_and_symbols() {
+        assert_eq!(
+            process("Alpha bravo seven dash Charlie.", DictationMode::Spelling),
+            "ab7-c"
+        );
+        assert_eq!(
+            process("Capital x-ray, 4, niner.", DictationMode::Spelling),
+            "X49"
+        );
+    }
+
+    #[test]
+    fn spell_prefix_applies_to_one_utterance() {
+        assert_eq!(
+            with_commands("Spell tango echo sierra tango.", DictationMode::Text),
+            "test"
+        );
+        assert_eq!(
+            with_commands("Spelling is hard.", DictationMode::Text),
+            "Spelling is hard."
+        );
+        assert_eq!(
+            with_commands("Tango echo.", DictationMode::Text),
+            "Tango echo."
+        );
+    }
+
+    #[test]
+    fn spell_prefix_needs_voice_commands_in_text_mode() {
+        assert_eq!(
+            process("Spell tango echo.", DictationMode::Text),
+            "Spell tango echo."
+        );
+        assert_eq!(
+            with_commands("Spell alpha bravo.", DictationMode::Markdown),
+            "Spell alpha bravo."
+        );
+    }
+
+    #[test]
+    fn numeric_mode_emits_digits_and_symbols() {
+        assert_eq!(process("Four two dot one.", DictationMode::Numeric), "42.1");
+        assert_eq!(
+            process(
+                "Forty two, dash, nineteen, oh, seven.",
+                DictationMode::Numeric
+            ),
+            "42-1907"
+        );
+        assert_eq!(process("Ninety.", DictationMode::Numeric), "90");
+    }
+
+    #[test]
+    fn number_prefix_and_toggle_command() {
+        assert_eq!(
+            with_commands("Numbers five five five dash one two.", DictationMode::Text),
+            "555-12"
+        );
+        assert_eq!(
+            with_commands("Numbers are hard.", DictationMode::Text),
+            "Numbers are hard."
+        );
+        assert_eq!(
+            voice_command("Numeric mode."),
+            Some(VoiceCommand::ToggleNumeric)
+        );
+        assert_eq!(voice_command("Numeric mode is neat."), None);
+    }
+
+    #[test]
+    fn number_prefix_needs_voice_commands_in_text_mode() {
+        assert_eq!(process("Number one.", DictationMode::Text), "Number one.");
+        assert_eq!(
+            with_commands("Number one.", DictationMode::Markdown),
+            "Number one."
+        );
+    }
+
+    #[test]
+    fn applies_case_commands() {
+        assert_eq!(
+            with_commands("Please all caps urgent review this.", DictationMode::Text),
+            "Please URGENT review this."
+        );
+        assert_eq!(
+            with_commands(
+                "All caps next two words hello world again.",
+                DictationMode::Text
+            ),
+            "HELLO WORLD again."
+        );
+        assert_eq!(
+            with_commands(
+                "Camel case next three words get user name now.",
+                DictationMode::Text
+            ),
+            "getUserName now."
+        );
+        assert_eq!(
+            with_commands("Call camel case get user name.", DictationMode::Text),
+            "Call getUserName."
+        );
+        assert_eq!(
+            with_commands("Foo, no space, bar.", DictationMode::Text),
+            "Foobar."
+        );
+        assert_eq!(
+            with_commands("Title case the old man and the sea.", DictationMode::Text),
+            "The Old Man And The Sea."
+        );
+    }
+
+    #[test]
+    fn case_commands_are_off_by_default() {
+        assert_eq!(
+            process("All caps hello, no space needed.", DictationMode::Text),
+            "All caps hello, no space needed."
+        );
+    }
+
+    #[test]
+    fn substitutes_spoken_emoji_when_enabled() {
+        let options = Options {
+            emoji: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            super::process("Great job, thumbs up emoji.", DictationMode::Text, &options),
+            "Great job, \u{1F44D}."
+        );
+        assert_eq!(
+            super::process("See you soon smiley face", DictationMode::Text, &options),
+            "See you soon \u{1F642}"
+        );
+        assert_eq!(
+            process("Great job, thumbs up emoji.", DictationMode::Text),
+            "Great job, thumbs up emoji."
+        );
+    }
+
+    #[test]
+    fn censors_profanity() {
+        let mask = Options {
+            censor: Censor::Mask,
+            ..Options::default()
+        };
+        let remove = Options {
+            censor: Censor::Remove,
+            ..Options::default()
+        };
+        assert_eq!(
+            super::process("Well, shit. That failed.", DictationMode::Text, &mask),
+            "Well, s***. That failed."
+        );
+        assert_eq!(
+            super::process("This damn build is slow.", DictationMode::Text, &remove),
+            "This build is slow."
+        );
+        assert_eq!(
+            super::process("Oh well, shit.", DictationMode::Text, &remove),
+            "Oh well."
+        );
+        assert_eq!(
+            process("Shitake mushrooms.", DictationMode::Text),
+            "Shitake mushrooms."
+        );
+    }
+
+    #[test]
+    fn applies_dictionary_case_insensitively() {
+        let words = BTreeMap::from([
+            ("github".to_string(), "GitHub".to_string()),
+            ("kubernetes".to_string(), "Kubernetes".to_string()),
+            ("shaun o'neil".to_string(), "Shawn O'Neill".to_string()),
+        ]);
+        let options = Options {
+            dictionary: Dictionary::new(&words),
+            voice_commands: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            super::process(
+                "Github hosts our kubernetes charts, ask Shaun O'Neil.",
+                DictationMode::Text,
+                &options
+            ),
+            "GitHub hosts our Kubernetes charts, ask Shawn O'Neill."
+        );
+        assert_eq!(
+            super::process("All caps github.", DictationMode::Text, &options),
+            "GITHUB."
+        );
+    }
+
+    #[test]
+    fn snaps_to_configured_phrases() {
+        let options = Options {
+            phrases: Phrases::new(&["Lights on".into(), "Lights off".into(), "Next slide".into()]),
+            ..Options::default()
+        };
+        let process = |text| super::process(text, DictationMode::Phrases, &options);
+        assert_eq!(process("lights on."), "Lights on");
+        assert_eq!(process("Light off"), "Lights off");
+        assert_eq!(process("next slides"), "Next slide");
+        assert_eq!(process("spell alpha bravo"), "");
+        assert_eq!(process("turn the lights on please"), "");
+    }
+
+    #[test]
+    fn punctuates_lowercase_streams() {
+        let options = Options {
+            punctuate: true,
+            ..Options::default()
+        };
+        let text = |input| super::process(input, DictationMode::Text, &options);
+        assert_eq!(
+            text("i think we should ship it"),
+            "I think we should ship it."
+        );
+        assert_eq!(text("can you review this"), "Can you review this?");
+        assert_eq!(text("Already cased, thanks."), "Already cased, thanks.");
+    }
+
+    #[test]
+    fn strips_non_speech_annotations() {
+        assert_eq!(
+            strip_non_speech("[MUSIC] Hello (laughs) world [BLANK_AUDIO]."),
+            "Hello world."
+        );
+        assert_eq!(strip_non_speech("♪ la la la ♪"), "");
+        assert_eq!(
+            strip_non_speech("*Applause* Thanks, [ Music ] all."),
+            "Thanks, all."
+        );
+    }
+
+    #[test]
+    fn keeps_dictated_brackets() {
+        for text in [
+            "Call me (at noon tomorrow if possible)",
+            "Set items [0] to the flag (optional).",
+            "As noted (see above), this is *important*.",
+            "Read chapter [2] (intro) first.",
+        ] {
+            assert_eq!(strip_non_speech(text), text);
+        }
+    }
+
+    #[test]
+    fn makes_text_terminal_safe() {
+        let terminal_safe = |text| ControlChars::TERMINAL.apply(text);
+        assert_eq!(terminal_safe("rm -rf build\nls"), "rm -rf build ls");
+        assert_eq!(terminal_safe("- one\n\n- two\n"), "- one - two");
+        assert_eq!(terminal_safe("a\tb \r\nc\x1b[2~"), "a b c[2~");
+        assert_eq!(terminal_safe("plain text"), "plain text");
+    }
+
+    #[test]
+    fn applies_newline_and_tab_policy() {
+        let keep = ControlChars::default();
+        assert_eq!(keep.apply("one\ntwo\tthree\x07"), "one\ntwo\tthree");
+        let single_line = ControlChars {
+            newlines: Breaks::Space,
+            tabs: Breaks::Remove,
+        };
+        assert_eq!(single_line.apply("One.\n\nTwo.\tThree."), "One. Two.Three.");
+    }
+
+    #[test]
+    fn redacts_cards_emails_and_custom_patterns() {
+        let config = RedactConfig {
+            credit_cards: true,
+            emails: true,
+            patterns: vec![r"ACME-\d{4}".into()],
+            ..RedactConfig::default()
+        };
+        let options = Options {
+            redactor: Redactor::new(&config).unwrap(),
+            ..Options::default()
+        };
+        assert_eq!(
+            super::process(
+                "Card 4111 1111 1111 1111, order 1234 5678 9012 3456.",
+                DictationMode::Text,
+                &options
+            ),
+            "Card [redacted], order 1234 5678 9012 3456."
+        );
+        assert_eq!(
+            super::process(
+                "Mail jo@example.com or jo at example dot org about ACME-1234.",
+                DictationMode::Text,
+                &options
+            ),
+            "Mail [redacted] or [redacted] about [redacted]."
+        );
+        assert!(Redactor::new(&RedactConfig {
+            patterns: vec!["(".into()],
+            ..RedactConfig::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn markdown_mode_produces_blocks() {
+        assert_eq!(
+            process("Heading two, project status.", DictationMode::Markdown),
+            "## Project status\n"
+        );
+        assert_eq!(
+            process("Bullet point ship the beta.", DictationMode::Markdown),
+            "- Ship the beta.\n"
+        );
+        assert_eq!(
+            process("Task, call the vendor.", DictationMode::Markdown),
+            "- [ ] Call the vendor.\n"
+        );
+        assert_eq!(process("Code block.", DictationMode::Markdown), "```\n");
+        assert_eq!(
+            process("Bullets are fine.", DictationMode::Markdown),
+            "Bullets are fine."
+        );
+    }
+
+    #[test]
+    fn code_mode_builds_identifiers_and_operators() {
+        assert_eq!(
+            process("Snake case user id.", DictationMode::Code),
+            "user_id"
+        );
+        assert_eq!(
+            process("Camel case get user name.", DictationMode::Code),
+            "getUserName"
+        );
+        assert_eq!(
+            process(
+                "Let snake case user id equals camel case get user name open paren close paren semicolon.",
+                DictationMode::Code
+            ),
+            "let user_id = getUserName();"
+        );
+        assert_eq!(
+            process("If x double equals five open brace", DictationMode::Code),
+            "if x == 5 {"
+        );
+        assert_eq!(
+            process(
+                "Constant case next two words max retries count",
+                DictationMode::Code
+            ),
+            "MAX_RETRIES count"
+        );
+    }
+
+    #[test]
+    fn code_mode_applies_dictionary_and_censor() {
+        let options = Options {
+            dictionary: Dictionary::new(&BTreeMap::from([(
+                "github".to_string(),
+                "GitHub".to_string(),
+            )])),
+            censor: Censor::Mask,
+            ..Options::default()
+        };
+        assert_eq!(
+            super::process(
+                "Snake case github token equals github damn",
+                DictationMode::Code,
+                &options
+            ),
+            "github_token = GitHub d***"
+        );
+    }
+
+    #[test]
+    fn splits_trailing_action_phrases() {
+        assert_eq!(
+            split_end_action("See you at noon, send it."),
+            ("See you at noon", Some(EndAction::Submit))
+        );
+        assert_eq!(
+            split_end_action("First point. New paragraph."),
+            ("First point.", Some(EndAction::NewParagraph))
+        );
+        assert_eq!(
+            split_end_action("This is wrong, scratch that."),
+            ("This is wrong", Some(EndAction::Discard))
+        );
+        assert_eq!(
+            split_end_action("I want to send it tomorrow."),
+            ("I want to send it tomorrow.", None)
+        );
+        assert_eq!(voice_command("Scratch that."), Some(VoiceCommand::UndoLast));
+    }
+}
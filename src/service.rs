@@ -0,0 +1,130 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::config;
+
+const SERVICE: &str = "whisp.service";
+/// How long the service must stay up after starting to count as working.
+const SETTLE_TIME: Duration = Duration::from_secs(3);
+
+fn unit_path() -> PathBuf {
+    config::default_config_path()
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new("/tmp"))
+        .join("systemd")
+        .join("user")
+        .join(SERVICE)
+}
+
+/// Quotes a path for an ExecStart line when it contains spaces.
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if path.contains(char::is_whitespace) {
+        format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        path.into_owned()
+    }
+}
+
+/// The user unit, matching `systemd/user/whisp.service` but with absolute paths.
+fn render_unit(exe: &Path, config_path: &Path) -> String {
+    format!(
+        "[Unit]
+Description=whisp push-to-talk speech-to-text
+Wants=graphical-session.target
+After=graphical-session.target
+PartOf=graphical-session.target
+
+[Service]
+Type=simple
+ExecStart={} --config {}
+Restart=on-failure
+RestartSec=2
+Environment=RUST_LOG=info
+NoNewPrivileges=yes
+
+[Install]
+WantedBy=graphical-session.target
+",
+        quote(exe),
+        quote(config_path)
+    )
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .context("running systemctl (is systemd available?)")?;
+    if !status.success() {
+        bail!("systemctl --user {} failed ({status})", args.join(" "));
+    }
+    Ok(())
+}
+
+/// Writes a user unit that runs this binary with the given config, enables and
+/// (re)starts it, and checks that it stays running. Returns the unit path.
+pub fn install(config_path: Option<&Path>, force: bool) -> Result<PathBuf> {
+    // Validates the config (creating the default one if needed) so the service
+    // doesn't start straight into a restart loop.
+    let loaded = config::load_config(config_path)?;
+    let exe = std::env::current_exe().context("locating the whisp binary")?;
+    let config_path = loaded
+        .path
+        .canonicalize()
+        .with_context(|| format!("resolving {}", loaded.path.display()))?;
+    let unit = render_unit(&exe, &config_path);
+
+    let path = unit_path();
+    match fs::read_to_string(&path) {
+        Ok(existing) if existing == unit => {}
+        Ok(_) if !force => bail!(
+            "{} already exists with different contents. Re-run with --force to replace it.",
+            path.display()
+        ),
+        _ => {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+            fs::write(&path, unit).with_context(|| format!("writing {}", path.display()))?;
+            println!("Wrote {}", path.display());
+        }
+    }
+
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", SERVICE])?;
+    systemctl(&["restart", SERVICE])?;
+    println!("Started {SERVICE}; checking that it stays up...");
+    thread::sleep(SETTLE_TIME);
+    if systemctl(&["is-active", "--quiet", SERVICE]).is_err() {
+        bail!(
+            "{SERVICE} did not stay running. See `journalctl --user -u {SERVICE} -n 50` for why."
+        );
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_unit;
+    use std::path::Path;
+
+    #[test]
+    fn unit_uses_absolute_quoted_paths() {
+        let unit = render_unit(
+            Path::new("/opt/whisp/bin/whisp"),
+            Path::new("/home/me/My Config/whisp.toml"),
+        );
+        assert!(unit.contains(
+            "ExecStart=/opt/whisp/bin/whisp --config \"/home/me/My Config/whisp.toml\"\n"
+        ));
+        assert!(unit.contains("Restart=on-failure\n"));
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::{Duration, Instant};
+
+use crate::hotkey::RecordingMode;
+
+/// Timing settings of the record hotkey.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Presses this soon after a recording stopped are ignored as key bounce.
+    pub debounce: Duration,
+    pub mode: RecordingMode,
+    /// With `RecordingMode::TapToggle`, releases sooner than this latch the recording.
+    pub hold_threshold: Duration,
+    /// A recording stopped this recently is continued by the next press.
+    pub merge_window: Duration,
+}
+
+/// What the main loop should do after a record hotkey event.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Transition {
+    /// Start capturing; `continuing` when it extends a held recording.
+    Start { continuing: bool },
+    /// Stop capturing and hand the audio to [`Session::stop`].
+    Stop,
+    /// A tap started the recording; it runs until the next press.
+    Latch,
+    /// Nothing to do.
+    Ignore,
+}
+
+/// What became of a stopped recording.
+#[derive(Debug, PartialEq)]
+pub enum Stopped {
+    /// Ready to transcribe.
+    Queue(Vec<f32>),
+    /// Held for `merge_window` in case the hotkey is pressed again.
+    Held,
+    /// Nothing was captured.
+    Empty,
+}
+
+/// The record hotkey's press/release/debounce/toggle/merge logic, kept apart from
+/// audio capture and threads so each trigger mode can be tested on its own.
+pub struct Session {
+    options: Options,
+    recording: bool,
+    record_start: Instant,
+    /// Set when a tap started the current recording; the next press ends it.
+    latched: bool,
+    last_stop: Instant,
+    /// A released recording waiting out `merge_window`.
+    held: Option<Vec<f32>>,
+}
+
+impl Session {
+    pub fn new(options: Options, now: Instant) -> Self {
+        Self {
+            options,
+            recording: false,
+            record_start: now,
+            latched: false,
+            last_stop: now - options.debounce,
+            held: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// How long the current (or last) recording has run.
+    pub fn duration(&self, now: Instant) -> Duration {
+        now - self.record_start
+    }
+
+    /// The record hotkey went down. Starting is refused while `paused`.
+    pub fn press(&mut self, now: Instant, paused: bool) -> Transition {
+        if self.recording && self.options.mode == RecordingMode::Toggle {
+            return Transition::Stop;
+        }
+        if self.recording {
+            // Ends a recording a tap started; its release then finds nothing to stop.
+            return if std::mem::take(&mut self.latched) {
+                Transition::Stop
+            } else {
+                Transition::Ignore
+            };
+        }
+        // Continuing a held recording isn't a bounce, however soon it comes.
+        if self.held.is_none() && now - self.last_stop < self.options.debounce {
+            return Transition::Ignore;
+        }
+        if paused {
+            return Transition::Ignore;
+        }
+        self.recording = true;
+        self.record_start = now;
+        Transition::Start {
+            continuing: self.held.is_some(),
+        }
+    }
+
+    /// The record hotkey came up.
+    pub fn release(&mut self, now: Instant) -> Transition {
+        if !self.recording || self.latched || self.options.mode == RecordingMode::Toggle {
+            return Transition::Ignore;
+        }
+        if self.options.mode == RecordingMode::TapToggle
+            && now - self.record_start < self.options.hold_threshold
+        {
+            self.latched = true;
+            return Transition::Latch;
+        }
+        Transition::Stop
+    }
+
+    /// Takes the audio of a recording that just stopped, prefixed by any held one.
+    pub fn stop(&mut self, mut audio: Vec<f32>, now: Instant) -> Stopped {
+        self.recording = false;
+        self.latched = false;
+        self.last_stop = now;
+        if let Some(mut previous) = self.held.take() {
+            previous.append(&mut audio);
+            audio = previous;
+        }
+        if audio.is_empty() {
+            Stopped::Empty
+        } else if self.options.merge_window.is_zero() {
+            Stopped::Queue(audio)
+        } else {
+            self.held = Some(audio);
+            Stopped::Held
+        }
+    }
+
+    /// The held recording, once its merge window has passed.
+    pub fn take_expired(&mut self, now: Instant) -> Option<Vec<f32>> {
+        if now - self.last_stop >= self.options.merge_window {
+            self.held.take()
+        } else {
+            None
+        }
+    }
+
+    /// The held recording regardless of the merge window, for shutdown.
+    pub fn take_held(&mut self) -> Option<Vec<f32>> {
+        self.held.take()
+    }
+
+    /// How long to wait for the next event before checking the merge window again.
+    pub fn timeout(&self, now: Instant, max: Duration) -> Duration {
+        if self.held.is_some() {
+            max.min(
+                self.options
+                    .merge_window
+                    .saturating_sub(now - self.last_stop),
+            )
+        } else {
+            max
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Options, Session, Stopped, Transition};
+    use crate::hotkey::RecordingMode;
+    use std::time::{Duration, Instant};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn session(mode: RecordingMode, merge_window: Duration) -> (Session, Instant) {
+        let now = Instant::now();
+        let options = Options {
+            debounce: ms(100),
+            mode,
+            hold_threshold: ms(300),
+            merge_window,
+        };
+        (Session::new(options, now), now)
+    }
+
+    #[test]
+    fn push_to_talk_records_while_held() {
+        let (mut session, t) = session(RecordingMode::Hold, Duration::ZERO);
+        assert_eq!(
+            session.press(t, false),
+            Transition::Start { continuing: false }
+        );
+        assert_eq!(session.press(t + ms(50), false), Transition::Ignore);
+        assert_eq!(session.release(t + ms(100)), Transition::Stop);
+        assert_eq!(
+            session.stop(vec![0.1], t + ms(100)),
+            Stopped::Queue(vec![0.1])
+        );
+        assert!(!session.is_recording());
+        assert_eq!(session.release(t + ms(150)), Transition::Ignore);
+        assert_eq!(session.stop(Vec::new(), t + ms(150)), Stopped::Empty);
+    }
+
+    #[test]
+    fn ignores_bounces_and_paused_presses() {
+        let (mut session, t) = session(RecordingMode::Hold, Duration::ZERO);
+        session.press(t, false);
+        session.release(t + ms(500));
+        session.stop(vec![0.1], t + ms(500));
+        assert_eq!(session.press(t + ms(550), false), Transition::Ignore);
+        assert_eq!(session.press(t + ms(650), true), Transition::Ignore);
+        assert_eq!(
+            session.press(t + ms(650), false),
+            Transition::Start { continuing: false }
+        );
+    }
+
+    #[test]
+    fn tap_latches_until_next_press() {
+        let (mut session, t) = session(RecordingMode::TapToggle, Duration::ZERO);
+        session.press(t, false);
+        assert_eq!(session.release(t + ms(100)), Transition::Latch);
+        assert!(session.is_recording());
+        assert_eq!(session.press(t + ms(2000), false), Transition::Stop);
+        session.stop(vec![0.1], t + ms(2000));
+        // The release of the stopping tap does nothing.
+        assert_eq!(session.release(t + ms(2050)), Transition::Ignore);
+
+        // Holding past the threshold still works as push-to-talk.
+        session.press(t + ms(3000), false);
+        assert_eq!(session.release(t + ms(3500)), Transition::Stop);
+    }
+
+    #[test]
+    fn toggle_starts_and_stops_on_presses() {
+        let (mut session, t) = session(RecordingMode::Toggle, Duration::ZERO);
+        assert_eq!(
+            session.press(t, false),
+            Transition::Start { continuing: false }
+        );
+        assert_eq!(session.release(t + ms(50)), Transition::Ignore);
+        assert_eq!(session.release(t + ms(5000)), Transition::Ignore);
+        assert!(session.is_recording());
+        assert_eq!(session.press(t + ms(6000), false), Transition::Stop);
+        session.stop(vec![0.1], t + ms(6000));
+        assert_eq!(session.release(t + ms(6050)), Transition::Ignore);
+        assert_eq!(session.press(t + ms(6050), false), Transition::Ignore);
+    }
+
+    #[test]
+    fn merges_recordings_within_the_window() {
+        let (mut session, t) = session(RecordingMode::Hold, ms(1000));
+        session.press(t, false);
+        session.release(t + ms(500));
+        assert_eq!(session.stop(vec![0.1], t + ms(500)), Stopped::Held);
+        assert_eq!(session.timeout(t + ms(700), ms(100)), ms(100));
+        assert_eq!(session.timeout(t + ms(1450), ms(100)), ms(50));
+        assert_eq!(session.take_expired(t + ms(700)), None);
+
+        // Pressing again inside the window continues, even within the debounce.
+        assert_eq!(
+            session.press(t + ms(550), false),
+            Transition::Start { continuing: true }
+        );
+        session.release(t + ms(1000));
+        assert_eq!(session.stop(vec![0.2], t + ms(1000)), Stopped::Held);
+        assert_eq!(session.take_expired(t + ms(1999)), None);
+        assert_eq!(session.take_expired(t + ms(2000)), Some(vec![0.1, 0.2]));
+        assert_eq!(session.take_held(), None);
+    }
+}
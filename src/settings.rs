@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Context, Result};
+use eframe::egui;
+use std::path::{Path, PathBuf};
+
+use crate::audio;
+use crate::config::{self, Config};
+use crate::hotkey;
+use crate::postprocess::{Censor, DictationMode};
+
+/// Top-level config keys the window edits; everything else in the file is left alone.
+const EDITABLE_KEYS: &[&str] = &[
+    "hotkey",
+    "audio_device",
+    "model",
+    "dictation_mode",
+    "voice_commands",
+    "smart_join",
+    "emoji",
+    "unicode_input",
+    "censor",
+    "output_template",
+    "terminal_safe",
+    "type_chunk_size",
+    "type_chunk_delay_ms",
+    "debounce_ms",
+];
+
+/// Opens the `whisp --settings` window for the config at `path_override` (or the default path).
+pub fn run(path_override: Option<&Path>) -> Result<()> {
+    let loaded = config::load_config(path_override)?;
+    let app = SettingsApp {
+        path: loaded.path,
+        config: loaded.config,
+        devices: audio::list_input_sources().unwrap_or_default(),
+        status: None,
+    };
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([480.0, 520.0]),
+        ..Default::default()
+    };
+    eframe::run_native("whisp settings", options, Box::new(|_| Ok(Box::new(app))))
+        .map_err(|err| anyhow!("settings window failed: {err}"))
+}
+
+struct SettingsApp {
+    path: PathBuf,
+    config: Config,
+    devices: Vec<audio::InputSource>,
+    /// Result of the last save: Ok(message) or Err(validation error).
+    status: Option<Result<String, String>>,
+}
+
+impl SettingsApp {
+    fn write_config(&self) -> Result<()> {
+        self.config.validate()?;
+        let toml::Value::Table(values) =
+            toml::Value::try_from(&self.config).context("serializing settings")?
+        else {
+            return Err(anyhow!("config did not serialize to a table"));
+        };
+        let settings: Vec<(&str, toml::Value)> = EDITABLE_KEYS
+            .iter()
+            .filter_map(|key| values.get(*key).map(|value| (*key, value.clone())))
+            .collect();
+        config::update_config(&self.path, &settings)
+    }
+
+    fn device_label(&self) -> String {
+        if self.config.audio_device.is_empty() {
+            return "System default".into();
+        }
+        self.devices
+            .iter()
+            .find(|source| source.name == self.config.audio_device)
+            .map_or_else(
+                || self.config.audio_device.clone(),
+                |source| source.description.clone(),
+            )
+    }
+}
+
+impl eframe::App for SettingsApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label(format!("Editing {}", self.path.display()));
+            ui.separator();
+
+            egui::Grid::new("settings")
+                .num_columns(2)
+                .spacing([12.0, 8.0])
+                .show(ui, |ui| {
+                    ui.label("Hotkey");
+                    ui.text_edit_singleline(&mut self.config.hotkey);
+                    ui.end_row();
+                    if hotkey::parse_hotkey(&self.config.hotkey).is_err() {
+                        ui.label("");
+                        ui.colored_label(egui::Color32::RED, "Unknown key name");
+                        ui.end_row();
+                    }
+
+                    ui.label("Microphone");
+                    let label = self.device_label();
+                    egui::ComboBox::from_id_salt("audio_device")
+                        .selected_text(label)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.config.audio_device,
+                                String::new(),
+                                "System default",
+                            );
+                            for source in &self.devices {
+                                ui.selectable_value(
+                                    &mut self.config.audio_device,
+                                    source.name.clone(),
+                                    &source.description,
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Model");
+                    egui::ComboBox::from_id_salt("model")
+                        .selected_text(self.config.model.clone())
+                        .show_ui(ui, |ui| {
+                            for preset in config::available_presets() {
+                                ui.selectable_value(
+                                    &mut self.config.model,
+                                    preset.to_string(),
+                                    *preset,
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Dictation mode");
+                    egui::ComboBox::from_id_salt("dictation_mode")
+                        .selected_text(format!("{:?}", self.config.dictation_mode))
+                        .show_ui(ui, |ui| {
+                            for mode in [
+                                DictationMode::Text,
+                                DictationMode::Spelling,
+                                DictationMode::Numeric,
+                                DictationMode::Markdown,
+                                DictationMode::Code,
+                                DictationMode::Phrases,
+                            ] {
+                                ui.selectable_value(
+                                    &mut self.config.dictation_mode,
+                                    mode,
+                                    format!("{mode:?}"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Profanity");
+                    egui::ComboBox::from_id_salt("censor")
+                        .selected_text(format!("{:?}", self.config.censor))
+                        .show_ui(ui, |ui| {
+                            for censor in [Censor::Off, Censor::Mask, Censor::Remove] {
+                                ui.selectable_value(
+                                    &mut self.config.censor,
+                                    censor,
+                                    format!("{censor:?}"),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("Output template");
+                    ui.text_edit_singleline(&mut self.config.output_template);
+                    ui.end_row();
+
+                    ui.label("Typing chunk size");
+                    ui.add(
+                        egui::DragValue::new(&mut self.config.type_chunk_size).range(0..=10_000),
+                    );
+                    ui.end_row();
+
+                    ui.label("Chunk delay (ms)");
+                    ui.add(
+                        egui::DragValue::new(&mut self.config.type_chunk_delay_ms).range(0..=1000),
+                    );
+                    ui.end_row();
+
+                    ui.label("Debounce (ms)");
+                    ui.add(egui::DragValue::new(&mut self.config.debounce_ms).range(0..=5000));
+                    ui.end_row();
+                });
+
+            ui.separator();
+            ui.checkbox(&mut self.config.voice_commands, "Voice commands");
+            ui.checkbox(&mut self.config.smart_join, "Smart join");
+            ui.checkbox(&mut self.config.emoji, "Spoken emoji");
+            ui.checkbox(
+                &mut self.config.unicode_input,
+                "Unicode input (Ctrl+Shift+U)",
+            );
+            ui.checkbox(
+                &mut self.config.terminal_safe,
+                "Terminal-safe (no line breaks or control characters)",
+            );
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.status = Some(match self.write_config() {
+                        Ok(()) => Ok("Saved. Restart whisp to apply.".into()),
+                        Err(err) => Err(format!("{err:#}")),
+                    });
+                }
+                match &self.status {
+                    Some(Ok(message)) => {
+                        ui.label(message);
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    None => {}
+                }
+            });
+        });
+    }
+}
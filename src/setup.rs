@@ -0,0 +1,237 @@
+use anyhow::{bail, Context, Result};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::audio;
+use crate::config;
+use crate::hotkey;
+use crate::util;
+
+const KEY_DETECT_TIMEOUT: Duration = Duration::from_secs(10);
+const LEVEL_PREVIEW: Duration = Duration::from_secs(3);
+const LEVEL_METER_WIDTH: usize = 40;
+
+const DICTATION_MODES: &[(&str, &str)] = &[
+    ("text", "type what you say"),
+    ("spelling", "NATO alphabet, digits, and symbol names"),
+    ("numeric", "spoken numbers and symbols only"),
+    ("markdown", "text plus headings, bullets, and code fences"),
+    (
+        "code",
+        "identifiers and operators for editors and terminals",
+    ),
+];
+
+/// Interactive `whisp --setup`: asks for the hotkey, microphone, model, and dictation
+/// mode, then writes them into a fresh config. Returns the path written.
+pub fn run(path_override: Option<&Path>, force: bool) -> Result<PathBuf> {
+    let path = path_override
+        .map(PathBuf::from)
+        .unwrap_or_else(config::default_config_path);
+    if path.exists() && !force {
+        bail!(
+            "Config already exists at {}. Re-run with --setup --force to replace it.",
+            path.display()
+        );
+    }
+    println!("whisp setup: answers are saved to {}\n", path.display());
+
+    let hotkey = choose_hotkey()?;
+    let audio_device = choose_audio_device()?;
+    let model = choose_model()?;
+    let dictation_mode = choose_dictation_mode()?;
+
+    config::write_config(
+        Some(&path),
+        true,
+        &[
+            ("hotkey", hotkey.into()),
+            ("audio_device", audio_device.into()),
+            ("model", model.into()),
+            ("dictation_mode", dictation_mode.into()),
+        ],
+    )
+}
+
+fn choose_hotkey() -> Result<String> {
+    println!("1. Hotkey");
+    println!(
+        "   Press the key you want to hold while speaking (waiting {}s)...",
+        KEY_DETECT_TIMEOUT.as_secs()
+    );
+    loop {
+        let Some(key) = hotkey::wait_for_key_press(KEY_DETECT_TIMEOUT)? else {
+            let name = prompt("   No key detected. Type a key name", "insert")?;
+            hotkey::parse_hotkey(&name)?;
+            return Ok(hotkey::normalize_hotkey_name(&name));
+        };
+        let Some(name) = hotkey::config_name(key) else {
+            println!("   That key has no name whisp can bind. Press another key...");
+            continue;
+        };
+        if confirm(&format!("   Use '{name}'?"), true)? {
+            return Ok(name);
+        }
+        println!("   Press another key...");
+    }
+}
+
+fn choose_audio_device() -> Result<String> {
+    println!("\n2. Microphone");
+    if !util::has_command("pactl") {
+        println!("   pactl not found; using the system default input.");
+        return Ok(String::new());
+    }
+    let sources = audio::list_input_sources()?;
+    println!("   0) System default");
+    for (index, source) in sources.iter().enumerate() {
+        println!("   {}) {}", index + 1, source.description);
+    }
+    loop {
+        let answer = prompt("   Choose a microphone", "0")?;
+        let name = match answer.parse::<usize>() {
+            Ok(0) => String::new(),
+            Ok(choice) if choice <= sources.len() => sources[choice - 1].name.clone(),
+            _ => {
+                println!("   Enter a number between 0 and {}.", sources.len());
+                continue;
+            }
+        };
+        preview_level(&name)?;
+        if confirm("   Use this microphone?", true)? {
+            return Ok(name);
+        }
+    }
+}
+
+/// Shows a live input level meter so the user can confirm the microphone hears them.
+fn preview_level(device: &str) -> Result<()> {
+    let capture = audio::AudioCapture::new(device)?;
+    capture.start_recording();
+    println!("   Say something to check the level...");
+    let started = Instant::now();
+    while started.elapsed() < LEVEL_PREVIEW {
+        thread::sleep(Duration::from_millis(100));
+        let filled =
+            ((capture.level() * LEVEL_METER_WIDTH as f32).round() as usize).min(LEVEL_METER_WIDTH);
+        print!(
+            "\r   [{}{}]",
+            "#".repeat(filled),
+            " ".repeat(LEVEL_METER_WIDTH - filled)
+        );
+        io::stdout().flush()?;
+    }
+    capture.stop_recording();
+    println!();
+    Ok(())
+}
+
+fn choose_model() -> Result<String> {
+    println!("\n3. Model");
+    let presets = config::available_presets();
+    if let [only] = presets {
+        println!("   Using {only} (the only available preset).");
+        return Ok(only.to_string());
+    }
+    choose("   Choose a model", presets)
+}
+
+fn choose_dictation_mode() -> Result<String> {
+    println!("\n4. Dictation mode");
+    for (index, (name, description)) in DICTATION_MODES.iter().enumerate() {
+        println!("   {}) {name}: {description}", index + 1);
+    }
+    let names: Vec<&str> = DICTATION_MODES.iter().map(|(name, _)| *name).collect();
+    choose("   Choose a mode", &names)
+}
+
+/// Asks for a 1-based index into `options`, defaulting to the first.
+fn choose(question: &str, options: &[&str]) -> Result<String> {
+    loop {
+        let answer = prompt(question, "1")?;
+        match answer.parse::<usize>() {
+            Ok(choice) if (1..=options.len()).contains(&choice) => {
+                return Ok(options[choice - 1].to_string())
+            }
+            _ => println!("   Enter a number between 1 and {}.", options.len()),
+        }
+    }
+}
+
+fn prompt(question: &str, default: &str) -> Result<String> {
+    let answer = read_answer(&format!("{question} [{default}]: "))?;
+    Ok(if answer.is_empty() {
+        default.to_string()
+    } else {
+        answer
+    })
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let hint = if default { "Y/n" } else { "y/N" };
+    let answer = read_answer(&format!("{question} [{hint}]: "))?;
+    // The detected hotkey may also have echoed into the terminal, so only the
+    // last character typed counts.
+    Ok(
+        match answer.chars().last().map(|c| c.to_ascii_lowercase()) {
+            Some('y') => true,
+            Some('n') => false,
+            _ => default,
+        },
+    )
+}
+
+fn read_answer(question: &str) -> Result<String> {
+    print!("{question}");
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("reading answer from stdin")?;
+    Ok(strip_escape_sequences(&line).trim().to_string())
+}
+
+/// Drops terminal escape sequences and control characters, such as the "\x1b[2~"
+/// a terminal echoes when Insert is pressed during hotkey detection.
+fn strip_escape_sequences(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            match chars.next() {
+                // CSI: parameters up to a final byte in '@'..='~'.
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // SS3: one more byte (F1-F4 on many terminals).
+                Some('O') => {
+                    chars.next();
+                }
+                _ => {}
+            }
+        } else if !c.is_control() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_escape_sequences;
+
+    #[test]
+    fn strips_echoed_key_sequences() {
+        assert_eq!(strip_escape_sequences("\x1b[2~y\n"), "y");
+        assert_eq!(strip_escape_sequences("\x1bOPn"), "n");
+        assert_eq!(strip_escape_sequences("\x1b[15;2~2"), "2");
+        assert_eq!(strip_escape_sequences("plain"), "plain");
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// When a transcription is spoken back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadBack {
+    #[default]
+    Off,
+    /// Speak it, wait until done, then type it.
+    Before,
+    /// Type it, then speak it.
+    After,
+}
+
+/// Text-to-speech program used for read-back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TtsEngine {
+    #[default]
+    SpeechDispatcher,
+    /// Local neural voices; needs `piper_model`.
+    Piper,
+}
+
+/// A configured read-back voice.
+#[derive(Debug, Clone)]
+pub enum Voice {
+    SpeechDispatcher,
+    Piper { model: PathBuf },
+}
+
+/// Sample rate of most Piper voices, used when the model's .json can't be read.
+const PIPER_DEFAULT_RATE: u64 = 22_050;
+
+impl Voice {
+    /// Speaks `text` and returns once it has been said.
+    pub fn speak(&self, text: &str) -> Result<()> {
+        match self {
+            Voice::SpeechDispatcher => {
+                let status = Command::new("spd-say")
+                    .args(["--application-name", "whisp", "--wait", "--"])
+                    .arg(text)
+                    .status()
+                    .context("Failed to run spd-say (speech-dispatcher)")?;
+                if !status.success() {
+                    bail!("spd-say exited with {status}");
+                }
+                Ok(())
+            }
+            Voice::Piper { model } => speak_piper(model, text),
+        }
+    }
+}
+
+/// Pipes `text` through `piper` and plays the raw audio with `aplay`.
+fn speak_piper(model: &Path, text: &str) -> Result<()> {
+    let mut piper = Command::new("piper")
+        .arg("--model")
+        .arg(model)
+        .arg("--output_raw")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to run piper")?;
+    let audio = piper.stdout.take().expect("stdout is piped");
+    let mut player = Command::new("aplay")
+        .args(["-q", "-t", "raw", "-f", "S16_LE", "-c", "1"])
+        .arg(format!("-r{}", piper_sample_rate(model)))
+        .stdin(audio)
+        .spawn()
+        .context("Failed to run aplay")?;
+    {
+        let mut stdin = piper.stdin.take().expect("stdin is piped");
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = piper.wait()?;
+    player.wait()?;
+    if !status.success() {
+        bail!("piper exited with {status}");
+    }
+    Ok(())
+}
+
+/// Reads `audio.sample_rate` from the voice's `<model>.json`.
+fn piper_sample_rate(model: &Path) -> u64 {
+    let mut config = model.as_os_str().to_owned();
+    config.push(".json");
+    std::fs::read_to_string(config)
+        .ok()
+        .and_then(|text| serde_json::from_str::<serde_json::Value>(&text).ok())
+        .and_then(|json| json["audio"]["sample_rate"].as_u64())
+        .unwrap_or(PIPER_DEFAULT_RATE)
+}
+
+/// speech-dispatcher priority of a spoken message.
+#[derive(Debug, Clone, Copy)]
+pub enum Priority {
+    /// Short state changes; spoken right away, interrupting lower priorities.
+    Important,
+    /// Transcribed text.
+    Message,
+}
+
+/// Speaks `text` through speech-dispatcher (`spd-say`) without blocking, so screen
+/// reader users hear what whisp is doing alongside Orca.
+pub fn say(text: &str, priority: Priority) {
+    let priority = match priority {
+        Priority::Important => "important",
+        Priority::Message => "message",
+    };
+    let text = text.to_string();
+    thread::spawn(move || {
+        let result = Command::new("spd-say")
+            .args(["--application-name", "whisp", "--priority", priority, "--"])
+            .arg(&text)
+            .status();
+        if let Err(err) = result {
+            log::warn!("spd-say failed: {err}");
+        }
+    });
+}
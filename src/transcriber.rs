@@ -1,47 +1,636 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::sync::mpsc;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use sherpa_rs::moonshine::{MoonshineConfig, MoonshineRecognizer};
 use sherpa_rs::transducer::{TransducerConfig, TransducerRecognizer};
+use sherpa_rs::whisper::{WhisperConfig, WhisperRecognizer};
 
-const MAX_QUEUE: usize = 20;
+use crate::audio::SAMPLE_RATE;
+use crate::config::ModelPaths;
+use crate::eval::Score;
+use crate::logging;
+use crate::media;
+use crate::metrics::{SessionStats, UtteranceMetrics};
+use crate::output::OutputCommand;
+use crate::util;
+
+/// Recordings that may wait for transcription; also the capacity of the audio channel.
+pub const MAX_QUEUE: usize = 20;
+/// Panics the worker recovers from before giving up on transcription.
+const MAX_RESTARTS: usize = 3;
+
+/// onnxruntime execution provider used for inference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Cpu,
+    /// NVIDIA GPU; requires a build with `--features cuda`.
+    Cuda,
+    /// The best provider this build and machine support, chosen at startup.
+    Auto,
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Provider::Cpu => "cpu",
+            Provider::Cuda => "cuda",
+            Provider::Auto => "auto",
+        })
+    }
+}
+
+impl Provider {
+    /// Picks the provider for `Auto` and says why; other providers are kept as set.
+    pub fn resolve(self) -> (Provider, &'static str) {
+        match self {
+            Provider::Auto if !cfg!(feature = "cuda") => {
+                (Provider::Cpu, "this build has no GPU support")
+            }
+            Provider::Auto if !cuda_available() => (Provider::Cpu, "no NVIDIA driver found"),
+            Provider::Auto => (Provider::Cuda, "NVIDIA driver found"),
+            provider => (provider, "set in the config"),
+        }
+    }
+}
+
+/// Speech recognition engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// sherpa-onnx with the configured `model` preset.
+    #[default]
+    Sherpa,
+    /// libvosk with a local model from `vosk_model`; requires a build with `--features vosk`.
+    Vosk,
+    /// A hosted speech-to-text API configured under `[cloud]`. Recordings leave the machine.
+    Cloud,
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Backend::Sherpa => "sherpa",
+            Backend::Vosk => "vosk",
+            Backend::Cloud => "cloud",
+        })
+    }
+}
+
+/// Order in which queued recordings are transcribed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueuePolicy {
+    /// Oldest recording first, so text is typed in the order it was spoken.
+    #[default]
+    OldestFirst,
+    /// Most recent recording first; older ones follow once it is typed.
+    NewestFirst,
+    /// Only the most recent recording; older queued ones are dropped.
+    LatestOnly,
+}
+
+impl QueuePolicy {
+    /// Takes the next job to transcribe, dropping any this policy discards.
+    fn next(self, queue: &mut VecDeque<AudioJob>) -> Option<AudioJob> {
+        match self {
+            QueuePolicy::OldestFirst => queue.pop_front(),
+            QueuePolicy::NewestFirst => queue.pop_back(),
+            QueuePolicy::LatestOnly => {
+                let job = queue.pop_back();
+                if !queue.is_empty() {
+                    log::info!("Dropping {} older queued recording(s)", queue.len());
+                    queue.clear();
+                }
+                job
+            }
+        }
+    }
+}
+
+/// How the transcription worker loads the model and schedules recordings.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    pub provider: Provider,
+    /// Run one throwaway inference before the first recording.
+    pub warmup: bool,
+    pub queue_policy: QueuePolicy,
+    /// Abandon a transcription that runs longer than this and reload the model.
+    pub timeout: Option<Duration>,
+    /// Emit each segment of a long Whisper recording as soon as it is decoded.
+    pub progressive: bool,
+}
+
+/// Length of the synthetic warmup clip.
+const WARMUP_SECS: f32 = 1.0;
+
+/// True when the NVIDIA kernel driver is loaded. Without it onnxruntime cannot use CUDA.
+fn cuda_available() -> bool {
+    Path::new("/proc/driver/nvidia/version").exists() && Path::new("/dev/nvidiactl").exists()
+}
+
+/// A captured recording waiting to be transcribed.
+pub struct AudioJob {
+    pub audio: Vec<f32>,
+    pub queued_at: Instant,
+}
+
+impl AudioJob {
+    pub fn new(audio: Vec<f32>) -> Self {
+        Self {
+            audio,
+            queued_at: Instant::now(),
+        }
+    }
+}
+
+/// Transcribed text together with its timing metrics.
+pub struct Transcript {
+    pub text: String,
+    pub metrics: UtteranceMetrics,
+    /// Continues the previous transcript of the same recording, typed progressively.
+    pub continuation: bool,
+}
+
+/// Receives a finished segment of a recording that is still being transcribed.
+type EarlySegment = dyn FnMut(String) + Send;
+
+/// Whisper decodes at most this much audio per call, so longer recordings are
+/// split at pauses and transcribed a segment at a time.
+const WHISPER_WINDOW_SECS: usize = 30;
+
+enum Recognizer {
+    Transducer(TransducerRecognizer),
+    Whisper(WhisperRecognizer),
+    Moonshine(MoonshineRecognizer),
+    #[cfg(feature = "vosk")]
+    Vosk(crate::vosk::Recognizer),
+    Cloud(crate::cloud::Client),
+}
 
 struct Transcriber {
-    recognizer: TransducerRecognizer,
+    recognizer: Recognizer,
 }
 
 impl Transcriber {
-    fn new(paths: &crate::config::ModelPaths) -> Result<Self> {
-        let config = TransducerConfig {
-            encoder: paths.encoder.to_string_lossy().into_owned(),
-            decoder: paths.decoder.to_string_lossy().into_owned(),
-            joiner: paths.joiner.to_string_lossy().into_owned(),
-            tokens: paths.tokens.to_string_lossy().into_owned(),
-            sample_rate: 16000,
-            feature_dim: 80,
-            num_threads: 4,
-            decoding_method: "greedy_search".into(),
-            model_type: "nemo_transducer".into(),
-            ..Default::default()
+    /// Loads the model on `provider`, falling back to the CPU if the GPU runtime is
+    /// missing or the model fails to load there.
+    fn new(paths: &crate::config::ModelPaths, provider: Provider) -> Result<Self> {
+        let provider = if provider == Provider::Auto {
+            let (resolved, reason) = provider.resolve();
+            log::info!("provider = \"auto\": using {resolved} ({reason})");
+            resolved
+        } else {
+            provider
+        };
+        let provider = if provider == Provider::Cuda && !cuda_available() {
+            log::warn!(
+                "provider = \"cuda\" but no NVIDIA driver was found (/proc/driver/nvidia); using the CPU instead"
+            );
+            Provider::Cpu
+        } else {
+            provider
+        };
+        match Self::load(paths, provider) {
+            Err(err) if provider != Provider::Cpu => {
+                log::warn!("Failed to load the model with provider {provider}: {err:#}. Falling back to the CPU.");
+                Self::load(paths, Provider::Cpu)
+            }
+            result => result,
+        }
+    }
+
+    fn load(paths: &crate::config::ModelPaths, provider: Provider) -> Result<Self> {
+        let recognizer = match paths {
+            ModelPaths::Transducer {
+                encoder,
+                decoder,
+                joiner,
+                tokens,
+            } => {
+                let config = TransducerConfig {
+                    encoder: encoder.to_string_lossy().into_owned(),
+                    decoder: decoder.to_string_lossy().into_owned(),
+                    joiner: joiner.to_string_lossy().into_owned(),
+                    tokens: tokens.to_string_lossy().into_owned(),
+                    sample_rate: 16000,
+                    feature_dim: 80,
+                    num_threads: 4,
+                    decoding_method: "greedy_search".into(),
+                    model_type: "nemo_transducer".into(),
+                    provider: Some(provider.to_string()),
+                    ..Default::default()
+                };
+                log::info!("Loading sherpa transducer model (provider: {provider})");
+                Recognizer::Transducer(
+                    TransducerRecognizer::new(config)
+                        .map_err(|e| anyhow!("Failed to create sherpa recognizer: {e}"))?,
+                )
+            }
+            ModelPaths::Whisper {
+                encoder,
+                decoder,
+                tokens,
+                language,
+            } => {
+                let config = WhisperConfig {
+                    encoder: encoder.to_string_lossy().into_owned(),
+                    decoder: decoder.to_string_lossy().into_owned(),
+                    tokens: tokens.to_string_lossy().into_owned(),
+                    language: language.to_string(),
+                    num_threads: Some(4),
+                    provider: Some(provider.to_string()),
+                    ..Default::default()
+                };
+                log::info!("Loading sherpa whisper model (provider: {provider})");
+                Recognizer::Whisper(
+                    WhisperRecognizer::new(config)
+                        .map_err(|e| anyhow!("Failed to create sherpa recognizer: {e}"))?,
+                )
+            }
+            ModelPaths::Moonshine {
+                preprocessor,
+                encoder,
+                uncached_decoder,
+                cached_decoder,
+                tokens,
+            } => {
+                let config = MoonshineConfig {
+                    preprocessor: preprocessor.to_string_lossy().into_owned(),
+                    encoder: encoder.to_string_lossy().into_owned(),
+                    uncached_decoder: uncached_decoder.to_string_lossy().into_owned(),
+                    cached_decoder: cached_decoder.to_string_lossy().into_owned(),
+                    tokens: tokens.to_string_lossy().into_owned(),
+                    num_threads: Some(4),
+                    provider: Some(provider.to_string()),
+                    ..Default::default()
+                };
+                log::info!("Loading sherpa moonshine model (provider: {provider})");
+                Recognizer::Moonshine(
+                    MoonshineRecognizer::new(config)
+                        .map_err(|e| anyhow!("Failed to create sherpa recognizer: {e}"))?,
+                )
+            }
+            #[cfg(feature = "vosk")]
+            ModelPaths::Vosk { dir } => {
+                log::info!("Loading Vosk model from {}", dir.display());
+                Recognizer::Vosk(crate::vosk::Recognizer::new(dir)?)
+            }
+            #[cfg(not(feature = "vosk"))]
+            ModelPaths::Vosk { .. } => {
+                anyhow::bail!("backend = \"vosk\" needs a build with `--features vosk`")
+            }
+            ModelPaths::Cloud(settings) => {
+                log::warn!(
+                    "backend = \"cloud\": recordings are sent to {} ({})",
+                    settings.service,
+                    settings.endpoint
+                );
+                Recognizer::Cloud(crate::cloud::Client::new(settings.clone()))
+            }
         };
-        log::info!("Loading sherpa transducer model");
-        let recognizer = TransducerRecognizer::new(config)
-            .map_err(|e| anyhow::anyhow!("Failed to create sherpa recognizer: {e}"))?;
         Ok(Self { recognizer })
     }
 
+    /// Transcribes a short quiet clip so onnxruntime finishes its lazy initialization
+    /// and allocations before the first real utterance.
+    fn warm_up(&mut self) {
+        if matches!(self.recognizer, Recognizer::Cloud(_)) {
+            // Nothing to initialize locally, and every request is billed.
+            return;
+        }
+        let audio = synthetic_clip(WARMUP_SECS);
+        let started = Instant::now();
+        match self.transcribe(&audio) {
+            Ok(_) => log::info!("Model warmup took {}ms", started.elapsed().as_millis()),
+            Err(err) => log::warn!("Model warmup failed: {err:#}"),
+        }
+    }
+
     fn transcribe(&mut self, audio: &[f32]) -> Result<String> {
-        let text = self.recognizer.transcribe(16000, audio);
+        self.transcribe_progressive(audio, None)
+    }
+
+    /// Transcribes `audio`. With `early`, each segment of a long Whisper recording
+    /// but the last is handed to it as soon as it is decoded, and only the last is
+    /// returned.
+    fn transcribe_progressive(
+        &mut self,
+        audio: &[f32],
+        mut early: Option<&mut EarlySegment>,
+    ) -> Result<String> {
+        let text = match &mut self.recognizer {
+            Recognizer::Transducer(recognizer) => recognizer.transcribe(16000, audio),
+            Recognizer::Whisper(recognizer) => {
+                if audio.len() > WHISPER_WINDOW_SECS * SAMPLE_RATE as usize {
+                    log::info!(
+                        "Recording is longer than Whisper's {WHISPER_WINDOW_SECS}s window; transcribing it in segments"
+                    );
+                    let mut texts: Vec<String> = Vec::new();
+                    for (start, end) in media::segments(audio) {
+                        let text = recognizer.transcribe(16000, &audio[start..end]).text;
+                        let text = text.trim();
+                        if text.is_empty() {
+                            continue;
+                        }
+                        if let Some(early) = early.as_mut() {
+                            if let Some(previous) = texts.pop() {
+                                early(previous);
+                            }
+                        }
+                        texts.push(text.to_string());
+                    }
+                    texts.join(" ")
+                } else {
+                    recognizer.transcribe(16000, audio).text
+                }
+            }
+            Recognizer::Moonshine(recognizer) => recognizer.transcribe(16000, audio).text,
+            #[cfg(feature = "vosk")]
+            Recognizer::Vosk(recognizer) => recognizer.transcribe(audio)?,
+            Recognizer::Cloud(client) => client.transcribe(audio)?,
+        };
         Ok(text.trim().to_string())
     }
 }
 
-pub fn validate_model(paths: &crate::config::ModelPaths) -> Result<()> {
-    let _ = Transcriber::new(paths).with_context(|| {
+/// `secs` of faint noise rather than pure silence, so the decoder runs as it would on speech.
+fn synthetic_clip(secs: f32) -> Vec<f32> {
+    let samples = (SAMPLE_RATE as f32 * secs) as usize;
+    (0..samples)
+        .map(|i| ((i * 7919 % 2000) as f32 / 1000.0 - 1.0) * 0.01)
+        .collect()
+}
+
+/// Timings and output of a one-off end-to-end transcription.
+pub struct SampleRun {
+    pub load: Duration,
+    pub audio_secs: f64,
+    pub inference: Duration,
+    pub text: String,
+}
+
+/// Loads the model and transcribes a synthetic clip, for `whisp --check --deep`.
+pub fn transcribe_sample(
+    paths: &crate::config::ModelPaths,
+    provider: Provider,
+) -> Result<SampleRun> {
+    let started = Instant::now();
+    let mut transcriber = Transcriber::new(paths, provider)?;
+    let load = started.elapsed();
+    let audio = synthetic_clip(1.0);
+    let started = Instant::now();
+    let text = transcriber.transcribe(&audio)?;
+    Ok(SampleRun {
+        load,
+        audio_secs: audio.len() as f64 / SAMPLE_RATE as f64,
+        inference: started.elapsed(),
+        text,
+    })
+}
+
+/// A loaded model used directly, outside the hotkey pipeline's worker thread.
+pub struct ClipTranscriber(Transcriber);
+
+impl ClipTranscriber {
+    pub fn new(paths: &crate::config::ModelPaths, provider: Provider) -> Result<Self> {
+        Ok(Self(Transcriber::new(paths, provider)?))
+    }
+
+    pub fn transcribe(&mut self, audio: &[f32]) -> Result<String> {
+        self.0.transcribe(audio)
+    }
+}
+
+/// Loads the model once and transcribes each clip in turn, for `whisp --transcribe`.
+/// `progress` is called with the number of clips done so far.
+pub fn transcribe_clips(
+    paths: &crate::config::ModelPaths,
+    provider: Provider,
+    clips: &[&[f32]],
+    mut progress: impl FnMut(usize),
+) -> Result<Vec<String>> {
+    let mut transcriber = ClipTranscriber::new(paths, provider)?;
+    let mut texts = Vec::with_capacity(clips.len());
+    for clip in clips {
+        texts.push(transcriber.transcribe(clip)?);
+        progress(texts.len());
+    }
+    Ok(texts)
+}
+
+/// Recordings waiting for the second pass; more are skipped rather than queued.
+const SECOND_PASS_QUEUE: usize = 2;
+
+/// Re-transcribes recordings with a slower, more accurate model on its own thread
+/// and shows the result in a notification when it differs from what was typed.
+pub struct SecondPass {
+    tx: mpsc::SyncSender<(Vec<f32>, String)>,
+}
+
+impl SecondPass {
+    pub fn spawn(paths: &ModelPaths, provider: Provider) -> Result<Self> {
+        let mut transcriber = Transcriber::new(paths, provider).with_context(|| {
+            format!("Failed to load second_pass_model from {}", paths.location())
+        })?;
+        let (tx, rx) = mpsc::sync_channel::<(Vec<f32>, String)>(SECOND_PASS_QUEUE);
+        thread::spawn(move || {
+            for (audio, typed) in rx {
+                let better = match transcriber.transcribe(&audio) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        log::warn!("Second pass failed: {err:#}");
+                        continue;
+                    }
+                };
+                if better.is_empty() || Score::new(&better, &typed).word_errors == 0 {
+                    continue;
+                }
+                log::info!("Second pass: {}", logging::redact(&better));
+                util::notify("whisp: second pass", &better);
+            }
+        });
+        Ok(Self { tx })
+    }
+
+    /// Queues `audio`, whose first-pass transcription was `typed`.
+    fn submit(&self, audio: Vec<f32>, typed: String) {
+        if self.tx.try_send((audio, typed)).is_err() {
+            log::debug!("Second pass busy, skipping a recording");
+        }
+    }
+}
+
+/// State of the transcription thread that survives a worker restart.
+struct Worker {
+    paths: crate::config::ModelPaths,
+    audio_rx: mpsc::Receiver<AudioJob>,
+    output_tx: mpsc::SyncSender<OutputCommand>,
+    stats: Arc<Mutex<SessionStats>>,
+    options: Options,
+    queue: VecDeque<AudioJob>,
+    second_pass: Option<SecondPass>,
+}
+
+impl Worker {
+    /// Transcribes recordings until the audio channel closes.
+    fn run(&mut self, mut transcriber: Transcriber) -> Result<()> {
+        loop {
+            if self.queue.is_empty() {
+                match self.audio_rx.recv() {
+                    Ok(job) => self.queue.push_back(job),
+                    Err(_) => {
+                        log::debug!("Audio channel closed, transcriber shutting down");
+                        return Ok(());
+                    }
+                }
+            }
+
+            // Pick up everything recorded meanwhile so the policy sees the newest job.
+            while let Ok(a) = self.audio_rx.try_recv() {
+                self.queue.push_back(a);
+                if self.queue.len() > MAX_QUEUE {
+                    log::warn!("Transcription queue overflow, dropping oldest recording");
+                    self.queue.pop_front();
+                }
+            }
+
+            self.stats.lock().unwrap().queue_depth = self.queue.len();
+            let Some(job) = self.options.queue_policy.next(&mut self.queue) else {
+                continue;
+            };
+            let started = Instant::now();
+            let queue_wait = started.duration_since(job.queued_at);
+            let audio_secs = job.audio.len() as f64 / SAMPLE_RATE as f64;
+            let second_audio = self.second_pass.as_ref().map(|_| job.audio.clone());
+            // Set once a segment was typed early; cleared when the job is abandoned so
+            // a timed-out inference types nothing more.
+            let progress = Arc::new(Mutex::new(Some(false)));
+            let early = self
+                .options
+                .progressive
+                .then(|| self.early_segments(progress.clone(), started, queue_wait));
+            let (result, reclaimed) =
+                transcribe_job(transcriber, job.audio, self.options.timeout, early);
+            let continuation = progress.lock().unwrap().take() == Some(true);
+            let metrics = UtteranceMetrics::new(audio_secs, queue_wait, started.elapsed());
+            match result {
+                Ok(text) if !text.is_empty() => {
+                    // `text` is only the tail of a recording typed progressively.
+                    if let (Some(pass), Some(audio), false) =
+                        (&self.second_pass, second_audio, continuation)
+                    {
+                        pass.submit(audio, text.clone());
+                    }
+                    let _ = self.output_tx.send(OutputCommand::Emit(Transcript {
+                        text,
+                        metrics,
+                        continuation,
+                    }));
+                }
+                Ok(_) => log::debug!("Empty transcription result ({metrics})"),
+                Err(e) => {
+                    log::error!("Transcription error: {e}");
+                    self.stats.lock().unwrap().record_error();
+                    if reclaimed.is_none() {
+                        util::notify("whisp", &format!("Recording skipped: {e}"));
+                    }
+                }
+            }
+            self.stats.lock().unwrap().queue_depth = self.queue.len();
+
+            transcriber = match reclaimed {
+                Some(transcriber) => transcriber,
+                // The timed-out inference still owns the old recognizer, which stays in
+                // memory alongside the reloaded one until that inference returns.
+                None => self.reload().context("Failed to reload the model")?,
+            };
+        }
+    }
+
+    /// Types segments of the current recording as they finish. Their metrics carry
+    /// no audio length; the recording's last segment accounts for all of it.
+    fn early_segments(
+        &self,
+        progress: Arc<Mutex<Option<bool>>>,
+        started: Instant,
+        queue_wait: Duration,
+    ) -> Box<EarlySegment> {
+        let output_tx = self.output_tx.clone();
+        Box::new(move |text| {
+            let mut progress = progress.lock().unwrap();
+            let Some(continuation) = *progress else {
+                return;
+            };
+            *progress = Some(true);
+            let _ = output_tx.send(OutputCommand::Emit(Transcript {
+                text,
+                metrics: UtteranceMetrics::new(0.0, queue_wait, started.elapsed()),
+                continuation,
+            }));
+        })
+    }
+
+    fn reload(&self) -> Result<Transcriber> {
+        log::info!("Reloading the model");
+        Transcriber::new(&self.paths, self.options.provider)
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+/// Runs one transcription, giving up after `timeout`. An abandoned inference keeps
+/// the transcriber, so None is returned in its place and the caller must reload.
+fn transcribe_job(
+    mut transcriber: Transcriber,
+    audio: Vec<f32>,
+    timeout: Option<Duration>,
+    mut early: Option<Box<EarlySegment>>,
+) -> (Result<String>, Option<Transcriber>) {
+    let Some(timeout) = timeout else {
+        let result = transcriber.transcribe_progressive(&audio, early.as_deref_mut());
+        return (result, Some(transcriber));
+    };
+    // sherpa-onnx can't be interrupted, so run it where it can be left behind.
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = transcriber.transcribe_progressive(&audio, early.as_deref_mut());
+        let _ = tx.send((result, transcriber));
+    });
+    match rx.recv_timeout(timeout) {
+        Ok((result, transcriber)) => (result, Some(transcriber)),
+        Err(mpsc::RecvTimeoutError::Timeout) => (
+            Err(anyhow!(
+                "transcription timed out after {}s",
+                timeout.as_secs_f64()
+            )),
+            None,
+        ),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            (Err(anyhow!("transcription thread panicked")), None)
+        }
+    }
+}
+
+pub fn validate_model(paths: &crate::config::ModelPaths, provider: Provider) -> Result<()> {
+    let _ = Transcriber::new(paths, provider).with_context(|| {
         format!(
             "Failed to load model from {}. Try deleting ~/.cache/huggingface and re-running.",
-            paths.encoder.display()
+            paths.location()
         )
     })?;
     Ok(())
@@ -53,47 +642,71 @@ pub fn validate_model(paths: &crate::config::ModelPaths) -> Result<()> {
 /// This validates the model before spawning the thread to provide immediate feedback.
 pub fn spawn_worker(
     paths: crate::config::ModelPaths,
-    audio_rx: mpsc::Receiver<Vec<f32>>,
-    text_tx: mpsc::Sender<String>,
+    audio_rx: mpsc::Receiver<AudioJob>,
+    output_tx: mpsc::SyncSender<OutputCommand>,
+    stats: Arc<Mutex<SessionStats>>,
+    options: Options,
+    second_pass: Option<SecondPass>,
 ) -> Result<JoinHandle<()>> {
     // Validate model loads BEFORE spawning thread for immediate error feedback
-    let transcriber = Transcriber::new(&paths).with_context(|| {
+    let transcriber = Transcriber::new(&paths, options.provider).with_context(|| {
         format!(
             "Failed to load model from {}. Try deleting ~/.cache/huggingface and re-running.",
-            paths.encoder.display()
+            paths.location()
         )
     })?;
 
     let handle = thread::spawn(move || {
         let mut transcriber = transcriber;
+        if options.warmup {
+            transcriber.warm_up();
+        }
         log::info!("Transcription worker ready");
 
-        let mut queue: VecDeque<Vec<f32>> = VecDeque::with_capacity(MAX_QUEUE);
+        let mut worker = Worker {
+            paths,
+            audio_rx,
+            output_tx,
+            stats,
+            options,
+            queue: VecDeque::with_capacity(MAX_QUEUE),
+            second_pass,
+        };
+        let mut transcriber = Some(transcriber);
+        let mut restarts = 0;
         loop {
-            let audio = match audio_rx.recv() {
-                Ok(a) => a,
-                Err(_) => {
-                    log::debug!("Audio channel closed, transcriber shutting down");
-                    break;
-                }
+            let current = match transcriber.take() {
+                Some(transcriber) => transcriber,
+                None => match worker.reload() {
+                    Ok(transcriber) => transcriber,
+                    Err(err) => {
+                        log::error!("Failed to reload the model: {err:#}");
+                        util::notify("whisp", "Transcription stopped: the model failed to reload");
+                        break;
+                    }
+                },
             };
-            queue.push_back(audio);
-
-            while let Ok(a) = audio_rx.try_recv() {
-                queue.push_back(a);
-                if queue.len() > MAX_QUEUE {
-                    log::warn!("Transcription queue overflow, dropping oldest recording");
-                    queue.pop_front();
+            match panic::catch_unwind(AssertUnwindSafe(|| worker.run(current))) {
+                Ok(Ok(())) => break,
+                Ok(Err(err)) => {
+                    log::error!("{err:#}");
+                    util::notify("whisp", &format!("Transcription stopped: {err:#}"));
+                    break;
                 }
-            }
-
-            while let Some(audio) = queue.pop_front() {
-                match transcriber.transcribe(&audio) {
-                    Ok(text) if !text.is_empty() => {
-                        let _ = text_tx.send(text);
+                Err(panic) => {
+                    worker.stats.lock().unwrap().record_error();
+                    restarts += 1;
+                    if restarts > MAX_RESTARTS {
+                        log::error!(
+                            "Transcription worker panicked {restarts} times, giving up: {}",
+                            panic_message(&panic)
+                        );
+                        break;
                     }
-                    Ok(_) => log::debug!("Empty transcription result"),
-                    Err(e) => log::error!("Transcription error: {e}"),
+                    log::error!(
+                        "Transcription worker panicked: {}. Restarting ({restarts}/{MAX_RESTARTS})",
+                        panic_message(&panic)
+                    );
                 }
             }
         }
@@ -101,3 +714,33 @@ pub fn spawn_worker(
 
     Ok(handle)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{panic_message, AudioJob, QueuePolicy};
+    use std::collections::VecDeque;
+
+    fn queue() -> VecDeque<AudioJob> {
+        (1..=3).map(|n| AudioJob::new(vec![n as f32])).collect()
+    }
+
+    fn order(policy: QueuePolicy) -> Vec<f32> {
+        let mut queue = queue();
+        std::iter::from_fn(|| policy.next(&mut queue))
+            .map(|job| job.audio[0])
+            .collect()
+    }
+
+    #[test]
+    fn queue_policies_pick_jobs_in_order() {
+        assert_eq!(order(QueuePolicy::OldestFirst), [1.0, 2.0, 3.0]);
+        assert_eq!(order(QueuePolicy::NewestFirst), [3.0, 2.0, 1.0]);
+        assert_eq!(order(QueuePolicy::LatestOnly), [3.0]);
+    }
+
+    #[test]
+    fn reads_panic_messages() {
+        let panic = std::panic::catch_unwind(|| panic!("bad {}", "input")).unwrap_err();
+        assert_eq!(panic_message(&*panic), "bad input");
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Gauge, List, ListItem, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::audio::AudioBuffer;
+use crate::metrics::SessionStats;
+use crate::output::{self, OutputCommand};
+
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Full-screen `--tui` dashboard. Reads the shared pipeline state and redraws it
+/// until `shutdown` is set (by Ctrl+C or the quit key).
+pub struct Dashboard {
+    pub hotkey: String,
+    pub model: String,
+    pub stats: Arc<Mutex<SessionStats>>,
+    pub audio: Arc<Mutex<AudioBuffer>>,
+    /// While set, the record hotkey is ignored.
+    pub paused: Arc<AtomicBool>,
+    pub shutdown: Arc<AtomicBool>,
+    pub output_tx: mpsc::SyncSender<OutputCommand>,
+}
+
+/// Takes over the terminal on a separate thread. Join the handle before printing
+/// anything else so the terminal is restored first.
+pub fn spawn(dashboard: Dashboard) -> JoinHandle<Result<()>> {
+    thread::spawn(move || {
+        let mut terminal = ratatui::init();
+        let result = dashboard.run(&mut terminal);
+        ratatui::restore();
+        // Also stop the main loop if the dashboard failed on its own.
+        dashboard.shutdown.store(true, Ordering::SeqCst);
+        result
+    })
+}
+
+impl Dashboard {
+    fn run(&self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.shutdown.load(Ordering::SeqCst) {
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(REFRESH)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            // Transcriptions may be typed into this terminal, so only keys the virtual
+            // keyboard never sends (function keys, Ctrl+C) act as commands.
+            match key.code {
+                KeyCode::F(10) => self.shutdown.store(true, Ordering::SeqCst),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.shutdown.store(true, Ordering::SeqCst)
+                }
+                KeyCode::F(2) => {
+                    self.paused.fetch_xor(true, Ordering::SeqCst);
+                }
+                KeyCode::F(3) => {
+                    output::send_control(&self.output_tx, OutputCommand::ToggleNumeric);
+                }
+                KeyCode::F(4) => {
+                    output::send_control(&self.output_tx, OutputCommand::UndoLast);
+                }
+                KeyCode::F(5) => {
+                    output::send_control(&self.output_tx, OutputCommand::RetryFailed);
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let (recording, level) = {
+            let audio = self.audio.lock().unwrap();
+            (audio.recording, audio.level())
+        };
+        let stats = self.stats.lock().unwrap();
+
+        let [status_area, level_area, stats_area, recent_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Length(4),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let (state, color) = if self.paused.load(Ordering::SeqCst) {
+            ("PAUSED".to_string(), Color::DarkGray)
+        } else if recording {
+            ("RECORDING".to_string(), Color::Red)
+        } else if stats.queue_depth > 0 {
+            (
+                format!("TRANSCRIBING ({} queued)", stats.queue_depth),
+                Color::Yellow,
+            )
+        } else {
+            ("IDLE".to_string(), Color::Green)
+        };
+        let status = Line::from(vec![
+            state.bold().fg(color),
+            format!("   hotkey: {}   model: {}", self.hotkey, self.model).into(),
+        ]);
+        frame.render_widget(
+            Paragraph::new(status).block(Block::bordered().title(" whisp ")),
+            status_area,
+        );
+
+        let level = if recording { level } else { 0.0 };
+        frame.render_widget(
+            Gauge::default()
+                .block(Block::bordered().title(" Input level "))
+                .gauge_style(Style::default().fg(Color::Cyan))
+                .ratio(f64::from(level)),
+            level_area,
+        );
+
+        let last_latency = stats.recent.back().map_or(0, |(_, ms)| *ms);
+        let summary = vec![
+            Line::from(format!(
+                "Utterances: {}   Words: {}   Audio: {:.1}s   Errors: {}",
+                stats.utterances, stats.words, stats.audio_secs, stats.errors
+            )),
+            Line::from(format!(
+                "Latency: last {last_latency}ms, average {}ms   Queue depth: {}",
+                stats.average_latency_ms(),
+                stats.queue_depth
+            )),
+        ];
+        frame.render_widget(
+            Paragraph::new(summary).block(Block::bordered().title(" Session ")),
+            stats_area,
+        );
+
+        let recent: Vec<ListItem> = stats
+            .recent
+            .iter()
+            .rev()
+            .map(|(text, ms)| ListItem::new(format!("{ms:>6}ms  {text}")))
+            .collect();
+        frame.render_widget(
+            List::new(recent).block(Block::bordered().title(" Recent transcriptions ")),
+            recent_area,
+        );
+
+        frame.render_widget(
+            Paragraph::new(
+                "F2 pause/resume   F3 numeric mode   F4 undo last   F5 retry   F10 quit",
+            )
+            .fg(Color::DarkGray),
+            help_area,
+        );
+    }
+}
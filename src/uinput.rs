@@ -1,13 +1,25 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use evdev::uinput::VirtualDeviceBuilder;
 use evdev::{AttributeSet, EventType, InputEvent, Key};
 use std::thread;
 use std::time::Duration;
 
+use crate::keymap::{self, Keymap, Level};
+use crate::logging;
+
 const INTER_EVENT_DELAY: Duration = Duration::from_millis(2);
 
+/// Name of the uinput device, so the hotkey listener can skip it.
+pub const DEVICE_NAME: &str = "whisp-virtual-keyboard";
+
 pub struct VirtualKeyboard {
     device: evdev::uinput::VirtualDevice,
+    /// Type characters missing from the keymap with the Ctrl+Shift+U hex sequence (GTK/IBus).
+    unicode_input: bool,
+    /// The desktop's keyboard layout, so characters land on the right keys.
+    keymap: Keymap,
+    /// The desktop's compose key, for accented characters the layout lacks.
+    compose_key: Option<Key>,
 }
 
 impl VirtualKeyboard {
@@ -19,7 +31,7 @@ impl VirtualKeyboard {
 
         let device = VirtualDeviceBuilder::new()
             .context("failed to open /dev/uinput")?
-            .name("whisp-virtual-keyboard")
+            .name(DEVICE_NAME)
             .with_keys(&keys)
             .context("failed to register key capabilities")?
             .build()
@@ -28,44 +40,119 @@ impl VirtualKeyboard {
         // Give udev time to create the device node and compositors time to recognize it.
         thread::sleep(Duration::from_millis(100));
 
-        Ok(Self { device })
+        Ok(Self {
+            device,
+            unicode_input: false,
+            keymap: Keymap::us(),
+            compose_key: None,
+        })
+    }
+
+    /// Enable Ctrl+Shift+U Unicode entry for characters without a key mapping.
+    pub fn set_unicode_input(&mut self, enabled: bool) {
+        self.unicode_input = enabled;
+    }
+
+    /// Use the desktop's layout instead of US when choosing keys.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    /// Type accented Latin characters the keymap lacks as compose sequences.
+    pub fn set_compose_key(&mut self, key: Option<Key>) {
+        self.compose_key = key;
     }
 
     /// Type text by sending individual key events.
-    /// Supports ASCII printable characters. Non-mappable characters are skipped with a warning.
-    pub fn type_text(&mut self, text: &str) -> Result<()> {
+    /// Supports the characters of the keymap, accented Latin characters via the compose
+    /// key when one is set, and any character via Ctrl+Shift+U when Unicode input is
+    /// enabled. Non-mappable characters are skipped with a warning.
+    /// Returns the number of characters actually typed.
+    pub fn type_text(&mut self, text: &str) -> Result<usize> {
+        let mut typed = 0;
         for ch in text.chars() {
-            if let Some((key, shift)) = char_to_key(ch) {
-                if shift {
-                    self.device
-                        .emit(&[InputEvent::new(EventType::KEY, Key::KEY_LEFTSHIFT.code(), 1)])
-                        .context("failed to press shift")?;
-                    thread::sleep(INTER_EVENT_DELAY);
-                }
-
-                self.device
-                    .emit(&[InputEvent::new(EventType::KEY, key.code(), 1)])
-                    .context("failed to press key")?;
-                thread::sleep(INTER_EVENT_DELAY);
-                self.device
-                    .emit(&[InputEvent::new(EventType::KEY, key.code(), 0)])
-                    .context("failed to release key")?;
-                thread::sleep(INTER_EVENT_DELAY);
-
-                if shift {
-                    self.device
-                        .emit(&[InputEvent::new(
-                            EventType::KEY,
-                            Key::KEY_LEFTSHIFT.code(),
-                            0,
-                        )])
-                        .context("failed to release shift")?;
-                    thread::sleep(INTER_EVENT_DELAY);
+            if let Some((key, level)) = self.keymap.get(ch) {
+                self.tap(key, level)?;
+                typed += 1;
+            } else if let Some(sequence) = self.compose_sequence(ch) {
+                for (key, level) in sequence {
+                    self.tap(key, level)?;
                 }
+                typed += 1;
+            } else if self.unicode_input {
+                self.type_unicode(ch)?;
+                typed += 1;
             } else {
-                log::warn!("uinput: no key mapping for character '{ch}' (U+{:04X}), skipping", ch as u32);
+                log::warn!(
+                    "uinput: no key mapping for character '{}', skipping",
+                    logging::redact(ch.encode_utf8(&mut [0; 4]))
+                );
             }
         }
+        Ok(typed)
+    }
+
+    /// Compose key followed by the keys of `ch`'s compose sequence, if all exist.
+    fn compose_sequence(&self, ch: char) -> Option<[(Key, Level); 3]> {
+        let compose = self.compose_key?;
+        let [first, second] = keymap::compose_sequence(ch)?;
+        Some([
+            (compose, Level::Base),
+            self.keymap.get(first)?,
+            self.keymap.get(second)?,
+        ])
+    }
+
+    /// Send `count` backspace presses.
+    pub fn backspace(&mut self, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.tap(Key::KEY_BACKSPACE, Level::Base)?;
+        }
+        Ok(())
+    }
+
+    /// Enter a code point as Ctrl+Shift+U, its hex digits, then Space, which GTK
+    /// and IBus input methods turn into the character.
+    fn type_unicode(&mut self, ch: char) -> Result<()> {
+        let Some((u, _)) = self.keymap.get('u') else {
+            bail!("keymap has no 'u' key for Ctrl+Shift+U entry");
+        };
+        self.set_key(Key::KEY_LEFTCTRL, true)?;
+        self.tap(u, Level::Shift)?;
+        self.set_key(Key::KEY_LEFTCTRL, false)?;
+        for digit in format!("{:x}", ch as u32).chars() {
+            let Some((key, level)) = self.keymap.get(digit) else {
+                bail!("keymap has no key for '{digit}' in Ctrl+Shift+U entry");
+            };
+            self.tap(key, level)?;
+        }
+        self.tap(Key::KEY_SPACE, Level::Base)
+    }
+
+    fn set_key(&mut self, key: Key, pressed: bool) -> Result<()> {
+        self.device
+            .emit(&[InputEvent::new(EventType::KEY, key.code(), pressed as i32)])
+            .with_context(|| format!("failed to send {key:?}"))?;
+        thread::sleep(INTER_EVENT_DELAY);
+        Ok(())
+    }
+
+    /// Press and release a single key, holding the modifiers for `level`.
+    fn tap(&mut self, key: Key, level: Level) -> Result<()> {
+        let modifiers: &[Key] = match level {
+            Level::Base => &[],
+            Level::Shift => &[Key::KEY_LEFTSHIFT],
+            Level::AltGr => &[Key::KEY_RIGHTALT],
+            Level::ShiftAltGr => &[Key::KEY_LEFTSHIFT, Key::KEY_RIGHTALT],
+        };
+        for modifier in modifiers {
+            self.set_key(*modifier, true)?;
+        }
+        self.set_key(key, true)?;
+        self.set_key(key, false)?;
+        for modifier in modifiers.iter().rev() {
+            self.set_key(*modifier, false)?;
+        }
         Ok(())
     }
 }
@@ -73,15 +160,12 @@ impl VirtualKeyboard {
 /// Check if /dev/uinput is accessible for writing.
 pub fn is_available() -> bool {
     use std::fs::OpenOptions;
-    OpenOptions::new()
-        .write(true)
-        .open("/dev/uinput")
-        .is_ok()
+    OpenOptions::new().write(true).open("/dev/uinput").is_ok()
 }
 
-/// Map a character to an evdev Key and whether Shift is required.
+/// Map a character to an evdev Key and whether Shift is required on a US layout.
 /// Returns None for unmappable characters (non-ASCII, special Unicode).
-fn char_to_key(ch: char) -> Option<(Key, bool)> {
+pub(crate) fn char_to_key(ch: char) -> Option<(Key, bool)> {
     Some(match ch {
         'a' => (Key::KEY_A, false),
         'b' => (Key::KEY_B, false),
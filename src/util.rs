@@ -1,4 +1,8 @@
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 pub fn has_command(name: &str) -> bool {
     Command::new(name)
@@ -9,3 +13,67 @@ pub fn has_command(name: &str) -> bool {
         .status()
         .is_ok()
 }
+
+/// Whether logind reports the current session as locked (its `LockedHint`). None
+/// when that can't be determined, e.g. without `loginctl` or outside a session.
+pub fn session_locked() -> Option<bool> {
+    let session = std::env::var("XDG_SESSION_ID").unwrap_or_else(|_| "auto".into());
+    let output = Command::new("loginctl")
+        .args(["show-session", &session, "--property=LockedHint", "--value"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    match String::from_utf8_lossy(&output.stdout).trim() {
+        "yes" => Some(true),
+        "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// How often `watch_session_lock` asks logind.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Follows [`session_locked`] on a background thread, so checking it costs no
+/// process start. None when the lock state can't be read at all.
+pub fn watch_session_lock() -> Option<Arc<AtomicBool>> {
+    let locked = Arc::new(AtomicBool::new(session_locked()?));
+    let watched = locked.clone();
+    thread::spawn(move || loop {
+        thread::sleep(LOCK_POLL_INTERVAL);
+        if let Some(state) = session_locked() {
+            watched.store(state, Ordering::Relaxed);
+        }
+    });
+    Some(locked)
+}
+
+/// Shows a desktop notification through `notify-send` without blocking. Does
+/// nothing when it isn't installed.
+pub fn notify(summary: &str, body: &str) {
+    let (summary, body) = (summary.to_string(), body.to_string());
+    thread::spawn(move || {
+        let result = Command::new("notify-send")
+            .args(["--app-name", "whisp", "--"])
+            .arg(&summary)
+            .arg(&body)
+            .status();
+        if let Err(err) = result {
+            log::debug!("notify-send failed: {err}");
+        }
+    });
+}
+
+/// Waits for `handle` until `deadline`. Returns false if the thread panicked or is
+/// still running by then (it is left running).
+pub fn join_until<T>(handle: JoinHandle<T>, deadline: Instant) -> bool {
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    handle.join().is_ok()
+}
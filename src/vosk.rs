@@ -0,0 +1,45 @@
+use anyhow::{anyhow, Result};
+use std::path::Path;
+
+use crate::audio::SAMPLE_RATE;
+
+/// Kaldi recognizer from libvosk for `backend = "vosk"`: small models that run on
+/// very constrained hardware.
+pub struct Recognizer {
+    // Vosk reference-counts the model, but keep it alive alongside its recognizer.
+    _model: vosk::Model,
+    recognizer: vosk::Recognizer,
+}
+
+impl Recognizer {
+    /// Loads an unpacked Vosk model directory (the one holding `am/` and `conf/`).
+    pub fn new(dir: &Path) -> Result<Self> {
+        vosk::set_log_level(vosk::LogLevel::Warn);
+        let model = vosk::Model::new(dir.to_string_lossy())
+            .ok_or_else(|| anyhow!("Vosk could not load a model from {}", dir.display()))?;
+        let recognizer = vosk::Recognizer::new(&model, SAMPLE_RATE as f32)
+            .ok_or_else(|| anyhow!("Vosk could not create a recognizer"))?;
+        Ok(Self {
+            _model: model,
+            recognizer,
+        })
+    }
+
+    pub fn transcribe(&mut self, audio: &[f32]) -> Result<String> {
+        let samples: Vec<i16> = audio
+            .iter()
+            .map(|s| (s.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+            .collect();
+        self.recognizer
+            .accept_waveform(&samples)
+            .map_err(|e| anyhow!("Vosk rejected the audio: {e}"))?;
+        // final_result also resets the recognizer for the next recording.
+        let text = self
+            .recognizer
+            .final_result()
+            .single()
+            .map(|result| result.text.to_string())
+            .unwrap_or_default();
+        Ok(text)
+    }
+}